@@ -0,0 +1,181 @@
+use lil_chess::engine::Engine;
+use lil_chess::evaluation::EvaluationWeights;
+use lil_chess::game_types::BoardState;
+use lil_chess::move_ordering::OrderingHeuristics;
+use lil_chess::search::{CancellationToken, SearchLimits, Searcher, Selectivity};
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: lilchess-bench [depth]";
+const DEFAULT_DEPTH: u32 = 7;
+const HASH_SIZE_MB: usize = 16;
+// openings, middlegames and endgames, quiet and tactical
+const POSITIONS: [&str; 8] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+    "r2q1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP3PPP/R2QKB1R w KQ - 0 9",
+    "r1b2rk1/2q1bppp/p2ppn2/1p6/3BPP2/2N2B2/PPP3PP/R2Q1R1K w - - 0 14",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "8/8/4k3/3p4/3P4/4K3/8/8 w - - 0 1",
+];
+
+// searches a fixed set of positions to a fixed depth, first with only the hash move and captures
+// ordered and then adding the heuristics for quiet moves one by one, then from a plain alpha-beta
+// search adding the pruning, reductions and extensions one by one, and compares the nodes it takes
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    let depth = match args.as_slice() {
+        [] => DEFAULT_DEPTH,
+        [depth] => match depth.parse::<u32>() {
+            Ok(depth) => depth,
+            Err(_) => {
+                eprintln!("'{}' is not a depth\n{}", depth, USAGE);
+                std::process::exit(1);
+            }
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    };
+    let ordering_configurations = [
+        ("captures only", OrderingHeuristics::none()),
+        (
+            "+ killer moves",
+            OrderingHeuristics {
+                killer_moves: true,
+                ..OrderingHeuristics::none()
+            },
+        ),
+        (
+            "+ history",
+            OrderingHeuristics {
+                counter_moves: false,
+                ..OrderingHeuristics::default()
+            },
+        ),
+        ("+ counter moves", OrderingHeuristics::default()),
+    ];
+    let selectivity_configurations = [
+        ("alpha-beta", Selectivity::none()),
+        (
+            "+ null move",
+            Selectivity {
+                null_move_pruning: true,
+                ..Selectivity::none()
+            },
+        ),
+        (
+            "+ reductions",
+            Selectivity {
+                null_move_pruning: true,
+                late_move_reductions: true,
+                ..Selectivity::none()
+            },
+        ),
+        (
+            "+ futility",
+            Selectivity {
+                check_extensions: false,
+                aspiration_windows: false,
+                ..Selectivity::default()
+            },
+        ),
+        (
+            "+ extensions",
+            Selectivity {
+                aspiration_windows: false,
+                ..Selectivity::default()
+            },
+        ),
+        ("+ aspiration", Selectivity::default()),
+    ];
+    let evaluation_weights = Engine::load_evaluation_weights();
+    let ordering_totals = ordering_configurations
+        .iter()
+        .map(|(name, heuristics)| {
+            let nodes = run(
+                name,
+                *heuristics,
+                Selectivity::none(),
+                depth,
+                &evaluation_weights,
+            );
+            (*name, nodes)
+        })
+        .collect::<Vec<_>>();
+    let selectivity_totals = selectivity_configurations
+        .iter()
+        .map(|(name, selectivity)| {
+            let nodes = run(
+                name,
+                OrderingHeuristics::default(),
+                *selectivity,
+                depth,
+                &evaluation_weights,
+            );
+            (*name, nodes)
+        })
+        .collect::<Vec<_>>();
+    print_totals(&ordering_totals);
+    println!();
+    print_totals(&selectivity_totals);
+}
+// the total nodes it takes to search all the positions
+fn run(
+    name: &str,
+    heuristics: OrderingHeuristics,
+    selectivity: Selectivity,
+    depth: u32,
+    evaluation_weights: &EvaluationWeights,
+) -> u64 {
+    println!("{}", name);
+    let mut total_nodes = 0;
+    let mut total_time = Duration::from_secs(0);
+    for fen in POSITIONS.iter() {
+        let board = BoardState::from_fen(fen).unwrap();
+        let mut searcher = Searcher::new(HASH_SIZE_MB);
+        searcher.evaluation_weights = evaluation_weights.clone();
+        searcher.move_ordering.heuristics = heuristics;
+        searcher.selectivity = selectivity;
+        let start = Instant::now();
+        let result = searcher.search(
+            &board,
+            &SearchLimits::depth(depth),
+            &CancellationToken::new(),
+        );
+        let elapsed = start.elapsed();
+        let best_move = result
+            .best_move
+            .map_or("none".to_owned(), |mv| mv.to_long_algebraic());
+        println!(
+            "  {:>10} nodes {:>8.3}s  {} {}",
+            result.nodes,
+            elapsed.as_secs_f64(),
+            best_move,
+            fen
+        );
+        total_nodes += result.nodes;
+        total_time += elapsed;
+    }
+    println!(
+        "  {:>10} nodes {:>8.3}s  in total\n",
+        total_nodes,
+        total_time.as_secs_f64()
+    );
+    total_nodes
+}
+// compared to the first configuration
+fn print_totals(totals: &[(&str, u64)]) {
+    let baseline = totals[0].1.max(1) as f64;
+    for (name, nodes) in totals.iter() {
+        println!(
+            "{:<16} {:>10} nodes ({:.0}% of {})",
+            name,
+            nodes,
+            *nodes as f64 * 100.0 / baseline,
+            totals[0].0
+        );
+    }
+}
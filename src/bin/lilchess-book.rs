@@ -0,0 +1,50 @@
+use lil_chess::book::{OpeningBook, DEFAULT_MAX_BOOK_DEPTH};
+use lil_chess::pgn::PgnReader;
+
+const USAGE: &str = "usage: lilchess-book <games.pgn> <book.bin> [max depth in plies]";
+
+// builds a Polyglot book out of the openings of a PGN collection
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    let (pgn_path, book_path, max_depth) = match args.as_slice() {
+        [pgn_path, book_path] => (pgn_path, book_path, None),
+        [pgn_path, book_path, max_depth] => (pgn_path, book_path, Some(max_depth)),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    };
+    let max_depth = match max_depth.map(|max_depth| max_depth.parse::<u32>()) {
+        None => DEFAULT_MAX_BOOK_DEPTH,
+        Some(Ok(max_depth)) => max_depth,
+        Some(Err(_)) => {
+            eprintln!("'{}' is not a depth\n{}", max_depth.unwrap(), USAGE);
+            std::process::exit(1);
+        }
+    };
+    let text = match std::fs::read_to_string(pgn_path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("Can't read '{}': {}", pgn_path, e);
+            std::process::exit(1);
+        }
+    };
+    let mut games = Vec::new();
+    for (i, game) in PgnReader::new(&text).enumerate() {
+        match game {
+            Ok(game) => games.push(game),
+            Err(e) => eprintln!("Skipping game {}: {}", i + 1, e),
+        }
+    }
+    let book = OpeningBook::from_games(games.iter(), max_depth);
+    if let Err(e) = book.save(book_path) {
+        eprintln!("Can't write '{}': {}", book_path, e);
+        std::process::exit(1);
+    }
+    println!(
+        "{} entries from {} games written to '{}'",
+        book.len(),
+        games.len(),
+        book_path
+    );
+}
@@ -0,0 +1,1043 @@
+use lil_chess::fen::STARTING_POSITION_FEN;
+use lil_chess::game_types::*;
+use lil_chess::pgn::{PgnGame, PgnReader};
+use lil_chess::search::MATE_SCORE;
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &str =
+    "usage: lilchess-match -engine [cmd=<path>] [name=<name>] [option.<name>=<value>]...
+                      -engine [cmd=<path>] [name=<name>] [option.<name>=<value>]...
+                      [-games <n>] [-openings <file.epd | file.pgn>]
+                      [-tc <seconds>[+<increment>] | -st <seconds per move>]
+                      [-draw movenumber=<n> movecount=<n> score=<cp>]
+                      [-resign movecount=<n> score=<cp>] [-maxmoves <n>]
+                      [-sprt elo0=<elo> elo1=<elo> [alpha=<a>] [beta=<b>]] [-pgnout <file.pgn>]
+An engine without cmd is lilchess-uci from next to this program.";
+const DEFAULT_GAMES: u32 = 100;
+const DEFAULT_TIME: Duration = Duration::from_secs(10);
+const DEFAULT_INCREMENT: Duration = Duration::from_millis(100);
+const DEFAULT_SPRT_ALPHA: f64 = 0.05;
+const DEFAULT_SPRT_BETA: f64 = 0.05;
+// how long an engine gets to start up and to answer isready
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+// how far past its time an engine may go before it loses on time, for the pipes to pass the move
+const TIME_MARGIN: Duration = Duration::from_millis(200);
+// the confidence of the error bars, in standard deviations
+const CONFIDENCE_Z: f64 = 1.96;
+
+struct EngineConfig {
+    command: Option<String>,
+    name: Option<String>,
+    options: Vec<(String, String)>,
+}
+#[derive(Clone, Copy)]
+enum TimeControl {
+    // the time for the whole game, and what's added after every move
+    Clock { time: Duration, increment: Duration },
+    MoveTime(Duration),
+}
+// games whose outcome is clear are ended early, to save time
+#[derive(Default)]
+struct Adjudication {
+    // a draw once both engines have scored the position within score of equal for movecount moves
+    // each, from movenumber on
+    draw: Option<(u32, usize, i32)>,
+    // a win once both engines agree by at least score for movecount moves each
+    resign: Option<(usize, i32)>,
+    // a draw after this many moves
+    max_moves: Option<u32>,
+}
+#[derive(Clone, Copy)]
+struct Sprt {
+    elo0: f64,
+    elo1: f64,
+    alpha: f64,
+    beta: f64,
+}
+struct Settings {
+    engines: Vec<EngineConfig>,
+    games: u32,
+    openings: Vec<GameHistory>,
+    time_control: TimeControl,
+    adjudication: Adjudication,
+    sprt: Option<Sprt>,
+    pgn_out: Option<String>,
+}
+
+#[derive(Debug)]
+enum EngineError {
+    Io(std::io::Error),
+    Timeout(&'static str),
+    Exited,
+    IllegalMove(String),
+}
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineError::Io(e) => write!(f, "can't talk to the engine: {}", e),
+            EngineError::Timeout(waiting_for) => write!(f, "no {} in time", waiting_for),
+            EngineError::Exited => write!(f, "the engine exited"),
+            EngineError::IllegalMove(mv) => write!(f, "'{}' is not a legal move", mv),
+        }
+    }
+}
+impl std::error::Error for EngineError {}
+impl From<std::io::Error> for EngineError {
+    fn from(e: std::io::Error) -> EngineError {
+        EngineError::Io(e)
+    }
+}
+
+// an engine running in its own process, talked to over UCI
+struct UciEngine {
+    name: String,
+    process: Child,
+    stdin: ChildStdin,
+    // the engine's output, line by line, read on another thread so that it can be waited on with a
+    // timeout
+    lines: mpsc::Receiver<String>,
+}
+impl UciEngine {
+    fn start(command: &str, options: &[(String, String)]) -> Result<UciEngine, EngineError> {
+        let mut process = Command::new(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = process.stdin.take().unwrap();
+        let stdout = process.stdout.take().unwrap();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut engine = UciEngine {
+            name: command.to_owned(),
+            process,
+            stdin,
+            lines: rx,
+        };
+        engine.send("uci")?;
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        loop {
+            let line = engine.read_line(deadline, "uciok")?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = name.trim().to_owned();
+            }
+            if line.trim() == "uciok" {
+                break;
+            }
+        }
+        for (name, value) in options.iter() {
+            engine.send(&format!("setoption name {} value {}", name, value))?;
+        }
+        engine.wait_until_ready()?;
+        Ok(engine)
+    }
+    fn send(&mut self, command: &str) -> Result<(), EngineError> {
+        writeln!(self.stdin, "{}", command)?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+    fn read_line(
+        &mut self,
+        deadline: Instant,
+        waiting_for: &'static str,
+    ) -> Result<String, EngineError> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match self.lines.recv_timeout(timeout) {
+            Ok(line) => Ok(line),
+            Err(mpsc::RecvTimeoutError::Timeout) => Err(EngineError::Timeout(waiting_for)),
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err(EngineError::Exited),
+        }
+    }
+    fn wait_until_ready(&mut self) -> Result<(), EngineError> {
+        self.send("isready")?;
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        while self.read_line(deadline, "readyok")?.trim() != "readyok" {}
+        Ok(())
+    }
+    fn new_game(&mut self) -> Result<(), EngineError> {
+        self.send("ucinewgame")?;
+        self.wait_until_ready()
+    }
+    // the engine's move, and the last score it gave for the position, from its own side
+    fn go(
+        &mut self,
+        history: &GameHistory,
+        go_command: &str,
+        deadline: Instant,
+    ) -> Result<(String, Option<i32>), EngineError> {
+        let mut position = format!("position fen {}", history.board_states[0].to_fen());
+        if !history.moves.is_empty() {
+            position.push_str(" moves");
+            for mv in history.moves.iter() {
+                position.push(' ');
+                position.push_str(&mv.to_long_algebraic());
+            }
+        }
+        self.send(&position)?;
+        self.send(go_command)?;
+        let mut score = None;
+        loop {
+            let line = self.read_line(deadline, "move")?;
+            let words = line.split_whitespace().collect::<Vec<&str>>();
+            match words.first() {
+                Some(&"bestmove") => match words.get(1) {
+                    Some(mv) => return Ok((mv.to_string(), score)),
+                    None => return Err(EngineError::IllegalMove(String::new())),
+                },
+                Some(&"info") => score = parse_score(&words).or(score),
+                _ => {}
+            }
+        }
+    }
+}
+// the engine may still be thinking, it's not waited for
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+// score cp <x> or score mate <y> in an info line, mates count the same however far away they are
+fn parse_score(words: &[&str]) -> Option<i32> {
+    let i = words.iter().position(|word| *word == "score")?;
+    let value = words.get(i + 2)?.parse::<i32>().ok()?;
+    match *words.get(i + 1)? {
+        "cp" => Some(value),
+        "mate" if value > 0 => Some(MATE_SCORE),
+        "mate" => Some(-MATE_SCORE),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+impl GameResult {
+    fn win_for(color: PlayerColor) -> GameResult {
+        match color {
+            PlayerColor::WHITE => GameResult::WhiteWins,
+            PlayerColor::BLACK => GameResult::BlackWins,
+        }
+    }
+    fn to_pgn(self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+        }
+    }
+}
+struct GameOutcome {
+    result: GameResult,
+    // for the Termination tag
+    termination: &'static str,
+    reason: String,
+}
+impl GameOutcome {
+    fn new(result: GameResult, termination: &'static str, reason: String) -> GameOutcome {
+        GameOutcome {
+            result,
+            termination,
+            reason,
+        }
+    }
+}
+
+// the wins, draws and losses of the first engine
+#[derive(Default)]
+struct Score {
+    wins: u32,
+    draws: u32,
+    losses: u32,
+}
+impl Score {
+    fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+    // the share of the points, and how much it varies from game to game
+    fn get_mean_and_variance(&self) -> (f64, f64) {
+        let games = self.games().max(1) as f64;
+        let (wins, draws, losses) = (
+            self.wins as f64 / games,
+            self.draws as f64 / games,
+            self.losses as f64 / games,
+        );
+        let mean = wins + draws / 2.0;
+        let variance = wins * (1.0 - mean).powi(2)
+            + draws * (0.5 - mean).powi(2)
+            + losses * (0.0 - mean).powi(2);
+        (mean, variance)
+    }
+    // the Elo difference and how far off it may be, None while one side has all the points, and
+    // the error is infinite while so few games have been played that it could
+    fn get_elo(&self) -> Option<(f64, f64)> {
+        let (mean, variance) = self.get_mean_and_variance();
+        if mean <= 0.0 || mean >= 1.0 {
+            return None;
+        }
+        let deviation = CONFIDENCE_Z * (variance / self.games() as f64).sqrt();
+        let (lower, upper) = (mean - deviation, mean + deviation);
+        let error = if lower <= 0.0 || upper >= 1.0 {
+            f64::INFINITY
+        } else {
+            (get_elo_difference(upper) - get_elo_difference(lower)) / 2.0
+        };
+        Some((get_elo_difference(mean), error))
+    }
+    // the log-likelihood ratio of the first engine being elo1 rather than elo0 stronger, with the
+    // points per game taken to be normally distributed
+    fn get_log_likelihood_ratio(&self, sprt: &Sprt) -> Option<f64> {
+        let (mean, variance) = self.get_mean_and_variance();
+        if variance <= 0.0 {
+            return None;
+        }
+        let mean0 = get_expected_score(sprt.elo0);
+        let mean1 = get_expected_score(sprt.elo1);
+        Some(
+            self.games() as f64 * (mean1 - mean0) * (2.0 * mean - mean0 - mean1) / (2.0 * variance),
+        )
+    }
+}
+fn get_expected_score(elo_difference: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo_difference / 400.0))
+}
+fn get_elo_difference(expected_score: f64) -> f64 {
+    400.0 * (expected_score / (1.0 - expected_score)).log10()
+}
+impl Sprt {
+    // below the first, elo0 is accepted, above the second elo1 is
+    fn get_bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+}
+
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    std::process::exit(1);
+}
+fn parse_seconds(text: &str) -> Duration {
+    match text.parse::<f64>() {
+        Ok(seconds) if seconds >= 0.0 => Duration::from_secs_f64(seconds),
+        _ => exit_with_usage(&format!("'{}' is not a number of seconds", text)),
+    }
+}
+fn parse_number<T: std::str::FromStr>(text: &str) -> T {
+    match text.parse::<T>() {
+        Ok(number) => number,
+        Err(_) => exit_with_usage(&format!("'{}' is not a number", text)),
+    }
+}
+// the name=value pairs following a flag
+fn take_pairs<'a>(
+    args: &mut std::iter::Peekable<std::slice::Iter<'a, String>>,
+) -> Vec<(&'a str, &'a str)> {
+    let mut pairs = Vec::new();
+    while let Some(arg) = args.peek() {
+        if arg.starts_with('-') {
+            break;
+        }
+        match arg.split_once('=') {
+            Some(pair) => pairs.push(pair),
+            None => exit_with_usage(&format!("'{}' is not a name=value pair", arg)),
+        }
+        args.next();
+    }
+    pairs
+}
+fn parse_args(args: &[String]) -> Settings {
+    let mut settings = Settings {
+        engines: Vec::new(),
+        games: DEFAULT_GAMES,
+        openings: Vec::new(),
+        time_control: TimeControl::Clock {
+            time: DEFAULT_TIME,
+            increment: DEFAULT_INCREMENT,
+        },
+        adjudication: Adjudication::default(),
+        sprt: None,
+        pgn_out: None,
+    };
+    let mut args = args.iter().peekable();
+    while let Some(flag) = args.next() {
+        let mut value = || match args.next() {
+            Some(value) => value.as_str(),
+            None => exit_with_usage(&format!("{} needs a value", flag)),
+        };
+        match flag.as_str() {
+            "-engine" => {
+                let mut config = EngineConfig {
+                    command: None,
+                    name: None,
+                    options: Vec::new(),
+                };
+                for (name, value) in take_pairs(&mut args) {
+                    match (name, name.strip_prefix("option.")) {
+                        ("cmd", _) => config.command = Some(value.to_owned()),
+                        ("name", _) => config.name = Some(value.to_owned()),
+                        (_, Some(option)) => {
+                            config.options.push((option.to_owned(), value.to_owned()))
+                        }
+                        _ => exit_with_usage(&format!("unknown engine setting '{}'", name)),
+                    }
+                }
+                settings.engines.push(config);
+            }
+            "-games" => settings.games = parse_number(value()),
+            "-openings" => settings.openings = load_openings(value()),
+            "-tc" => {
+                let text = value();
+                let (time, increment) = text.split_once('+').unwrap_or((text, "0"));
+                settings.time_control = TimeControl::Clock {
+                    time: parse_seconds(time),
+                    increment: parse_seconds(increment),
+                };
+            }
+            "-st" => settings.time_control = TimeControl::MoveTime(parse_seconds(value())),
+            "-maxmoves" => settings.adjudication.max_moves = Some(parse_number(value())),
+            "-pgnout" => settings.pgn_out = Some(value().to_owned()),
+            "-draw" => {
+                let (mut move_number, mut move_count, mut score) = (0, 0, 0);
+                for (name, value) in take_pairs(&mut args) {
+                    match name {
+                        "movenumber" => move_number = parse_number(value),
+                        "movecount" => move_count = parse_number(value),
+                        "score" => score = parse_number(value),
+                        _ => exit_with_usage(&format!("unknown draw setting '{}'", name)),
+                    }
+                }
+                settings.adjudication.draw = Some((move_number, move_count.max(1), score));
+            }
+            "-resign" => {
+                let (mut move_count, mut score) = (0, 0);
+                for (name, value) in take_pairs(&mut args) {
+                    match name {
+                        "movecount" => move_count = parse_number(value),
+                        "score" => score = parse_number(value),
+                        _ => exit_with_usage(&format!("unknown resign setting '{}'", name)),
+                    }
+                }
+                settings.adjudication.resign = Some((move_count.max(1), score));
+            }
+            "-sprt" => {
+                let mut sprt = Sprt {
+                    elo0: 0.0,
+                    elo1: 0.0,
+                    alpha: DEFAULT_SPRT_ALPHA,
+                    beta: DEFAULT_SPRT_BETA,
+                };
+                for (name, value) in take_pairs(&mut args) {
+                    match name {
+                        "elo0" => sprt.elo0 = parse_number(value),
+                        "elo1" => sprt.elo1 = parse_number(value),
+                        "alpha" => sprt.alpha = parse_number(value),
+                        "beta" => sprt.beta = parse_number(value),
+                        _ => exit_with_usage(&format!("unknown SPRT setting '{}'", name)),
+                    }
+                }
+                if sprt.elo1 <= sprt.elo0 {
+                    exit_with_usage("elo1 has to be above elo0");
+                }
+                settings.sprt = Some(sprt);
+            }
+            _ => exit_with_usage(&format!("unknown flag '{}'", flag)),
+        }
+    }
+    if settings.engines.len() != 2 {
+        exit_with_usage("there have to be two engines");
+    }
+    if settings.openings.is_empty() {
+        let board = BoardState::from_fen(STARTING_POSITION_FEN).unwrap();
+        settings.openings.push(GameHistory::new(vec![board], None));
+    }
+    settings
+}
+// the games of a PGN file, or a position in FEN or EPD on every line
+fn load_openings(path: &str) -> Vec<GameHistory> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("Can't read '{}': {}", path, e);
+            std::process::exit(1);
+        }
+    };
+    let mut openings = Vec::new();
+    if path.ends_with(".pgn") {
+        for (i, game) in PgnReader::new(&text).enumerate() {
+            match game {
+                Ok(game) => openings.push(game.history),
+                Err(e) => eprintln!("Skipping opening {}: {}", i + 1, e),
+            }
+        }
+    } else {
+        for (i, line) in text.lines().enumerate() {
+            let fields = line.split_whitespace().collect::<Vec<&str>>();
+            if fields.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // EPD has operations where FEN has the move counters
+            let board = BoardState::from_fen(&fields[..fields.len().min(6)].join(" "))
+                .or_else(|_| BoardState::from_fen(&fields[..fields.len().min(4)].join(" ")));
+            match board {
+                Ok(board) => openings.push(GameHistory::new(vec![board], None)),
+                Err(e) => eprintln!("Skipping line {}: {}", i + 1, e),
+            }
+        }
+    }
+    let count = openings.len();
+    openings.retain(|opening| !opening.get_status().is_over());
+    if openings.len() < count {
+        eprintln!(
+            "Skipping {} openings that are already over",
+            count - openings.len()
+        );
+    }
+    if openings.is_empty() {
+        eprintln!("There are no openings in '{}'", path);
+        std::process::exit(1);
+    }
+    openings
+}
+// lilchess-uci, from where this program is
+fn get_default_engine_command() -> String {
+    let name = format!("lilchess-uci{}", std::env::consts::EXE_SUFFIX);
+    match std::env::current_exe() {
+        Ok(path) => path.with_file_name(name).to_string_lossy().into_owned(),
+        Err(_) => name,
+    }
+}
+
+// the result once the scores of the last moves, one for every move played so far, call for one
+fn adjudicate(
+    adjudication: &Adjudication,
+    board: &BoardState,
+    scores: &[Option<i32>],
+) -> Option<GameOutcome> {
+    if let Some((move_count, score)) = adjudication.resign {
+        if let Some(last_scores) = get_last_scores(scores, move_count * 2) {
+            if last_scores.iter().all(|last_score| *last_score >= score) {
+                let reason = "White is winning by agreement".to_owned();
+                return Some(GameOutcome::new(
+                    GameResult::WhiteWins,
+                    "adjudication",
+                    reason,
+                ));
+            }
+            if last_scores.iter().all(|last_score| *last_score <= -score) {
+                let reason = "Black is winning by agreement".to_owned();
+                return Some(GameOutcome::new(
+                    GameResult::BlackWins,
+                    "adjudication",
+                    reason,
+                ));
+            }
+        }
+    }
+    if let Some((move_number, move_count, score)) = adjudication.draw {
+        if board.fullmove_number >= move_number {
+            if let Some(last_scores) = get_last_scores(scores, move_count * 2) {
+                if last_scores
+                    .iter()
+                    .all(|last_score| last_score.abs() <= score)
+                {
+                    let reason = "Drawn by agreement".to_owned();
+                    return Some(GameOutcome::new(GameResult::Draw, "adjudication", reason));
+                }
+            }
+        }
+    }
+    if let Some(max_moves) = adjudication.max_moves {
+        if scores.len() as u32 >= max_moves * 2 {
+            let reason = format!("Drawn after {} moves", max_moves);
+            return Some(GameOutcome::new(GameResult::Draw, "adjudication", reason));
+        }
+    }
+    None
+}
+// from White's side, none if there aren't enough yet or an engine didn't give one
+fn get_last_scores(scores: &[Option<i32>], count: usize) -> Option<Vec<i32>> {
+    if scores.len() < count {
+        return None;
+    }
+    scores[scores.len() - count..].iter().copied().collect()
+}
+
+// plays a game from the opening, engines[white] has White, an engine that fails loses the game and is
+// dropped, to be started again for the next one
+fn play_game(
+    engines: &mut [Option<UciEngine>; 2],
+    names: &[String; 2],
+    white: usize,
+    opening: &GameHistory,
+    settings: &Settings,
+) -> (GameHistory, GameOutcome) {
+    let mut history = opening.clone();
+    history.player_out_of_time = None;
+    for (i, engine) in engines.iter_mut().enumerate() {
+        let color = if i == white {
+            PlayerColor::WHITE
+        } else {
+            PlayerColor::BLACK
+        };
+        if let Err(e) = engine
+            .as_mut()
+            .map_or(Err(EngineError::Exited), UciEngine::new_game)
+        {
+            *engine = None;
+            let reason = format!("{} can't start the game: {}", names[i], e);
+            let result = GameResult::win_for(PlayerColor::opposite(color));
+            return (history, GameOutcome::new(result, "abandoned", reason));
+        }
+    }
+    let mut clocks = match settings.time_control {
+        TimeControl::Clock { time, .. } => [time; 2],
+        TimeControl::MoveTime(move_time) => [move_time; 2],
+    };
+    // every move's score from White's side, by the engine that played it
+    let mut scores = Vec::<Option<i32>>::new();
+    loop {
+        let status = history.get_status();
+        if status.is_over() {
+            let result = match status.get_winner() {
+                Some(color) => GameResult::win_for(color),
+                None => GameResult::Draw,
+            };
+            let reason = describe_status(status).to_owned();
+            return (history, GameOutcome::new(result, "normal", reason));
+        }
+        let board = history.board_states.last().unwrap().clone();
+        if let Some(outcome) = adjudicate(&settings.adjudication, &board, &scores) {
+            return (history, outcome);
+        }
+        let color = board.player_to_move;
+        let side = color.index();
+        let i = if color == PlayerColor::WHITE {
+            white
+        } else {
+            1 - white
+        };
+        let go_command = match settings.time_control {
+            TimeControl::Clock { increment, .. } => format!(
+                "go wtime {} btime {} winc {} binc {}",
+                clocks[0].as_millis(),
+                clocks[1].as_millis(),
+                increment.as_millis(),
+                increment.as_millis()
+            ),
+            TimeControl::MoveTime(move_time) => format!("go movetime {}", move_time.as_millis()),
+        };
+        let started_at = Instant::now();
+        let deadline = started_at + clocks[side] + TIME_MARGIN;
+        let reply = engines[i]
+            .as_mut()
+            .map_or(Err(EngineError::Exited), |engine| {
+                engine.go(&history, &go_command, deadline)
+            })
+            .and_then(
+                |(text, score)| match board.move_from_long_algebraic(&text) {
+                    Ok(mv) => Ok((mv, score)),
+                    Err(_) => Err(EngineError::IllegalMove(text)),
+                },
+            );
+        let elapsed = started_at.elapsed();
+        let (mv, score) = match reply {
+            Ok(reply) => reply,
+            Err(EngineError::Timeout(_)) => {
+                engines[i] = None;
+                // it's only a draw when the opponent can't mate
+                history.player_out_of_time = Some(color);
+                let result = match history.get_status().get_winner() {
+                    Some(winner) => GameResult::win_for(winner),
+                    None => GameResult::Draw,
+                };
+                let reason = format!("{} loses on time", names[i]);
+                return (history, GameOutcome::new(result, "time forfeit", reason));
+            }
+            Err(e) => {
+                engines[i] = None;
+                let result = GameResult::win_for(PlayerColor::opposite(color));
+                let reason = format!("{} loses: {}", names[i], e);
+                return (
+                    history,
+                    GameOutcome::new(result, "rules infraction", reason),
+                );
+            }
+        };
+        if let TimeControl::Clock { increment, .. } = settings.time_control {
+            clocks[side] = clocks[side].saturating_sub(elapsed) + increment;
+        }
+        scores.push(score.map(|score| board.score_for_current_player(score)));
+        history.execute_move(&mv);
+    }
+}
+fn describe_status(status: GameStatus) -> &'static str {
+    match status {
+        GameStatus::Ongoing => "Still going",
+        GameStatus::Checkmate { .. } => "Checkmate",
+        GameStatus::Timeout { .. } => "Time's up",
+        GameStatus::Stalemate => "Drawn by stalemate",
+        GameStatus::FiftyMoveRule => "Drawn by the fifty-move rule",
+        GameStatus::ThreefoldRepetition => "Drawn by threefold repetition",
+        GameStatus::InsufficientMaterial => "Drawn by insufficient material",
+    }
+}
+fn save_game(
+    path: &str,
+    history: GameHistory,
+    outcome: &GameOutcome,
+    names: &[String; 2],
+    white: usize,
+    round: u32,
+    time_control: TimeControl,
+) -> std::io::Result<()> {
+    let mut game = PgnGame::new(history);
+    game.set_tag("Event", "LilChess match");
+    game.set_tag("Site", "LilChess");
+    game.set_tag("Round", &round.to_string());
+    game.set_tag("White", &names[white]);
+    game.set_tag("Black", &names[1 - white]);
+    game.set_tag("Result", outcome.result.to_pgn());
+    game.set_tag("Termination", outcome.termination);
+    game.set_tag(
+        "TimeControl",
+        &match time_control {
+            TimeControl::Clock { time, increment } => {
+                format!("{}+{}", time.as_secs_f64(), increment.as_secs_f64())
+            }
+            TimeControl::MoveTime(move_time) => format!("{}/move", move_time.as_secs_f64()),
+        },
+    );
+    match game.annotations.last_mut() {
+        Some(annotation) => annotation.comment = Some(outcome.reason.clone()),
+        None => game.initial_comment = Some(outcome.reason.clone()),
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    file.write_all(game.to_pgn().as_bytes())
+}
+fn print_score(score: &Score, names: &[String; 2], sprt: Option<&Sprt>) {
+    let (mean, _) = score.get_mean_and_variance();
+    println!(
+        "Score of {} vs {}: {} - {} - {}  [{:.3}] {}",
+        names[0],
+        names[1],
+        score.wins,
+        score.losses,
+        score.draws,
+        mean,
+        score.games()
+    );
+    match score.get_elo() {
+        Some((elo, error)) => println!("Elo difference: {:.1} +/- {:.1}", elo, error),
+        None => println!("Elo difference: unknown while one side has all the points"),
+    }
+    if let Some(sprt) = sprt {
+        let (lower, upper) = sprt.get_bounds();
+        match score.get_log_likelihood_ratio(sprt) {
+            Some(llr) => println!(
+                "SPRT elo0={} elo1={}: LLR {:.2} ({:.2}, {:.2})",
+                sprt.elo0, sprt.elo1, llr, lower, upper
+            ),
+            None => println!(
+                "SPRT elo0={} elo1={}: LLR unknown yet ({:.2}, {:.2})",
+                sprt.elo0, sprt.elo1, lower, upper
+            ),
+        }
+    }
+}
+
+// plays the games between two engines, each opening twice so both get to play both sides of it
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    let settings = parse_args(&args);
+    let default_command = get_default_engine_command();
+    let commands = settings
+        .engines
+        .iter()
+        .map(|config| {
+            config
+                .command
+                .clone()
+                .unwrap_or_else(|| default_command.clone())
+        })
+        .collect::<Vec<String>>();
+    let start_engine = |i: usize| match UciEngine::start(&commands[i], &settings.engines[i].options)
+    {
+        Ok(engine) => Some(engine),
+        Err(e) => {
+            eprintln!("Can't start '{}': {}", commands[i], e);
+            None
+        }
+    };
+    let mut engines = [start_engine(0), start_engine(1)];
+    if engines.iter().any(|engine| engine.is_none()) {
+        std::process::exit(1);
+    }
+    let mut names = [0, 1].map(|i| {
+        settings.engines[i]
+            .name
+            .clone()
+            .unwrap_or_else(|| engines[i].as_ref().unwrap().name.clone())
+    });
+    if names[0] == names[1] {
+        names = [format!("{} 1", names[0]), format!("{} 2", names[1])];
+    }
+
+    let mut score = Score::default();
+    for game_index in 0..settings.games {
+        for (i, engine) in engines.iter_mut().enumerate() {
+            if engine.is_none() {
+                *engine = start_engine(i);
+            }
+        }
+        let opening = &settings.openings[(game_index as usize / 2) % settings.openings.len()];
+        let white = game_index as usize % 2;
+        let (history, outcome) = play_game(&mut engines, &names, white, opening, &settings);
+        let first_engine_result = match (outcome.result, white) {
+            (GameResult::Draw, _) => 0,
+            (GameResult::WhiteWins, 0) | (GameResult::BlackWins, 1) => 1,
+            _ => -1,
+        };
+        match first_engine_result {
+            1 => score.wins += 1,
+            0 => score.draws += 1,
+            _ => score.losses += 1,
+        }
+        println!(
+            "Game {} of {}: {} vs {} {} ({})",
+            game_index + 1,
+            settings.games,
+            names[white],
+            names[1 - white],
+            outcome.result.to_pgn(),
+            outcome.reason
+        );
+        if let Some(path) = settings.pgn_out.as_ref() {
+            let round = game_index + 1;
+            let time_control = settings.time_control;
+            if let Err(e) = save_game(path, history, &outcome, &names, white, round, time_control) {
+                eprintln!("Can't write the game to '{}': {}", path, e);
+            }
+        }
+        print_score(&score, &names, settings.sprt.as_ref());
+        if let Some(sprt) = settings.sprt.as_ref() {
+            let (lower, upper) = sprt.get_bounds();
+            match score.get_log_likelihood_ratio(sprt) {
+                Some(llr) if llr >= upper => {
+                    println!(
+                        "SPRT: H1 accepted, {} is stronger by {} Elo or more",
+                        names[0], sprt.elo1
+                    );
+                    break;
+                }
+                Some(llr) if llr <= lower => {
+                    println!(
+                        "SPRT: H0 accepted, {} isn't stronger by more than {} Elo",
+                        names[0], sprt.elo0
+                    );
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(wins: u32, draws: u32, losses: u32) -> Score {
+        Score {
+            wins,
+            draws,
+            losses,
+        }
+    }
+    fn assert_close(value: f64, expected: f64) {
+        assert!(
+            (value - expected).abs() < 0.001,
+            "{} != {}",
+            value,
+            expected
+        );
+    }
+
+    #[test]
+    fn elo() {
+        let (elo, error) = score(60, 20, 20).get_elo().unwrap();
+        assert_close(elo, 147.191);
+        assert_close(error, 66.015);
+        let (elo, error) = score(20, 30, 50).get_elo().unwrap();
+        assert_close(elo, -107.538);
+        assert_close(error, 59.165);
+        let (elo, error) = score(10, 10, 10).get_elo().unwrap();
+        assert_close(elo, 0.0);
+        assert_close(error, 104.560);
+    }
+
+    #[test]
+    fn elo_without_enough_games() {
+        assert!(score(0, 0, 0).get_elo().is_none());
+        assert!(score(5, 0, 0).get_elo().is_none());
+        assert!(score(0, 3, 7).get_elo().is_some());
+        assert!(score(0, 0, 7).get_elo().is_none());
+        let (elo, error) = score(1, 1, 0).get_elo().unwrap();
+        assert_close(elo, 190.849);
+        assert_eq!(error, f64::INFINITY);
+    }
+
+    #[test]
+    fn log_likelihood_ratio() {
+        let sprt = Sprt {
+            elo0: 0.0,
+            elo1: 10.0,
+            alpha: 0.05,
+            beta: 0.05,
+        };
+        let ratio = |score: Score| score.get_log_likelihood_ratio(&sprt).unwrap();
+        assert_close(ratio(score(60, 20, 20)), 1.734);
+        assert_close(ratio(score(10, 10, 10)), -0.019);
+        assert_close(ratio(score(20, 30, 50)), -1.483);
+        // all draws don't vary at all
+        assert!(score(0, 12, 0).get_log_likelihood_ratio(&sprt).is_none());
+        let (lower, upper) = sprt.get_bounds();
+        assert_close(lower, -2.944);
+        assert_close(upper, 2.944);
+    }
+
+    fn board(fullmove_number: u32) -> BoardState {
+        let fen = format!("4k3/8/8/8/8/8/8/R3K3 w - - 0 {}", fullmove_number);
+        BoardState::from_fen(&fen).unwrap()
+    }
+    fn result(outcome: Option<GameOutcome>) -> Option<&'static str> {
+        outcome.map(|outcome| match outcome.result {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+        })
+    }
+
+    #[test]
+    fn resign_and_draw_adjudication() {
+        let adjudication = Adjudication {
+            draw: Some((20, 2, 10)),
+            resign: Some((3, 500)),
+            max_moves: None,
+        };
+        let adjudicate = |fullmove_number, scores: &[Option<i32>]| {
+            result(adjudicate(&adjudication, &board(fullmove_number), scores))
+        };
+        let winning = [
+            Some(600),
+            Some(550),
+            Some(700),
+            Some(900),
+            Some(500),
+            Some(800),
+        ];
+        assert_eq!(adjudicate(30, &winning), Some("1-0"));
+        assert_eq!(adjudicate(30, &winning[1..]), None);
+        let losing = winning.iter().map(|score| score.map(|score| -score));
+        assert_eq!(adjudicate(30, &losing.collect::<Vec<_>>()), Some("0-1"));
+        // one engine not agreeing is enough to play on
+        let disputed = [
+            Some(600),
+            Some(550),
+            Some(700),
+            Some(400),
+            Some(500),
+            Some(800),
+        ];
+        assert_eq!(adjudicate(30, &disputed), None);
+
+        // a draw needs fewer scores than a resignation, and doesn't wait for them
+        let level = [Some(5), Some(-10), Some(0), Some(10)];
+        assert_eq!(adjudicate(30, &level), Some("1/2-1/2"));
+        assert_eq!(adjudicate(19, &level), None);
+        assert_eq!(
+            adjudicate(30, &[Some(50), Some(5), Some(-10), Some(0), Some(10)]),
+            Some("1/2-1/2")
+        );
+        assert_eq!(
+            adjudicate(30, &[Some(5), Some(-10), Some(20), Some(10)]),
+            None
+        );
+        // a move without a score only holds up the rules whose window it's in
+        assert_eq!(
+            adjudicate(30, &[None, Some(5), Some(-10), Some(0), Some(10)]),
+            Some("1/2-1/2")
+        );
+        assert_eq!(adjudicate(30, &[Some(5), None, Some(0), Some(10)]), None);
+        assert_eq!(
+            adjudicate(
+                30,
+                &[
+                    None,
+                    Some(600),
+                    Some(550),
+                    Some(700),
+                    Some(900),
+                    Some(500),
+                    Some(800)
+                ]
+            ),
+            Some("1-0")
+        );
+    }
+
+    #[test]
+    fn draw_after_max_moves() {
+        let adjudication = Adjudication {
+            max_moves: Some(3),
+            ..Adjudication::default()
+        };
+        let adjudicate =
+            |scores: &[Option<i32>]| result(adjudicate(&adjudication, &board(1), scores));
+        assert_eq!(adjudicate(&[]), None);
+        assert_eq!(adjudicate(&[Some(900); 5]), None);
+        // however clear the scores are, and even when there are none
+        assert_eq!(adjudicate(&[Some(900); 6]), Some("1/2-1/2"));
+        assert_eq!(adjudicate(&[None; 6]), Some("1/2-1/2"));
+    }
+
+    #[test]
+    fn scores_from_info_lines() {
+        let score = |line: &str| parse_score(&line.split_whitespace().collect::<Vec<_>>());
+        assert_eq!(
+            score("info depth 8 score cp 35 nodes 1200 pv e2e4"),
+            Some(35)
+        );
+        assert_eq!(score("info depth 8 score cp -120 pv e2e4"), Some(-120));
+        assert_eq!(
+            score("info depth 12 score mate 3 pv a1a8"),
+            Some(MATE_SCORE)
+        );
+        assert_eq!(
+            score("info depth 12 score mate -2 pv h8g8"),
+            Some(-MATE_SCORE)
+        );
+        assert_eq!(score("info depth 8 nodes 1200 pv e2e4"), None);
+        assert_eq!(score("info string score cp"), None);
+        assert_eq!(score("info score lowerbound 20"), None);
+    }
+}
@@ -0,0 +1,51 @@
+use lil_chess::fen::STARTING_POSITION_FEN;
+use lil_chess::game_types::BoardState;
+use std::time::Instant;
+
+const USAGE: &str = "usage: lilchess-perft [FEN] <depth>";
+
+// prints the node count after every first move, like other engines' divide commands
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    let (fen, depth) = match args.as_slice() {
+        [depth] => (STARTING_POSITION_FEN, depth),
+        [fen, depth] => (fen.as_str(), depth),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    };
+    let depth = match depth.parse::<u32>() {
+        Ok(depth) => depth,
+        Err(_) => {
+            eprintln!("'{}' is not a depth\n{}", depth, USAGE);
+            std::process::exit(1);
+        }
+    };
+    let board = match BoardState::from_fen(fen) {
+        Ok(board) => board,
+        Err(e) => {
+            eprintln!("Invalid position '{}': {}", fen, e);
+            std::process::exit(1);
+        }
+    };
+    let start = Instant::now();
+    let mut divided = board.divide(depth);
+    divided.sort_by_key(|(mv, _)| mv.to_long_algebraic());
+    for (mv, nodes) in divided.iter() {
+        println!("{}: {}", mv.to_long_algebraic(), nodes);
+    }
+    let nodes = if depth == 0 {
+        1
+    } else {
+        divided.iter().map(|(_, nodes)| nodes).sum()
+    };
+    let elapsed = start.elapsed().as_secs_f64();
+    println!();
+    println!("Nodes searched: {}", nodes);
+    println!(
+        "Time: {:.3}s ({:.0} nodes/s)",
+        elapsed,
+        nodes as f64 / elapsed.max(0.001)
+    );
+}
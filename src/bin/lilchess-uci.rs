@@ -0,0 +1,447 @@
+use lil_chess::book::{OpeningBook, BOOK_PATH, DEFAULT_MAX_BOOK_DEPTH};
+use lil_chess::engine::Engine;
+use lil_chess::fen::STARTING_POSITION_FEN;
+use lil_chess::game_types::{BoardState, PlayerColor};
+use lil_chess::random::Random;
+use lil_chess::search::*;
+use lil_chess::tablebase::{Tablebases, TABLEBASE_PATH};
+use lil_chess::transposition::{TranspositionTable, DEFAULT_HASH_SIZE_MB};
+use std::io::BufRead;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const MAX_HASH_SIZE_MB: usize = 4096;
+const MAX_BOOK_DEPTH: u32 = 100;
+const MAX_THREADS: usize = 256;
+// the parts of the search's selectivity that can be turned off, to measure what they're worth
+const SELECTIVITY_OPTIONS: [&str; 6] = [
+    "NullMovePruning",
+    "LateMoveReductions",
+    "FutilityPruning",
+    "ReverseFutilityPruning",
+    "CheckExtensions",
+    "AspirationWindows",
+];
+
+struct Uci {
+    searcher: Arc<Mutex<Searcher>>,
+    cancellation: CancellationToken,
+    search_thread: Option<thread::JoinHandle<()>>,
+    board: BoardState,
+    // the keys of the positions before the current one, for repetitions
+    previous_positions: Vec<u64>,
+    book: Option<OpeningBook>,
+    use_book: bool,
+    book_depth: u32,
+    random: Random,
+}
+fn get_selectivity_option<'a>(
+    selectivity: &'a mut Selectivity,
+    name: &str,
+) -> Option<&'a mut bool> {
+    match name {
+        "nullmovepruning" => Some(&mut selectivity.null_move_pruning),
+        "latemovereductions" => Some(&mut selectivity.late_move_reductions),
+        "futilitypruning" => Some(&mut selectivity.futility_pruning),
+        "reversefutilitypruning" => Some(&mut selectivity.reverse_futility_pruning),
+        "checkextensions" => Some(&mut selectivity.check_extensions),
+        "aspirationwindows" => Some(&mut selectivity.aspiration_windows),
+        _ => None,
+    }
+}
+fn info_line(result: &SearchResult) -> String {
+    let score = match get_mate_in(result.score) {
+        Some(mate_in) => format!("mate {}", mate_in),
+        None => format!("cp {}", result.score),
+    };
+    let milliseconds = result.elapsed.as_millis() as u64;
+    let pv = result
+        .principal_variation
+        .iter()
+        .map(|mv| mv.to_long_algebraic())
+        .collect::<Vec<String>>();
+    format!(
+        "info depth {} score {} nodes {} nps {} time {} hashfull {} pv {}",
+        result.depth,
+        score,
+        result.nodes,
+        result.nodes * 1000 / milliseconds.max(1),
+        milliseconds,
+        result.hash_usage,
+        pv.join(" ")
+    )
+}
+fn parse_milliseconds(value: Option<&&str>) -> Option<Duration> {
+    // some GUIs send negative times when the clock runs out
+    value
+        .and_then(|value| value.parse::<i64>().ok())
+        .map(|milliseconds| Duration::from_millis(milliseconds.max(0) as u64))
+}
+// go [depth <x>] [nodes <x>] [movetime <x>] [wtime <x> btime <x> [winc <x>] [binc <x>] [movestogo <x>]] [infinite]
+fn parse_go(args: &[&str], player_to_move: PlayerColor) -> (SearchLimits, bool) {
+    let mut limits = SearchLimits::default();
+    let mut time_left = [None, None];
+    let mut increment = [Duration::from_secs(0); 2];
+    let mut moves_to_go = None;
+    let mut is_infinite = false;
+    for (i, arg) in args.iter().enumerate() {
+        let value = args.get(i + 1);
+        match *arg {
+            "depth" => limits.depth = value.and_then(|value| value.parse().ok()),
+            "nodes" => limits.nodes = value.and_then(|value| value.parse().ok()),
+            "movetime" => limits.move_time = parse_milliseconds(value),
+            "wtime" => time_left[0] = parse_milliseconds(value),
+            "btime" => time_left[1] = parse_milliseconds(value),
+            "winc" => increment[0] = parse_milliseconds(value).unwrap_or_default(),
+            "binc" => increment[1] = parse_milliseconds(value).unwrap_or_default(),
+            "movestogo" => moves_to_go = value.and_then(|value| value.parse().ok()),
+            "infinite" => is_infinite = true,
+            _ => {}
+        }
+    }
+    let side = player_to_move.index();
+    if let Some(time_left) = time_left[side] {
+        limits.clock = Some(Clock {
+            time_left,
+            increment: increment[side],
+            moves_to_go,
+        });
+    }
+    if is_infinite {
+        limits = SearchLimits::default();
+    }
+    (limits, is_infinite)
+}
+// position [startpos | fen <FEN>] [moves <move>...], the position and the keys of the ones before it
+fn parse_position(args: &[&str]) -> Result<(BoardState, Vec<u64>), String> {
+    let moves_index = args
+        .iter()
+        .position(|arg| *arg == "moves")
+        .unwrap_or(args.len());
+    let mut board = match args.first() {
+        Some(&"startpos") => BoardState::from_fen(STARTING_POSITION_FEN).unwrap(),
+        Some(&"fen") => {
+            let fen = args[1..moves_index].join(" ");
+            BoardState::from_fen(&fen).map_err(|e| format!("invalid position '{}': {}", fen, e))?
+        }
+        _ => return Err("expected 'startpos' or 'fen'".to_owned()),
+    };
+    let mut previous_positions = Vec::new();
+    for text in args.iter().skip(moves_index + 1) {
+        let mv = board
+            .move_from_long_algebraic(text)
+            .map_err(|e| e.to_string())?;
+        previous_positions.push(board.hash());
+        board.make_move(&mv);
+    }
+    Ok((board, previous_positions))
+}
+
+impl Uci {
+    fn new() -> Uci {
+        let mut searcher = Searcher::new(DEFAULT_HASH_SIZE_MB);
+        searcher.evaluation_weights = Engine::load_evaluation_weights();
+        searcher.tablebases = Engine::load_tablebases();
+        searcher.on_iteration = Some(Box::new(|result| println!("{}", info_line(result))));
+        Uci {
+            searcher: Arc::new(Mutex::new(searcher)),
+            cancellation: CancellationToken::new(),
+            search_thread: None,
+            board: BoardState::from_fen(STARTING_POSITION_FEN).unwrap(),
+            previous_positions: Vec::new(),
+            book: Engine::load_opening_book(),
+            use_book: true,
+            book_depth: DEFAULT_MAX_BOOK_DEPTH,
+            random: Random::from_time(),
+        }
+    }
+    /** Handles one line of input, false once it's time to quit. */
+    fn execute(&mut self, line: &str) -> bool {
+        let words = line.split_whitespace().collect::<Vec<&str>>();
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return true,
+        };
+        match command {
+            "uci" => {
+                println!("id name LilChess");
+                println!("id author nexovec");
+                println!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    DEFAULT_HASH_SIZE_MB, MAX_HASH_SIZE_MB
+                );
+                println!("option name Clear Hash type button");
+                println!(
+                    "option name Threads type spin default 1 min 1 max {}",
+                    MAX_THREADS
+                );
+                println!("option name OwnBook type check default true");
+                println!("option name Book File type string default {}", BOOK_PATH);
+                println!(
+                    "option name Book Depth type spin default {} min 0 max {}",
+                    DEFAULT_MAX_BOOK_DEPTH, MAX_BOOK_DEPTH
+                );
+                println!(
+                    "option name SyzygyPath type string default {}",
+                    TABLEBASE_PATH
+                );
+                for name in SELECTIVITY_OPTIONS.iter() {
+                    println!("option name {} type check default true", name);
+                }
+                println!("uciok");
+            }
+            "isready" => println!("readyok"),
+            "ucinewgame" => {
+                self.stop();
+                let mut searcher = self.searcher.lock().unwrap();
+                searcher.transposition_table.clear();
+                searcher.move_ordering.clear();
+            }
+            "position" => {
+                self.stop();
+                if let Err(e) = self.set_position(args) {
+                    println!("info string {}", e);
+                }
+            }
+            "go" => {
+                self.stop();
+                let (limits, is_infinite) = parse_go(args, self.board.player_to_move);
+                self.go(limits, is_infinite);
+            }
+            "stop" => self.stop(),
+            "setoption" => {
+                self.stop();
+                self.set_option(args);
+            }
+            "quit" => {
+                self.stop();
+                return false;
+            }
+            _ => println!("info string unknown command '{}'", command),
+        }
+        true
+    }
+    fn set_position(&mut self, args: &[&str]) -> Result<(), String> {
+        let (board, previous_positions) = parse_position(args)?;
+        self.board = board;
+        self.previous_positions = previous_positions;
+        Ok(())
+    }
+    fn go(&mut self, limits: SearchLimits, is_infinite: bool) {
+        if !is_infinite && self.use_book {
+            let random = &mut self.random;
+            let board = &self.board;
+            if let Some(mv) = self
+                .book
+                .as_ref()
+                .and_then(|book| book.choose_move(board, random))
+            {
+                println!("info string book move");
+                println!("bestmove {}", mv.to_long_algebraic());
+                return;
+            }
+        }
+        self.cancellation = CancellationToken::new();
+        let cancellation = self.cancellation.clone();
+        let searcher = self.searcher.clone();
+        let board = self.board.clone();
+        let previous_positions = self.previous_positions.clone();
+        self.search_thread = Some(thread::spawn(move || {
+            let mut searcher = searcher.lock().unwrap();
+            searcher.previous_positions = previous_positions;
+            let result = searcher.search(&board, &limits, &cancellation);
+            // the GUI doesn't expect a move before it says stop when the search is infinite
+            while is_infinite && !cancellation.is_cancelled() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            let best_move = match result.best_move {
+                Some(mv) => mv.to_long_algebraic(),
+                None => "0000".to_owned(),
+            };
+            match result.principal_variation.get(1) {
+                Some(ponder_move) => println!(
+                    "bestmove {} ponder {}",
+                    best_move,
+                    ponder_move.to_long_algebraic()
+                ),
+                None => println!("bestmove {}", best_move),
+            }
+        }));
+    }
+    // waits for the running search to send its move
+    fn stop(&mut self) {
+        if let Some(search_thread) = self.search_thread.take() {
+            self.cancellation.cancel();
+            let _ = search_thread.join();
+        }
+    }
+    // setoption name <id> [value <x>]
+    fn set_option(&mut self, args: &[&str]) {
+        let value_index = args
+            .iter()
+            .position(|arg| *arg == "value")
+            .unwrap_or(args.len());
+        let name = args
+            .get(1..value_index)
+            .map(|name| name.join(" "))
+            .unwrap_or_default();
+        let value = args.get(value_index + 1..).map(|value| value.join(" "));
+        let mut searcher = self.searcher.lock().unwrap();
+        let name_key = name.to_ascii_lowercase();
+        if let Some(is_enabled) = get_selectivity_option(&mut searcher.selectivity, &name_key) {
+            match value.as_deref() {
+                Some(value) if value.eq_ignore_ascii_case("true") => *is_enabled = true,
+                Some(value) if value.eq_ignore_ascii_case("false") => *is_enabled = false,
+                _ => println!("info string '{}' takes true or false", name),
+            }
+            return;
+        }
+        match (name_key.as_str(), value) {
+            ("hash", Some(value)) => match value.parse::<usize>() {
+                Ok(size_mb) if (1..=MAX_HASH_SIZE_MB).contains(&size_mb) => {
+                    searcher.transposition_table = Arc::new(TranspositionTable::new(size_mb))
+                }
+                _ => println!("info string '{}' is not a valid hash size", value),
+            },
+            ("clear hash", _) => searcher.transposition_table.clear(),
+            ("threads", Some(value)) => match value.parse::<usize>() {
+                Ok(threads) if (1..=MAX_THREADS).contains(&threads) => searcher.threads = threads,
+                _ => println!("info string '{}' is not a valid thread count", value),
+            },
+            ("ownbook", Some(value)) => self.use_book = value.eq_ignore_ascii_case("true"),
+            ("book file", Some(path)) => match OpeningBook::load(&path) {
+                Ok(mut book) => {
+                    book.max_depth = self.book_depth;
+                    self.book = Some(book);
+                }
+                Err(e) => {
+                    println!("info string can't load the book '{}': {}", path, e);
+                    self.book = None;
+                }
+            },
+            ("book depth", Some(value)) => match value.parse::<u32>() {
+                Ok(depth) if depth <= MAX_BOOK_DEPTH => {
+                    self.book_depth = depth;
+                    if let Some(book) = self.book.as_mut() {
+                        book.max_depth = depth;
+                    }
+                }
+                _ => println!("info string '{}' is not a valid book depth", value),
+            },
+            // an empty path or one without tables turns them off
+            ("syzygypath", path) => {
+                let tablebases = Tablebases::new(path.as_deref().unwrap_or_default());
+                if tablebases.max_pieces() == 0 {
+                    searcher.tablebases = None;
+                } else {
+                    println!(
+                        "info string found tablebases for up to {} pieces",
+                        tablebases.max_pieces()
+                    );
+                    searcher.tablebases = Some(Arc::new(tablebases));
+                }
+            }
+            _ => println!("info string unknown option '{}'", name),
+        }
+    }
+}
+
+fn main() {
+    let mut uci = Uci::new();
+    let stdin = std::io::stdin();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if !uci.execute(&line) {
+            return;
+        }
+    }
+    uci.stop();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn go(command: &str, player_to_move: PlayerColor) -> (SearchLimits, bool) {
+        let args = command.split_whitespace().collect::<Vec<&str>>();
+        parse_go(&args, player_to_move)
+    }
+    fn position(command: &str) -> Result<(BoardState, Vec<u64>), String> {
+        let args = command.split_whitespace().collect::<Vec<&str>>();
+        parse_position(&args)
+    }
+
+    #[test]
+    fn go_limits() {
+        let (limits, is_infinite) = go("depth 7 nodes 50000 movetime 250", PlayerColor::WHITE);
+        assert_eq!(limits.depth, Some(7));
+        assert_eq!(limits.nodes, Some(50000));
+        assert_eq!(limits.move_time, Some(Duration::from_millis(250)));
+        assert!(limits.clock.is_none());
+        assert!(!is_infinite);
+        // values that aren't numbers are left out
+        let (limits, _) = go("depth seven", PlayerColor::WHITE);
+        assert_eq!(limits.depth, None);
+    }
+
+    #[test]
+    fn go_clock_of_the_side_to_move() {
+        let command = "wtime 60000 btime 30000 winc 1000 binc 500 movestogo 20";
+        let clock = go(command, PlayerColor::WHITE).0.clock.unwrap();
+        assert_eq!(clock.time_left, Duration::from_secs(60));
+        assert_eq!(clock.increment, Duration::from_secs(1));
+        assert_eq!(clock.moves_to_go, Some(20));
+        let clock = go(command, PlayerColor::BLACK).0.clock.unwrap();
+        assert_eq!(clock.time_left, Duration::from_secs(30));
+        assert_eq!(clock.increment, Duration::from_millis(500));
+        // a clock that ran out counts as no time left
+        let clock = go("wtime -150 btime 1000", PlayerColor::WHITE)
+            .0
+            .clock
+            .unwrap();
+        assert_eq!(clock.time_left, Duration::from_secs(0));
+        assert_eq!(clock.increment, Duration::from_secs(0));
+        assert_eq!(clock.moves_to_go, None);
+        // only the other side's time isn't enough
+        assert!(go("btime 1000", PlayerColor::WHITE).0.clock.is_none());
+    }
+
+    #[test]
+    fn go_infinite() {
+        let (limits, is_infinite) = go("infinite depth 3 wtime 1000", PlayerColor::WHITE);
+        assert!(is_infinite);
+        assert_eq!(limits.depth, None);
+        assert!(limits.clock.is_none());
+    }
+
+    #[test]
+    fn positions() {
+        let (board, previous_positions) = position("startpos").unwrap();
+        assert_eq!(board.to_fen(), STARTING_POSITION_FEN);
+        assert!(previous_positions.is_empty());
+        let (board, previous_positions) = position("startpos moves e2e4 e7e5 g1f3").unwrap();
+        assert_eq!(
+            board.to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+        assert_eq!(previous_positions.len(), 3);
+        assert_eq!(
+            previous_positions[0],
+            BoardState::from_fen(STARTING_POSITION_FEN).unwrap().hash()
+        );
+        let (board, _) = position("fen 3k4/P7/8/8/8/8/8/4K3 w - - 0 1 moves a7a8q d8c7").unwrap();
+        assert_eq!(board.to_fen(), "Q7/2k5/8/8/8/8/8/4K3 w - - 1 2");
+    }
+
+    #[test]
+    fn position_errors() {
+        assert!(position("").is_err());
+        assert!(position("somewhere").is_err());
+        assert!(position("fen 8/8/8 w - - 0 1").is_err());
+        assert!(position("startpos moves e2e4 e7e4").is_err());
+    }
+}
@@ -0,0 +1,131 @@
+// one bit per square, a1 is bit 0, h1 bit 7 and h8 bit 63, the same indexing as x + y * 8
+pub type Bitboard = u64;
+
+// directions are indices into RAYS, the first four go towards higher squares
+const NORTH: usize = 0;
+const EAST: usize = 1;
+const NORTH_EAST: usize = 2;
+const NORTH_WEST: usize = 3;
+const SOUTH: usize = 4;
+const WEST: usize = 5;
+const SOUTH_WEST: usize = 6;
+const SOUTH_EAST: usize = 7;
+const DIRECTION_STEPS: [(i8, i8); 8] = [
+    (0, 1),
+    (1, 0),
+    (1, 1),
+    (-1, 1),
+    (0, -1),
+    (-1, 0),
+    (-1, -1),
+    (1, -1),
+];
+
+pub const KNIGHT_ATTACKS: [Bitboard; 64] = leaper_attacks(&[
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+]);
+pub const KING_ATTACKS: [Bitboard; 64] = leaper_attacks(&DIRECTION_STEPS);
+// indexed by color (see PlayerColor::index), the squares a pawn of that color attacks
+pub const PAWN_ATTACKS: [[Bitboard; 64]; 2] = [
+    leaper_attacks(&[(-1, 1), (1, 1)]),
+    leaper_attacks(&[(-1, -1), (1, -1)]),
+];
+const RAYS: [[Bitboard; 64]; 8] = rays();
+
+const fn leaper_attacks(steps: &[(i8, i8)]) -> [Bitboard; 64] {
+    let mut table = [0; 64];
+    let mut square = 0;
+    while square < 64 {
+        let mut i = 0;
+        while i < steps.len() {
+            let x = (square % 8) as i8 + steps[i].0;
+            let y = (square / 8) as i8 + steps[i].1;
+            if x >= 0 && x < 8 && y >= 0 && y < 8 {
+                table[square] |= 1 << (x + y * 8);
+            }
+            i += 1;
+        }
+        square += 1;
+    }
+    table
+}
+// every square a slider could reach from a square on an empty board, per direction
+const fn rays() -> [[Bitboard; 64]; 8] {
+    let mut table = [[0; 64]; 8];
+    let mut direction = 0;
+    while direction < 8 {
+        let mut square = 0;
+        while square < 64 {
+            let mut x = (square % 8) as i8 + DIRECTION_STEPS[direction].0;
+            let mut y = (square / 8) as i8 + DIRECTION_STEPS[direction].1;
+            while x >= 0 && x < 8 && y >= 0 && y < 8 {
+                table[direction][square] |= 1 << (x + y * 8);
+                x += DIRECTION_STEPS[direction].0;
+                y += DIRECTION_STEPS[direction].1;
+            }
+            square += 1;
+        }
+        direction += 1;
+    }
+    table
+}
+
+pub fn square_index(x: i8, y: i8) -> usize {
+    (x + y * 8) as usize
+}
+pub fn square_bit(x: i8, y: i8) -> Bitboard {
+    1 << square_index(x, y)
+}
+/** Iterates over the indices of the set bits, from a1 towards h8. */
+pub fn squares(bitboard: Bitboard) -> Squares {
+    Squares(bitboard)
+}
+pub struct Squares(Bitboard);
+impl Iterator for Squares {
+    type Item = usize;
+    fn next(&mut self) -> Option<usize> {
+        if self.0 == 0 {
+            return None;
+        }
+        let square = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
+}
+
+// the ray is cut off behind the first blocker, the blocker itself can still be captured
+fn ray_attacks(square: usize, direction: usize, occupied: Bitboard) -> Bitboard {
+    let ray = RAYS[direction][square];
+    let blockers = ray & occupied;
+    if blockers == 0 {
+        return ray;
+    }
+    let first_blocker = if direction < SOUTH {
+        blockers.trailing_zeros()
+    } else {
+        63 - blockers.leading_zeros()
+    };
+    ray ^ RAYS[direction][first_blocker as usize]
+}
+pub fn bishop_attacks(square: usize, occupied: Bitboard) -> Bitboard {
+    ray_attacks(square, NORTH_EAST, occupied)
+        | ray_attacks(square, NORTH_WEST, occupied)
+        | ray_attacks(square, SOUTH_EAST, occupied)
+        | ray_attacks(square, SOUTH_WEST, occupied)
+}
+pub fn rook_attacks(square: usize, occupied: Bitboard) -> Bitboard {
+    ray_attacks(square, NORTH, occupied)
+        | ray_attacks(square, EAST, occupied)
+        | ray_attacks(square, SOUTH, occupied)
+        | ray_attacks(square, WEST, occupied)
+}
+pub fn queen_attacks(square: usize, occupied: Bitboard) -> Bitboard {
+    bishop_attacks(square, occupied) | rook_attacks(square, occupied)
+}
@@ -0,0 +1,214 @@
+use crate::game_types::*;
+use crate::pgn::PgnGame;
+use crate::random::Random;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+
+// where the engine looks for its book, it plays without one if there's no file
+pub const BOOK_PATH: &str = "./res/book.bin";
+// in plies from the start of the game
+pub const DEFAULT_MAX_BOOK_DEPTH: u32 = 16;
+const ENTRY_SIZE: usize = 16;
+
+/** One move of a position in the Polyglot format: 16 big-endian bytes, entries sorted by key. */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BookEntry {
+    pub key: u64,
+    // to file, to rank, from file, from rank and promotion piece, 3 bits each from the lowest up
+    pub raw_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+/** A Polyglot `.bin` opening book, the positions are keyed with BoardState::hash. */
+pub struct OpeningBook {
+    entries: Vec<BookEntry>,
+    // the book isn't used once the game is this many plies in
+    pub max_depth: u32,
+}
+#[derive(Debug)]
+pub enum BookError {
+    Io(std::io::Error),
+    InvalidLength(usize),
+}
+impl fmt::Display for BookError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BookError::Io(e) => write!(f, "{}", e),
+            BookError::InvalidLength(length) => write!(
+                f,
+                "{} bytes isn't a whole number of {} byte entries",
+                length, ENTRY_SIZE
+            ),
+        }
+    }
+}
+impl std::error::Error for BookError {}
+impl From<std::io::Error> for BookError {
+    fn from(e: std::io::Error) -> BookError {
+        BookError::Io(e)
+    }
+}
+
+impl OpeningBook {
+    pub fn new(mut entries: Vec<BookEntry>) -> OpeningBook {
+        entries.sort_by_key(|entry| (entry.key, u16::MAX - entry.weight));
+        OpeningBook {
+            entries,
+            max_depth: DEFAULT_MAX_BOOK_DEPTH,
+        }
+    }
+    pub fn load(path: &str) -> Result<OpeningBook, BookError> {
+        OpeningBook::from_bytes(&fs::read(path)?)
+    }
+    pub fn save(&self, path: &str) -> Result<(), BookError> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+    pub fn from_bytes(bytes: &[u8]) -> Result<OpeningBook, BookError> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(BookError::InvalidLength(bytes.len()));
+        }
+        let entries = bytes
+            .chunks(ENTRY_SIZE)
+            .map(|chunk| {
+                let mut key = [0; 8];
+                key.copy_from_slice(&chunk[0..8]);
+                BookEntry {
+                    key: u64::from_be_bytes(key),
+                    raw_move: u16::from_be_bytes([chunk[8], chunk[9]]),
+                    weight: u16::from_be_bytes([chunk[10], chunk[11]]),
+                    learn: u32::from_be_bytes([chunk[12], chunk[13], chunk[14], chunk[15]]),
+                }
+            })
+            .collect();
+        Ok(OpeningBook::new(entries))
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.entries.len() * ENTRY_SIZE);
+        for entry in self.entries.iter() {
+            bytes.extend_from_slice(&entry.key.to_be_bytes());
+            bytes.extend_from_slice(&entry.raw_move.to_be_bytes());
+            bytes.extend_from_slice(&entry.weight.to_be_bytes());
+            bytes.extend_from_slice(&entry.learn.to_be_bytes());
+        }
+        bytes
+    }
+    /** A book of the first max_depth plies of the games. Like Polyglot's own, a move is weighted two
+    for every game the side playing it won and one for every draw, moves only ever played by the
+    losing side are left out. */
+    pub fn from_games<'a>(
+        games: impl IntoIterator<Item = &'a PgnGame>,
+        max_depth: u32,
+    ) -> OpeningBook {
+        let mut weights = HashMap::<(u64, u16), u32>::new();
+        for game in games {
+            let winner = match game.get_result() {
+                "1-0" => Some(PlayerColor::WHITE),
+                "0-1" => Some(PlayerColor::BLACK),
+                _ => None,
+            };
+            for (board, mv) in game
+                .history
+                .board_states
+                .iter()
+                .zip(game.history.moves.iter())
+            {
+                if get_ply(board) >= max_depth {
+                    break;
+                }
+                let points = match winner {
+                    Some(color) if color == board.player_to_move => 2,
+                    Some(_) => 0,
+                    // draws, and unfinished games still show what's played
+                    None => 1,
+                };
+                *weights.entry((board.hash(), encode_move(mv))).or_insert(0) += points;
+            }
+        }
+        let entries = weights
+            .into_iter()
+            .filter(|(_, weight)| *weight > 0)
+            .map(|((key, raw_move), weight)| BookEntry {
+                key,
+                raw_move,
+                weight: weight.min(u16::MAX as u32) as u16,
+                learn: 0,
+            })
+            .collect();
+        OpeningBook {
+            max_depth,
+            ..OpeningBook::new(entries)
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    /** The book's legal moves in the position with their weights, the heaviest first. */
+    pub fn get_moves(&self, board: &BoardState) -> Vec<(ChessMove, u16)> {
+        let key = board.hash();
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let legal_moves = board.get_all_legal_moves();
+        self.entries[start..]
+            .iter()
+            .take_while(|entry| entry.key == key)
+            .filter_map(|entry| {
+                // a colliding key can bring up moves that aren't legal here
+                legal_moves
+                    .iter()
+                    .find(|mv| encode_move(mv) == entry.raw_move)
+                    .map(|mv| (*mv, entry.weight))
+            })
+            .collect()
+    }
+    /** A move from the book picked at random, with the chances in proportion to the weights. None
+    past the max depth or when the position isn't in the book. */
+    pub fn choose_move(&self, board: &BoardState, random: &mut Random) -> Option<ChessMove> {
+        if get_ply(board) >= self.max_depth {
+            return None;
+        }
+        let moves = self.get_moves(board);
+        let total_weight: u64 = moves.iter().map(|(_, weight)| *weight as u64).sum();
+        let mut pick = random.below(total_weight);
+        for (mv, weight) in moves.iter() {
+            if pick < *weight as u64 {
+                return Some(*mv);
+            }
+            pick -= *weight as u64;
+        }
+        None
+    }
+}
+// the number of plies played since the starting position, going by the move number
+fn get_ply(board: &BoardState) -> u32 {
+    let ply = board.fullmove_number.saturating_sub(1) * 2;
+    match board.player_to_move {
+        PlayerColor::WHITE => ply,
+        PlayerColor::BLACK => ply + 1,
+    }
+}
+/** The move in Polyglot's encoding, where castling is the king taking its own rook. */
+pub fn encode_move(mv: &ChessMove) -> u16 {
+    let to_x = if mv.is_king_side_castles() {
+        7
+    } else if mv.is_queen_side_castles() {
+        0
+    } else {
+        mv.to.x
+    };
+    let promotion = match mv.promotion() {
+        Some(PieceType::KNIGHT) => 1,
+        Some(PieceType::BISHOP) => 2,
+        Some(PieceType::ROOK) => 3,
+        Some(PieceType::QUEEN) => 4,
+        _ => 0,
+    };
+    to_x as u16
+        | (mv.to.y as u16) << 3
+        | (mv.from.x as u16) << 6
+        | (mv.from.y as u16) << 9
+        | promotion << 12
+}
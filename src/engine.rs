@@ -1,10 +1,32 @@
+use crate::book::{OpeningBook, BOOK_PATH};
+use crate::evaluation::EvaluationWeights;
 use crate::game_types::*;
-use std::sync::mpsc;
+use crate::personality::EngineSettings;
+use crate::random::Random;
+use crate::review::{review_game, GameReview, REVIEW_DEPTH, REVIEW_MOVE_TIME};
+use crate::search::{CancellationToken, SearchLimits, SearchResult, Searcher};
+use crate::tablebase::{Tablebases, TABLEBASE_PATH};
+use crate::transposition::DEFAULT_HASH_SIZE_MB;
+use std::path::Path;
+use std::sync::mpsc::{self, TryRecvError};
+use std::sync::{Arc, Mutex};
 use std::thread;
+
+// the engine's evaluation weights are read from here, so they can be tuned without recompiling
+pub const EVALUATION_WEIGHTS_PATH: &str = "./res/evaluation_weights.cfg";
 pub struct Engine {
-    sender: mpsc::Sender<ChessMove>,
-    receiver: mpsc::Receiver<ChessMove>,
+    // the move of the running search, a search that ends without one drops its end of the channel
+    move_receiver: Option<mpsc::Receiver<ChessMove>>,
+    // every finished depth of the running analysis, a new analysis gets a new channel
+    analysis_receiver: Option<mpsc::Receiver<SearchResult>>,
+    review_receiver: Option<mpsc::Receiver<GameReview>>,
     computing_thread_handle: Option<thread::JoinHandle<()>>,
+    cancellation: CancellationToken,
+    // kept between moves so the transposition table carries over
+    searcher: Arc<Mutex<Searcher>>,
+    settings: EngineSettings,
+    book: Option<OpeningBook>,
+    random: Random,
 }
 // #[derive(Clone)]
 // pub struct PositionEvaluationResult {
@@ -13,161 +35,213 @@ pub struct Engine {
 // }
 impl Engine {
     pub fn new() -> Engine {
-        let (sx, rx) = mpsc::channel();
+        Engine::with_hash_size(DEFAULT_HASH_SIZE_MB)
+    }
+    /** An engine whose transposition table takes up about hash_size_mb megabytes. */
+    pub fn with_hash_size(hash_size_mb: usize) -> Engine {
+        let mut searcher = Searcher::new(hash_size_mb);
+        searcher.evaluation_weights = Engine::load_evaluation_weights();
+        searcher.tablebases = Engine::load_tablebases();
+        let settings = EngineSettings::default();
+        searcher.threads = settings.threads;
         Engine {
-            sender: sx,
-            receiver: rx,
+            move_receiver: None,
+            analysis_receiver: None,
+            review_receiver: None,
             computing_thread_handle: None,
+            cancellation: CancellationToken::new(),
+            searcher: Arc::new(Mutex::new(searcher)),
+            settings,
+            book: Engine::load_opening_book(),
+            random: Random::from_time(),
         }
     }
-    fn static_position_evaluation(board: &BoardState, mv: &ChessMove) -> f32 {
-        let mut result = 0.0f32;
-        // TODO: Precompute MoveDescription per move
-        if board.evaluate_is_check(None) {
-            result += board.score_for_current_player(-1.0f32);
-        }
-        if board.evaluate_is_checkmate() {
-            result -= board.score_for_current_player(-1000.0f32);
+    /** An engine playing at the settings' skill level and in their style. */
+    pub fn with_settings(settings: EngineSettings) -> Engine {
+        let mut engine = Engine::new();
+        {
+            let mut searcher = engine.searcher.lock().unwrap();
+            settings.style.apply(&mut searcher.evaluation_weights);
+            searcher.multi_pv = settings.multi_pv();
+            searcher.threads = settings.threads;
         }
-        for piece in board.get_pieces_vec().iter() {
-            match piece.color {
-                PlayerColor::WHITE => result += Engine::get_piece_worth(piece),
-                PlayerColor::BLACK => result -= Engine::get_piece_worth(piece),
+        engine.settings = settings;
+        engine
+    }
+    pub fn settings(&self) -> EngineSettings {
+        self.settings
+    }
+    /** The weights from EVALUATION_WEIGHTS_PATH, or the built-in ones if the file can't be loaded. */
+    pub fn load_evaluation_weights() -> EvaluationWeights {
+        match EvaluationWeights::load(EVALUATION_WEIGHTS_PATH) {
+            Ok(weights) => weights,
+            Err(e) => {
+                eprintln!(
+                    "Can't load the evaluation weights in '{}', using the defaults: {}",
+                    EVALUATION_WEIGHTS_PATH, e
+                );
+                EvaluationWeights::default()
             }
         }
-        result
     }
-    fn get_piece_worth(p: &Piece) -> f32 {
-        let result = match p.piece_type {
-            PieceType::BISHOP => 3.2,
-            PieceType::KNIGHT => 3.0,
-            PieceType::ROOK => 5.0,
-            PieceType::QUEEN => 9.0,
-            PieceType::PAWN => 1.0,
-            PieceType::KING => 0.0,
-        };
-        result
-    }
-    fn compute_position_score(board_state: &BoardState, mv: &ChessMove, depth: u32) -> f32 {
-        // TODO: Pick the maximum if white to move, minimum if black to move
-        // TODO: Get rid of Engine::compute_move()
-        // TODO: Track checks past depth
-        // TODO: Track capture sequences past depth
-        // TODO: Track mate threats past depth
-        // TODO: Track capture threats past depth
-        let mut moves = board_state.get_all_legal_moves();
-        if moves.len() == 0 {
-            return Engine::static_position_evaluation(board_state, mv);
+    /** The book in BOOK_PATH, if there's one that can be read. */
+    pub fn load_opening_book() -> Option<OpeningBook> {
+        if !Path::new(BOOK_PATH).exists() {
+            return None;
         }
-        if depth == 1 {
-            // let best_move = moves
-            //     .iter()
-            //     .min_by(|mv1, mv2| {
-            //         let static_position_score_1 =
-            //             Engine::static_position_evaluation(&board_state.after_move(*mv1), mv1);
-            //         let static_position_score_2 =
-            //             Engine::static_position_evaluation(&board_state.after_move(*mv2), mv2);
-            //         static_position_score_1
-            //             .partial_cmp(&static_position_score_2)
-            //             .unwrap()
-            //     })
-            //     .unwrap();
-            // return Engine::static_position_evaluation(
-            //     &board_state.after_move(best_move),
-            //     best_move,
-            // );
-            let mut best_move: Option<ChessMove> = None;
-            let mut best_score: Option<f32> = None;
-            while let Some(mv) = moves.pop() {
-                let pos = board_state.after_move(&mv);
-                let score = Engine::static_position_evaluation(&pos, &mv);
-                // TODO: verify option compare is right here:
-                if best_score.is_none() || best_score.unwrap().partial_cmp(&score).unwrap().is_lt()
-                {
-                    best_score = Some(score);
-                    best_move = Some(mv)
-                }
+        match OpeningBook::load(BOOK_PATH) {
+            Ok(book) => Some(book),
+            Err(e) => {
+                eprintln!("Can't load the opening book in '{}': {}", BOOK_PATH, e);
+                None
             }
-            return Engine::static_position_evaluation(
-                &board_state.after_move(&best_move.unwrap()),
-                &best_move.unwrap(),
-            );
         }
-        let subnode_scores = moves
-            .iter()
-            .map(|mv| {
-                let new_position = board_state.after_move(mv);
-                Engine::compute_position_score(&new_position, mv, depth - 1)
-            })
-            .collect::<Vec<f32>>();
-        if board_state.player_to_move == PlayerColor::WHITE {
-            return subnode_scores.iter().fold(0.0f32, |a, &b| a.max(b));
-
-            // let iter = subnode_scores.iter();
-            // return *iter.max().unwrap();
-
-            // return *subnode_scores
-            //     .iter()
-            //     .max_by(|a, b| a.partial_cmp(b).unwrap())
-            //     .unwrap();
-        } else {
-            return subnode_scores.iter().fold(f32::INFINITY, |a, &b| a.min(b));
-            // return *subnode_scores
-            //     .iter()
-            //     .min_by(|a, b| a.partial_cmp(b).unwrap())
-            //     .unwrap();
+    }
+    /** The Syzygy tables in TABLEBASE_PATH, if there are any. */
+    pub fn load_tablebases() -> Option<Arc<Tablebases>> {
+        let tablebases = Tablebases::new(TABLEBASE_PATH);
+        if tablebases.max_pieces() == 0 {
+            return None;
         }
+        Some(Arc::new(tablebases))
+    }
+    /** Rough piece values for ordering captures and exchanges, the evaluation has its own. */
+    pub fn get_piece_worth(p: &Piece) -> i32 {
+        Engine::get_piece_type_worth(p.piece_type)
     }
-    pub fn maybe_calculate_move(&mut self, board_state: BoardState) -> Option<ChessMove> {
+    pub fn get_piece_type_worth(piece_type: PieceType) -> i32 {
+        let result = match piece_type {
+            PieceType::BISHOP => 320,
+            PieceType::KNIGHT => 300,
+            PieceType::ROOK => 500,
+            PieceType::QUEEN => 900,
+            PieceType::PAWN => 100,
+            PieceType::KING => 0,
+        };
+        result
+    }
+    /** Plays from the opening book if it knows the game's position, otherwise starts searching the
+    position on another thread if it isn't already, and returns the move once the search is done. */
+    pub fn maybe_calculate_move(
+        &mut self,
+        history: &GameHistory,
+        limits: SearchLimits,
+    ) -> Option<ChessMove> {
         if self.computing_thread_handle.is_none() {
-            let tx = self.sender.clone();
+            let (board_state, previous_positions) = split_history(history);
+            let random = &mut self.random;
+            let book_move = self
+                .book
+                .as_ref()
+                .and_then(|book| book.choose_move(&board_state, random));
+            if book_move.is_some() {
+                return book_move;
+            }
+            let (tx, rx) = mpsc::channel();
+            self.move_receiver = Some(rx);
+            let searcher = self.searcher.clone();
+            self.cancellation = CancellationToken::new();
+            let cancellation = self.cancellation.clone();
+            let settings = self.settings;
+            let limits = settings.limit_search(&limits);
+            let mut random = Random::new(self.random.next_u64());
             self.computing_thread_handle = Some(thread::spawn(move || {
-                // thread::sleep(time::Duration::from_millis(1500));
-                let moves = board_state.get_all_legal_moves();
-                let depth = 2;
-                // let best_move = moves.last().unwrap();
-                // let best_move = moves
-                //     .iter()
-                //     .min_by(|mv1, mv2| {
-                //         let score1 = Engine::compute_position_score(
-                //             &board_state.after_move(*mv1),
-                //             mv1,
-                //             depth,
-                //         );
-                //         let score2 = Engine::compute_position_score(
-                //             &board_state.after_move(*mv2),
-                //             mv2,
-                //             depth,
-                //         );
-                //         score1.partial_cmp(&score2).unwrap()
-                //     })
-                //     .unwrap()
-                //     .to_owned();
-                let mut best_move = moves.last().unwrap();
-                let mut best_score = Engine::compute_position_score(
-                    &board_state.after_move(&best_move),
-                    &best_move,
-                    depth,
-                );
-                // FIXME: pawn can take on the other side of the board
-                let mut moves_iter = moves.iter();
-                while let Some(mv) = moves_iter.next() {
-                    let score =
-                        Engine::compute_position_score(&board_state.after_move(&mv), &mv, depth);
-                    if score < best_score {
-                        best_score = score;
-                        best_move = &mv;
-                    }
+                let mut searcher = searcher.lock().unwrap();
+                searcher.previous_positions = previous_positions;
+                let result = searcher.search(&board_state, &limits, &cancellation);
+                searcher.previous_positions.clear();
+                if let Some(chosen_move) = settings.choose_move(&result, &mut random) {
+                    let _ = tx.send(chosen_move);
                 }
-                let _ = tx.send(best_move.clone());
-                drop(tx);
             }));
         }
-        let maybe_result = self.receiver.try_recv();
-        if maybe_result.is_ok() {
-            self.computing_thread_handle = None;
-            return Some(maybe_result.unwrap());
+        match self.move_receiver.as_ref()?.try_recv() {
+            Ok(chosen_move) => {
+                self.computing_thread_handle = None;
+                Some(chosen_move)
+            }
+            // nothing to play, the next call searches again
+            Err(TryRecvError::Disconnected) => {
+                self.computing_thread_handle = None;
+                None
+            }
+            Err(TryRecvError::Empty) => None,
+        }
+    }
+    /** Searches the game's current position on another thread until it's cancelled, with a line for
+    each of the best `lines` moves, in place of any search that was running. The result of every
+    finished depth can be picked up with poll_analysis. */
+    pub fn start_analysis(&mut self, history: &GameHistory, lines: usize) {
+        self.cancel();
+        let (board_state, previous_positions) = split_history(history);
+        let (tx, rx) = mpsc::channel();
+        self.analysis_receiver = Some(rx);
+        let searcher = self.searcher.clone();
+        self.cancellation = CancellationToken::new();
+        let cancellation = self.cancellation.clone();
+        self.computing_thread_handle = Some(thread::spawn(move || {
+            let mut searcher = searcher.lock().unwrap();
+            let multi_pv = searcher.multi_pv;
+            searcher.multi_pv = lines.max(1);
+            searcher.previous_positions = previous_positions;
+            searcher.on_iteration = Some(Box::new(move |result: &SearchResult| {
+                let _ = tx.send(result.clone());
+            }));
+            searcher.search(&board_state, &SearchLimits::default(), &cancellation);
+            searcher.on_iteration = None;
+            searcher.previous_positions.clear();
+            searcher.multi_pv = multi_pv;
+        }));
+    }
+    /** The latest depth the analysis has finished since the last call, if it has finished any. */
+    pub fn poll_analysis(&mut self) -> Option<SearchResult> {
+        self.analysis_receiver.as_ref()?.try_iter().last()
+    }
+    /** Goes through the game's moves on another thread, in place of any search that was running,
+    the review can be picked up with poll_review once it's done. */
+    pub fn start_review(&mut self, history: &GameHistory) {
+        self.cancel();
+        let history = history.clone();
+        let (tx, rx) = mpsc::channel();
+        self.review_receiver = Some(rx);
+        let searcher = self.searcher.clone();
+        self.cancellation = CancellationToken::new();
+        let cancellation = self.cancellation.clone();
+        self.computing_thread_handle = Some(thread::spawn(move || {
+            let limits = SearchLimits {
+                depth: Some(REVIEW_DEPTH),
+                move_time: Some(REVIEW_MOVE_TIME),
+                ..SearchLimits::default()
+            };
+            let mut searcher = searcher.lock().unwrap();
+            if let Some(review) = review_game(&mut searcher, &history, &limits, &cancellation) {
+                let _ = tx.send(review);
+            }
+        }));
+    }
+    pub fn poll_review(&mut self) -> Option<GameReview> {
+        self.review_receiver.as_ref()?.try_recv().ok()
+    }
+    /** Stops the search, if one is running, and throws its move, analysis or review away. */
+    pub fn cancel(&mut self) {
+        if let Some(handle) = self.computing_thread_handle.take() {
+            self.cancellation.cancel();
+            let _ = handle.join();
         }
-        None
+        self.move_receiver = None;
+        self.analysis_receiver = None;
+        self.review_receiver = None;
+    }
+}
+// the game's current position, and the keys of the ones before it, which are a draw to repeat
+fn split_history(history: &GameHistory) -> (BoardState, Vec<u64>) {
+    let (board_state, previous_states) = history.board_states.split_last().unwrap();
+    let previous_positions = previous_states.iter().map(|board| board.hash()).collect();
+    (board_state.clone(), previous_positions)
+}
+// the search thread would otherwise go on thinking for a game that's gone
+impl Drop for Engine {
+    fn drop(&mut self) {
+        self.cancel();
     }
 }
@@ -0,0 +1,554 @@
+use crate::bitboard::*;
+use crate::game_types::*;
+use std::fmt;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub};
+
+const FILE_A: Bitboard = 0x0101_0101_0101_0101;
+// what each piece type adds to the game phase, all of them together make MAX_PHASE
+const PHASE_WEIGHTS: [i32; 6] = [0, 2, 1, 1, 0, 4];
+const MAX_PHASE: i32 = 24;
+
+/** A weight that blends from its midgame to its endgame value as the pieces come off. */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TaperedWeight {
+    pub midgame: i32,
+    pub endgame: i32,
+}
+/** Everything the static evaluation scores a position with. Weights are in centipawns for the side
+that has the feature, indexed by PieceType::index where there's one per piece type. */
+#[derive(Clone, Debug)]
+pub struct EvaluationWeights {
+    pub piece_values: [TaperedWeight; 6],
+    // from White's side, a8 to h1 like a diagram, Black's pieces look them up mirrored
+    pub piece_square_tables: [[TaperedWeight; 64]; 6],
+    // per square a piece can go to that isn't covered by an enemy pawn
+    pub mobility: [TaperedWeight; 6],
+    // per own pawn on the files around the king, one or two ranks in front of it
+    pub king_shelter: TaperedWeight,
+    // per attack on the squares around the enemy king, when at least two pieces join in
+    pub king_attack: [TaperedWeight; 6],
+    // by the rank the pawn is on, counted from its own side
+    pub passed_pawn: [TaperedWeight; 8],
+    pub doubled_pawn: TaperedWeight,
+    pub isolated_pawn: TaperedWeight,
+    pub bishop_pair: TaperedWeight,
+    pub rook_on_open_file: TaperedWeight,
+    pub rook_on_half_open_file: TaperedWeight,
+}
+#[derive(Debug)]
+pub enum WeightsError {
+    Io(std::io::Error),
+    MissingEquals {
+        line: usize,
+    },
+    UnknownKey {
+        line: usize,
+        key: String,
+    },
+    InvalidValue {
+        line: usize,
+        value: String,
+    },
+    WrongValueCount {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+}
+impl fmt::Display for WeightsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WeightsError::Io(e) => write!(f, "{}", e),
+            WeightsError::MissingEquals { line } => {
+                write!(f, "line {} has values but no 'key =' before them", line)
+            }
+            WeightsError::UnknownKey { line, key } => {
+                write!(f, "line {}: there's no weight called '{}'", line, key)
+            }
+            WeightsError::InvalidValue { line, value } => {
+                write!(f, "line {}: '{}' is not a whole number", line, value)
+            }
+            WeightsError::WrongValueCount {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: expected {} values, found {}",
+                line, expected, found
+            ),
+        }
+    }
+}
+impl std::error::Error for WeightsError {}
+
+// midgame and endgame piece-square tables, written the way they look on a diagram
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+#[rustfmt::skip]
+const PAWN_ENDGAME_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    60, 60, 60, 60, 60, 60, 60, 60,
+    40, 40, 40, 40, 40, 40, 40, 40,
+    20, 20, 20, 20, 20, 20, 20, 20,
+    10, 10, 10, 10, 10, 10, 10, 10,
+     5,  5,  5,  5,  5,  5,  5,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+#[rustfmt::skip]
+const BISHOP_TABLE: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+#[rustfmt::skip]
+const ROOK_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+#[rustfmt::skip]
+const QUEEN_TABLE: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+#[rustfmt::skip]
+const KING_TABLE: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+#[rustfmt::skip]
+const KING_ENDGAME_TABLE: [i32; 64] = [
+    -50,-40,-30,-20,-20,-30,-40,-50,
+    -30,-20,-10,  0,  0,-10,-20,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-30,  0,  0,  0,  0,-30,-30,
+    -50,-30,-30,-30,-30,-30,-30,-50,
+];
+
+impl TaperedWeight {
+    pub fn new(midgame: i32, endgame: i32) -> TaperedWeight {
+        TaperedWeight { midgame, endgame }
+    }
+    /** The weight at the given phase, MAX_PHASE being the full set of pieces and 0 bare kings and pawns. */
+    pub fn taper(self, phase: i32) -> i32 {
+        (self.midgame * phase + self.endgame * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+impl Add for TaperedWeight {
+    type Output = TaperedWeight;
+    fn add(self, other: TaperedWeight) -> TaperedWeight {
+        TaperedWeight::new(self.midgame + other.midgame, self.endgame + other.endgame)
+    }
+}
+impl AddAssign for TaperedWeight {
+    fn add_assign(&mut self, other: TaperedWeight) {
+        *self = *self + other;
+    }
+}
+impl Sub for TaperedWeight {
+    type Output = TaperedWeight;
+    fn sub(self, other: TaperedWeight) -> TaperedWeight {
+        self + -other
+    }
+}
+impl Neg for TaperedWeight {
+    type Output = TaperedWeight;
+    fn neg(self) -> TaperedWeight {
+        TaperedWeight::new(-self.midgame, -self.endgame)
+    }
+}
+impl Mul<i32> for TaperedWeight {
+    type Output = TaperedWeight;
+    fn mul(self, factor: i32) -> TaperedWeight {
+        TaperedWeight::new(self.midgame * factor, self.endgame * factor)
+    }
+}
+
+fn tapered_table(midgame: &[i32; 64], endgame: &[i32; 64]) -> [TaperedWeight; 64] {
+    let mut table = [TaperedWeight::default(); 64];
+    for (i, weight) in table.iter_mut().enumerate() {
+        *weight = TaperedWeight::new(midgame[i], endgame[i]);
+    }
+    table
+}
+fn piece_name(piece_type: PieceType) -> &'static str {
+    match piece_type {
+        PieceType::PAWN => "pawn",
+        PieceType::ROOK => "rook",
+        PieceType::BISHOP => "bishop",
+        PieceType::KNIGHT => "knight",
+        PieceType::KING => "king",
+        PieceType::QUEEN => "queen",
+    }
+}
+// the values of a single weight, in the order they're written in the config file
+fn pair(weight: &mut TaperedWeight) -> Vec<&mut i32> {
+    vec![&mut weight.midgame, &mut weight.endgame]
+}
+fn split(weights: &mut [TaperedWeight]) -> (Vec<&mut i32>, Vec<&mut i32>) {
+    weights
+        .iter_mut()
+        .map(|weight| (&mut weight.midgame, &mut weight.endgame))
+        .unzip()
+}
+fn table_index(square: usize, color: PlayerColor) -> usize {
+    match color {
+        PlayerColor::WHITE => square ^ 56,
+        PlayerColor::BLACK => square,
+    }
+}
+// the squares in front of a pawn on its own and the neighbouring files
+fn front_span(square: usize, color: PlayerColor) -> Bitboard {
+    let x = square % 8;
+    let y = square / 8;
+    let files = adjacent_files(x) | FILE_A << x;
+    let ranks_in_front = match color {
+        PlayerColor::WHITE if y < 7 => !0 << (8 * (y + 1)),
+        PlayerColor::BLACK => (1 << (8 * y)) - 1,
+        _ => 0,
+    };
+    files & ranks_in_front
+}
+// the two ranks in front of the king, on its own and the neighbouring files
+fn king_shelter_squares(square: usize, color: PlayerColor) -> Bitboard {
+    let x = square % 8;
+    let y = square / 8;
+    let ranks = match color {
+        PlayerColor::WHITE if y < 7 => 0xffff_u64 << (8 * (y + 1)),
+        PlayerColor::WHITE => 0,
+        PlayerColor::BLACK if y >= 2 => 0xffff_u64 << (8 * (y - 2)),
+        PlayerColor::BLACK => 0xffff_u64 >> (8 * (2 - y)),
+    };
+    (adjacent_files(x) | FILE_A << x) & ranks
+}
+fn adjacent_files(x: usize) -> Bitboard {
+    let mut files = 0;
+    if x > 0 {
+        files |= FILE_A << (x - 1);
+    }
+    if x < 7 {
+        files |= FILE_A << (x + 1);
+    }
+    files
+}
+
+impl Default for EvaluationWeights {
+    fn default() -> EvaluationWeights {
+        let mut weights = EvaluationWeights {
+            piece_values: [TaperedWeight::default(); 6],
+            piece_square_tables: [[TaperedWeight::default(); 64]; 6],
+            mobility: [TaperedWeight::default(); 6],
+            king_shelter: TaperedWeight::new(12, 0),
+            king_attack: [TaperedWeight::default(); 6],
+            passed_pawn: [TaperedWeight::default(); 8],
+            doubled_pawn: TaperedWeight::new(-10, -20),
+            isolated_pawn: TaperedWeight::new(-10, -15),
+            bishop_pair: TaperedWeight::new(30, 50),
+            rook_on_open_file: TaperedWeight::new(25, 10),
+            rook_on_half_open_file: TaperedWeight::new(12, 6),
+        };
+        let pieces = [
+            (PieceType::PAWN, (100, 120), (0, 0), (0, 0)),
+            (PieceType::KNIGHT, (300, 290), (4, 4), (10, 0)),
+            (PieceType::BISHOP, (320, 310), (5, 5), (10, 0)),
+            (PieceType::ROOK, (500, 540), (2, 4), (15, 0)),
+            (PieceType::QUEEN, (900, 950), (1, 2), (25, 0)),
+            (PieceType::KING, (0, 0), (0, 0), (0, 0)),
+        ];
+        for (piece_type, value, mobility, king_attack) in pieces.iter() {
+            let i = piece_type.index();
+            weights.piece_values[i] = TaperedWeight::new(value.0, value.1);
+            weights.mobility[i] = TaperedWeight::new(mobility.0, mobility.1);
+            weights.king_attack[i] = TaperedWeight::new(king_attack.0, king_attack.1);
+        }
+        let tables = [
+            (PieceType::PAWN, &PAWN_TABLE, &PAWN_ENDGAME_TABLE),
+            (PieceType::KNIGHT, &KNIGHT_TABLE, &KNIGHT_TABLE),
+            (PieceType::BISHOP, &BISHOP_TABLE, &BISHOP_TABLE),
+            (PieceType::ROOK, &ROOK_TABLE, &ROOK_TABLE),
+            (PieceType::QUEEN, &QUEEN_TABLE, &QUEEN_TABLE),
+            (PieceType::KING, &KING_TABLE, &KING_ENDGAME_TABLE),
+        ];
+        for (piece_type, midgame, endgame) in tables.iter() {
+            weights.piece_square_tables[piece_type.index()] = tapered_table(midgame, endgame);
+        }
+        let passed_pawn = [
+            (0, 0),
+            (5, 10),
+            (10, 15),
+            (15, 25),
+            (25, 45),
+            (40, 70),
+            (60, 110),
+            (0, 0),
+        ];
+        for (weight, (midgame, endgame)) in weights.passed_pawn.iter_mut().zip(passed_pawn.iter()) {
+            *weight = TaperedWeight::new(*midgame, *endgame);
+        }
+        weights
+    }
+}
+impl EvaluationWeights {
+    /** Reads weights from a config file, see from_config. */
+    pub fn load(path: &str) -> Result<EvaluationWeights, WeightsError> {
+        let text = std::fs::read_to_string(path).map_err(WeightsError::Io)?;
+        EvaluationWeights::from_config(&text)
+    }
+    /** Parses `key = values` with the values separated by whitespace, they may go on over the
+    following lines, and `#` starts a comment. Single weights take a midgame and an endgame value,
+    tables have a `_midgame` and an `_endgame` key with a value per square or rank.
+    Weights that aren't mentioned keep their default. */
+    pub fn from_config(text: &str) -> Result<EvaluationWeights, WeightsError> {
+        // the line each key is on, the key and its values
+        let mut settings = Vec::<(usize, &str, Vec<i32>)>::new();
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let mut values = line.split('#').next().unwrap();
+            if let Some(equals) = values.find('=') {
+                let key = values[..equals].trim();
+                settings.push((line_number, key, Vec::new()));
+                values = &values[equals + 1..];
+            }
+            for value in values.split_whitespace() {
+                let setting = match settings.last_mut() {
+                    Some(setting) => setting,
+                    None => return Err(WeightsError::MissingEquals { line: line_number }),
+                };
+                setting
+                    .2
+                    .push(value.parse().map_err(|_| WeightsError::InvalidValue {
+                        line: line_number,
+                        value: value.to_owned(),
+                    })?);
+            }
+        }
+        let mut weights = EvaluationWeights::default();
+        let mut entries = weights.entries();
+        for (line, key, values) in settings {
+            let targets = match entries.iter_mut().find(|(name, _)| name == key) {
+                Some((_, targets)) => targets,
+                None => {
+                    return Err(WeightsError::UnknownKey {
+                        line,
+                        key: key.to_owned(),
+                    })
+                }
+            };
+            if values.len() != targets.len() {
+                return Err(WeightsError::WrongValueCount {
+                    line,
+                    expected: targets.len(),
+                    found: values.len(),
+                });
+            }
+            for (target, value) in targets.iter_mut().zip(values) {
+                **target = value;
+            }
+        }
+        drop(entries);
+        Ok(weights)
+    }
+    /** Writes every weight out in the format from_config reads. */
+    pub fn to_config(&self) -> String {
+        let mut weights = self.clone();
+        let mut config = String::new();
+        for (key, values) in weights.entries() {
+            // tables are laid out like a board
+            let row_length = if values.len() == 64 { 8 } else { values.len() };
+            config.push_str(&key);
+            config.push_str(" =");
+            for (i, value) in values.iter().enumerate() {
+                if i % row_length == 0 && values.len() > row_length {
+                    config.push_str("\n   ");
+                }
+                config.push_str(&format!(" {:>4}", value));
+            }
+            config.push('\n');
+        }
+        config
+    }
+    // every weight a config file can set, by key
+    fn entries(&mut self) -> Vec<(String, Vec<&mut i32>)> {
+        let mut entries = Vec::new();
+        let per_piece = PIECE_TYPES
+            .iter()
+            .zip(self.piece_values.iter_mut())
+            .zip(self.mobility.iter_mut())
+            .zip(self.king_attack.iter_mut());
+        for (((piece_type, value), mobility), king_attack) in per_piece {
+            let name = piece_name(*piece_type);
+            entries.push((format!("{}_value", name), pair(value)));
+            // pawns and kings aren't scored for their moves or attacks on the king
+            if *piece_type != PieceType::PAWN && *piece_type != PieceType::KING {
+                entries.push((format!("{}_mobility", name), pair(mobility)));
+                entries.push((format!("{}_king_attack", name), pair(king_attack)));
+            }
+        }
+        let tables = PIECE_TYPES.iter().zip(self.piece_square_tables.iter_mut());
+        for (piece_type, table) in tables {
+            let (midgame, endgame) = split(table);
+            let name = piece_name(*piece_type);
+            entries.push((format!("{}_table_midgame", name), midgame));
+            entries.push((format!("{}_table_endgame", name), endgame));
+        }
+        let (midgame, endgame) = split(&mut self.passed_pawn);
+        entries.push(("passed_pawn_midgame".to_owned(), midgame));
+        entries.push(("passed_pawn_endgame".to_owned(), endgame));
+        entries.push(("king_shelter".to_owned(), pair(&mut self.king_shelter)));
+        entries.push(("doubled_pawn".to_owned(), pair(&mut self.doubled_pawn)));
+        entries.push(("isolated_pawn".to_owned(), pair(&mut self.isolated_pawn)));
+        entries.push(("bishop_pair".to_owned(), pair(&mut self.bishop_pair)));
+        entries.push((
+            "rook_on_open_file".to_owned(),
+            pair(&mut self.rook_on_open_file),
+        ));
+        entries.push((
+            "rook_on_half_open_file".to_owned(),
+            pair(&mut self.rook_on_half_open_file),
+        ));
+        entries
+    }
+
+    /** The score of the position in centipawns, positive when White is better. */
+    pub fn evaluate(&self, board: &BoardState) -> i32 {
+        let score = self.evaluate_side(board, PlayerColor::WHITE)
+            - self.evaluate_side(board, PlayerColor::BLACK);
+        let mut phase = 0;
+        for piece_type in PIECE_TYPES.iter() {
+            phase += PHASE_WEIGHTS[piece_type.index()]
+                * board.piece_bitboards[piece_type.index()].count_ones() as i32;
+        }
+        // promotions can make for more than the starting material
+        score.taper(phase.min(MAX_PHASE))
+    }
+    fn evaluate_side(&self, board: &BoardState, color: PlayerColor) -> TaperedWeight {
+        let opponent = PlayerColor::opposite(color);
+        let own = board.color_bitboards[color.index()];
+        let occupied = board.get_occupied();
+        let own_pawns = board.get_bitboard(PieceType::PAWN, color);
+        let enemy_pawns = board.get_bitboard(PieceType::PAWN, opponent);
+        let mut enemy_pawn_attacks = 0;
+        for square in squares(enemy_pawns) {
+            enemy_pawn_attacks |= PAWN_ATTACKS[opponent.index()][square];
+        }
+        let enemy_king = board.get_bitboard(PieceType::KING, opponent);
+        let enemy_king_zone = match squares(enemy_king).next() {
+            Some(square) => KING_ATTACKS[square] | enemy_king,
+            None => 0,
+        };
+
+        let mut score = TaperedWeight::default();
+        let mut king_attack = TaperedWeight::default();
+        let mut king_attackers = 0;
+        for piece_type in PIECE_TYPES.iter() {
+            let i = piece_type.index();
+            for square in squares(board.get_bitboard(*piece_type, color)) {
+                score += self.piece_values[i];
+                score += self.piece_square_tables[i][table_index(square, color)];
+                let attacks = match piece_type {
+                    PieceType::KNIGHT => KNIGHT_ATTACKS[square],
+                    PieceType::BISHOP => bishop_attacks(square, occupied),
+                    PieceType::ROOK => rook_attacks(square, occupied),
+                    PieceType::QUEEN => queen_attacks(square, occupied),
+                    PieceType::PAWN | PieceType::KING => continue,
+                };
+                let mobility = (attacks & !own & !enemy_pawn_attacks).count_ones() as i32;
+                score += self.mobility[i] * mobility;
+                let king_zone_attacks = (attacks & enemy_king_zone).count_ones() as i32;
+                if king_zone_attacks > 0 {
+                    king_attackers += 1;
+                    king_attack += self.king_attack[i] * king_zone_attacks;
+                }
+            }
+        }
+        // a single attacker is easy to fend off
+        if king_attackers >= 2 {
+            score += king_attack;
+        }
+
+        if let Some(king_square) = squares(board.get_bitboard(PieceType::KING, color)).next() {
+            let shelter = king_shelter_squares(king_square, color);
+            score += self.king_shelter * (shelter & own_pawns).count_ones() as i32;
+        }
+
+        for x in 0..8 {
+            let file = FILE_A << x;
+            let pawns_on_file = (own_pawns & file).count_ones() as i32;
+            if pawns_on_file > 1 {
+                score += self.doubled_pawn * (pawns_on_file - 1);
+            }
+            if pawns_on_file > 0 && own_pawns & adjacent_files(x) == 0 {
+                score += self.isolated_pawn * pawns_on_file;
+            }
+        }
+        for square in squares(own_pawns) {
+            if front_span(square, color) & enemy_pawns == 0 {
+                let rank = match color {
+                    PlayerColor::WHITE => square / 8,
+                    PlayerColor::BLACK => 7 - square / 8,
+                };
+                score += self.passed_pawn[rank];
+            }
+        }
+
+        if board.get_bitboard(PieceType::BISHOP, color).count_ones() >= 2 {
+            score += self.bishop_pair;
+        }
+        for square in squares(board.get_bitboard(PieceType::ROOK, color)) {
+            let file = FILE_A << (square % 8);
+            if (own_pawns | enemy_pawns) & file == 0 {
+                score += self.rook_on_open_file;
+            } else if own_pawns & file == 0 {
+                score += self.rook_on_half_open_file;
+            }
+        }
+        score
+    }
+}
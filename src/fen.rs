@@ -0,0 +1,290 @@
+use crate::game_types::*;
+use std::fmt;
+use tetra::math::Vec2;
+
+pub const STARTING_POSITION_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Debug, PartialEq)]
+pub enum FenError {
+    WrongFieldCount(usize),
+    WrongRankCount(usize),
+    WrongRankLength { rank: usize, length: usize },
+    InvalidPiece(char),
+    InvalidSideToMove(String),
+    InvalidCastlingRights(String),
+    InvalidEnPassantSquare(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+    PawnOnBackRank { x: i8, y: i8 },
+    WrongKingCount { color: PlayerColor, count: usize },
+}
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::WrongFieldCount(count) => write!(
+                f,
+                "expected 6 space separated fields (or 4 without move counters), found {}",
+                count
+            ),
+            FenError::WrongRankCount(count) => {
+                write!(f, "expected 8 ranks in piece placement, found {}", count)
+            }
+            FenError::WrongRankLength { rank, length } => write!(
+                f,
+                "rank {} describes {} squares instead of 8",
+                rank, length
+            ),
+            FenError::InvalidPiece(c) => write!(f, "'{}' is not a valid piece letter", c),
+            FenError::InvalidSideToMove(field) => {
+                write!(f, "side to move must be 'w' or 'b', found '{}'", field)
+            }
+            FenError::InvalidCastlingRights(field) => write!(
+                f,
+                "castling rights must be '-' or a combination of 'KQkq', found '{}'",
+                field
+            ),
+            FenError::InvalidEnPassantSquare(field) => write!(
+                f,
+                "en passant square must be '-' or a square on the 3rd/6th rank matching the side to move, found '{}'",
+                field
+            ),
+            FenError::InvalidHalfmoveClock(field) => write!(
+                f,
+                "halfmove clock must be a non-negative number, found '{}'",
+                field
+            ),
+            FenError::InvalidFullmoveNumber(field) => write!(
+                f,
+                "fullmove number must be a positive number, found '{}'",
+                field
+            ),
+            FenError::PawnOnBackRank { x, y } => write!(
+                f,
+                "there is a pawn on {}, pawns can't stand on the first or last rank",
+                square_name(*x, *y)
+            ),
+            FenError::WrongKingCount { color, count } => {
+                write!(f, "expected exactly one {:?} king, found {}", color, count)
+            }
+        }
+    }
+}
+impl std::error::Error for FenError {}
+
+pub fn square_name(x: i8, y: i8) -> String {
+    format!("{}{}", (b'a' + x as u8) as char, y + 1)
+}
+fn piece_from_char(c: char, x: i8, y: i8) -> Result<Piece, FenError> {
+    let color = if c.is_ascii_uppercase() {
+        PlayerColor::WHITE
+    } else {
+        PlayerColor::BLACK
+    };
+    let piece_type = match c.to_ascii_lowercase() {
+        'p' => PieceType::PAWN,
+        'n' => PieceType::KNIGHT,
+        'b' => PieceType::BISHOP,
+        'r' => PieceType::ROOK,
+        'q' => PieceType::QUEEN,
+        'k' => PieceType::KING,
+        _ => return Err(FenError::InvalidPiece(c)),
+    };
+    Ok(Piece::new(x, y, piece_type, color))
+}
+fn piece_to_char(piece: &Piece) -> char {
+    let c = match piece.piece_type {
+        PieceType::PAWN => 'p',
+        PieceType::KNIGHT => 'n',
+        PieceType::BISHOP => 'b',
+        PieceType::ROOK => 'r',
+        PieceType::QUEEN => 'q',
+        PieceType::KING => 'k',
+    };
+    match piece.color {
+        PlayerColor::WHITE => c.to_ascii_uppercase(),
+        PlayerColor::BLACK => c,
+    }
+}
+fn parse_piece_placement(field: &str) -> Result<Vec<Piece>, FenError> {
+    let ranks = field.split('/').collect::<Vec<&str>>();
+    if ranks.len() != 8 {
+        return Err(FenError::WrongRankCount(ranks.len()));
+    }
+    let mut pieces = Vec::<Piece>::with_capacity(32);
+    for (i, rank) in ranks.iter().enumerate() {
+        // FEN lists the ranks from the 8th down to the 1st
+        let y = 7 - i as i8;
+        let mut x: usize = 0;
+        for c in rank.chars() {
+            if let Some(empty_squares) = c.to_digit(10) {
+                if empty_squares == 0 || empty_squares > 8 {
+                    return Err(FenError::InvalidPiece(c));
+                }
+                x += empty_squares as usize;
+                continue;
+            }
+            if x < 8 {
+                let piece = piece_from_char(c, x as i8, y)?;
+                if piece.piece_type == PieceType::PAWN && (y == 0 || y == 7) {
+                    return Err(FenError::PawnOnBackRank { x: piece.x, y });
+                }
+                pieces.push(piece);
+            }
+            x += 1;
+        }
+        if x != 8 {
+            return Err(FenError::WrongRankLength {
+                rank: y as usize + 1,
+                length: x,
+            });
+        }
+    }
+    for color in [PlayerColor::WHITE, PlayerColor::BLACK].iter() {
+        let count = pieces
+            .iter()
+            .filter(|p| p.piece_type == PieceType::KING && p.color == *color)
+            .count();
+        if count != 1 {
+            return Err(FenError::WrongKingCount {
+                color: *color,
+                count,
+            });
+        }
+    }
+    Ok(pieces)
+}
+fn parse_castling_rights(field: &str) -> Result<CastlingRules, FenError> {
+    let mut rules = CastlingRules::new(false, false, false, false);
+    if field == "-" {
+        return Ok(rules);
+    }
+    if field.is_empty() {
+        return Err(FenError::InvalidCastlingRights(field.to_owned()));
+    }
+    for c in field.chars() {
+        let right = match c {
+            'K' => &mut rules.white_can_still_castle_k,
+            'Q' => &mut rules.white_can_still_castle_q,
+            'k' => &mut rules.black_can_still_castle_k,
+            'q' => &mut rules.black_can_still_castle_q,
+            _ => return Err(FenError::InvalidCastlingRights(field.to_owned())),
+        };
+        if *right {
+            return Err(FenError::InvalidCastlingRights(field.to_owned()));
+        }
+        *right = true;
+    }
+    Ok(rules)
+}
+fn parse_en_passant(field: &str, player_to_move: PlayerColor) -> Result<Option<i8>, FenError> {
+    if field == "-" {
+        return Ok(None);
+    }
+    let error = || FenError::InvalidEnPassantSquare(field.to_owned());
+    let bytes = field.as_bytes();
+    if bytes.len() != 2 || !(b'a'..=b'h').contains(&bytes[0]) {
+        return Err(error());
+    }
+    let expected_rank = match player_to_move {
+        PlayerColor::WHITE => b'6',
+        PlayerColor::BLACK => b'3',
+    };
+    if bytes[1] != expected_rank {
+        return Err(error());
+    }
+    Ok(Some((bytes[0] - b'a') as i8))
+}
+impl BoardState {
+    /** Parses a position in Forsyth-Edwards Notation. The move counters may be omitted. */
+    pub fn from_fen(fen: &str) -> Result<BoardState, FenError> {
+        let fields = fen.split_whitespace().collect::<Vec<&str>>();
+        if fields.len() != 6 && fields.len() != 4 {
+            return Err(FenError::WrongFieldCount(fields.len()));
+        }
+        let pieces = parse_piece_placement(fields[0])?;
+        let player_to_move = match fields[1] {
+            "w" => PlayerColor::WHITE,
+            "b" => PlayerColor::BLACK,
+            other => return Err(FenError::InvalidSideToMove(other.to_owned())),
+        };
+        let castling_rules = parse_castling_rights(fields[2])?;
+        let can_take_en_passant = parse_en_passant(fields[3], player_to_move)?;
+        let mut halfmove_clock = 0;
+        let mut fullmove_number = 1;
+        if fields.len() == 6 {
+            halfmove_clock = fields[4]
+                .parse::<u32>()
+                .map_err(|_| FenError::InvalidHalfmoveClock(fields[4].to_owned()))?;
+            fullmove_number = match fields[5].parse::<u32>() {
+                Ok(number) if number > 0 => number,
+                _ => return Err(FenError::InvalidFullmoveNumber(fields[5].to_owned())),
+            };
+        }
+        let mut board =
+            BoardState::new(pieces, player_to_move, castling_rules, can_take_en_passant);
+        board.halfmove_clock = halfmove_clock;
+        board.fullmove_number = fullmove_number;
+        Ok(board)
+    }
+    pub fn to_fen(&self) -> String {
+        let mut placement = String::with_capacity(72);
+        for y in (0..8).rev() {
+            let mut empty_squares = 0;
+            for x in 0..8 {
+                match self.get_piece_at_square(Vec2::new(x, y)) {
+                    Some(piece) => {
+                        if empty_squares > 0 {
+                            placement.push_str(&empty_squares.to_string());
+                            empty_squares = 0;
+                        }
+                        placement.push(piece_to_char(&piece));
+                    }
+                    None => empty_squares += 1,
+                }
+            }
+            if empty_squares > 0 {
+                placement.push_str(&empty_squares.to_string());
+            }
+            if y > 0 {
+                placement.push('/');
+            }
+        }
+        let player_to_move = match self.player_to_move {
+            PlayerColor::WHITE => "w",
+            PlayerColor::BLACK => "b",
+        };
+        let mut castling = String::with_capacity(4);
+        let rules = &self.castling_rules;
+        if rules.white_can_still_castle_k {
+            castling.push('K');
+        }
+        if rules.white_can_still_castle_q {
+            castling.push('Q');
+        }
+        if rules.black_can_still_castle_k {
+            castling.push('k');
+        }
+        if rules.black_can_still_castle_q {
+            castling.push('q');
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+        let en_passant = match self.can_take_en_passant {
+            Some(x) => match self.player_to_move {
+                PlayerColor::WHITE => square_name(x, 5),
+                PlayerColor::BLACK => square_name(x, 2),
+            },
+            None => "-".to_owned(),
+        };
+        format!(
+            "{} {} {} {} {} {}",
+            placement,
+            player_to_move,
+            castling,
+            en_passant,
+            self.halfmove_clock,
+            self.fullmove_number
+        )
+    }
+}
@@ -1,50 +1,73 @@
+use crate::bitboard::*;
+use crate::zobrist::*;
 use tetra::math::Vec2;
+fn square_to_pos(square: usize) -> Vec2<i8> {
+    Vec2::new((square % 8) as i8, (square / 8) as i8)
+}
 #[inline(always)]
 pub fn is_within_chessboard(p: Vec2<i8>) -> bool {
     !(p.x >= 8 || p.y >= 8 || p.x < 0 || p.y < 0)
 }
+#[derive(Clone)]
 pub struct GameHistory {
     pub board_states: Vec<BoardState>,
     pub moves: Vec<ChessMove>,
+    pub player_out_of_time: Option<PlayerColor>,
 }
-#[derive(PartialEq)]
-pub enum MovePlausibility {
-    MOVE,
-    TAKES,
-    IMPOSSIBLE,
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum GameStatus {
+    Ongoing,
+    Checkmate { winner: PlayerColor },
+    Timeout { winner: PlayerColor },
+    Stalemate,
+    FiftyMoveRule,
+    ThreefoldRepetition,
+    InsufficientMaterial,
 }
 #[derive(Clone)]
 pub struct BoardState {
-    pub squares: Vec<Option<Piece>>,
+    // the squares of each piece type, indexed by PieceType::index
+    pub piece_bitboards: [Bitboard; 6],
+    // and of each color's pieces, indexed by PlayerColor::index
+    pub color_bitboards: [Bitboard; 2],
     pub player_to_move: PlayerColor,
     pub castling_rules: CastlingRules,
-    pub can_castle_k_this_move: bool,
-    pub can_castle_q_this_move: bool,
     pub can_take_en_passant: Option<i8>,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    zobrist_key: u64,
 }
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Piece {
     pub x: i8,
     pub y: i8,
     pub piece_type: PieceType,
     pub color: PlayerColor,
 }
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct ChessMove {
     pub from: Piece,
     pub to: Piece,
 }
-#[derive(Clone)]
+#[derive(Clone, Copy, PartialEq)]
 pub struct CastlingRules {
     pub white_can_still_castle_q: bool,
     pub white_can_still_castle_k: bool,
     pub black_can_still_castle_q: bool,
     pub black_can_still_castle_k: bool,
 }
+// everything make_move can't work out backwards from the move itself
+#[derive(Clone, Copy)]
+pub struct UndoInfo {
+    pub captured: Option<Piece>,
+    pub castling_rules: CastlingRules,
+    pub can_take_en_passant: Option<i8>,
+    pub halfmove_clock: u32,
+    pub zobrist_key: u64,
+}
 pub struct MoveDescription {
     pub was_takes: bool,
     pub was_check: bool,
-    pub was_checkmate: bool,
 }
 #[derive(PartialEq, Clone, Copy, Debug)]
 pub enum PieceType {
@@ -60,6 +83,21 @@ pub enum PlayerColor {
     BLACK,
     WHITE,
 }
+// in the order of PieceType::index
+pub const PIECE_TYPES: [PieceType; 6] = [
+    PieceType::PAWN,
+    PieceType::ROOK,
+    PieceType::BISHOP,
+    PieceType::KNIGHT,
+    PieceType::KING,
+    PieceType::QUEEN,
+];
+pub const PROMOTION_PIECE_TYPES: [PieceType; 4] = [
+    PieceType::QUEEN,
+    PieceType::ROOK,
+    PieceType::BISHOP,
+    PieceType::KNIGHT,
+];
 impl CastlingRules {
     pub fn new(
         white_can_still_castle_q: bool,
@@ -75,6 +113,17 @@ impl CastlingRules {
         }
     }
 }
+impl GameStatus {
+    pub fn is_over(&self) -> bool {
+        *self != GameStatus::Ongoing
+    }
+    pub fn get_winner(&self) -> Option<PlayerColor> {
+        match self {
+            GameStatus::Checkmate { winner } | GameStatus::Timeout { winner } => Some(*winner),
+            _ => None,
+        }
+    }
+}
 impl GameHistory {
     pub fn new(board_states: Vec<BoardState>, moves: Option<Vec<ChessMove>>) -> GameHistory {
         let board_states = board_states;
@@ -85,45 +134,69 @@ impl GameHistory {
         GameHistory {
             board_states: board_states,
             moves: moves_unwrapped,
+            player_out_of_time: None,
         }
     }
+    pub fn get_status(&self) -> GameStatus {
+        let board = self.board_states.last().unwrap();
+        if board.evaluate_is_checkmate() {
+            return GameStatus::Checkmate {
+                winner: PlayerColor::opposite(board.player_to_move),
+            };
+        }
+        if board.evaluate_is_stalemate() {
+            return GameStatus::Stalemate;
+        }
+        if board.evaluate_is_insufficient_material() {
+            return GameStatus::InsufficientMaterial;
+        }
+        if let Some(color) = self.player_out_of_time {
+            // running out of time is a draw when the opponent hasn't got the material to mate with
+            let winner = PlayerColor::opposite(color);
+            if board.has_insufficient_mating_material(winner) {
+                return GameStatus::InsufficientMaterial;
+            }
+            return GameStatus::Timeout { winner };
+        }
+        if board.halfmove_clock >= 100 {
+            return GameStatus::FiftyMoveRule;
+        }
+        if self.count_repetitions() >= 3 {
+            return GameStatus::ThreefoldRepetition;
+        }
+        GameStatus::Ongoing
+    }
+    // how many times the current position has occurred, itself included
+    fn count_repetitions(&self) -> usize {
+        let board = self.board_states.last().unwrap();
+        // captures and pawn moves can't be undone, so only positions since the last one can repeat
+        self.board_states
+            .iter()
+            .rev()
+            .take(board.halfmove_clock as usize + 1)
+            .step_by(2)
+            .filter(|other| board.is_same_position(other))
+            .count()
+    }
     pub fn get_board(&mut self) -> &BoardState {
         self.board_states.last().unwrap()
     }
+    /** Undoes the last move, returning it, or None at the starting position. */
+    pub fn take_back(&mut self) -> Option<ChessMove> {
+        let mv = self.moves.pop()?;
+        self.board_states.pop();
+        self.player_out_of_time = None;
+        Some(mv)
+    }
     pub fn execute_move(&mut self, mv: &ChessMove) -> MoveDescription {
         let board_state = self.get_board().clone();
-        self.moves.push(mv.clone());
-        let board = self.get_board().to_owned();
-        let board_after_move = board.after_move(mv);
-        // TODO: slow, BOOO!! optimize.
-        let pieces = board_state
-            .squares
-            .iter()
-            .filter(|thing| thing.is_some())
-            .map(|thing| thing.unwrap())
-            .collect::<Vec<Piece>>();
-        // TODO: slow, BOOO!! optimize.
-        let en_passant_takeable_pawn = pieces.iter().find(|piece_there| {
-            board_state.can_take_en_passant.is_some()
-                && piece_there.x == board_state.can_take_en_passant.unwrap()
-                && piece_there.color == PlayerColor::opposite(mv.to.color)
-                && ((piece_there.color == PlayerColor::WHITE && piece_there.y == 3)
-                    || (piece_there.color == PlayerColor::BLACK && piece_there.y == 4))
-        });
-        let was_takes = pieces.iter().any(|piece_there| {
-            // TODO: en-passant false positives here!
-            // println!("{:?}",board_state.can_take_en_passant);
-            mv.to.pos() == piece_there.pos()
-                || (en_passant_takeable_pawn.is_some()
-                    && mv.to.piece_type == PieceType::PAWN
-                    && mv.to.x != mv.from.x
-                    && mv.to.x == en_passant_takeable_pawn.unwrap().x
-                    && ((mv.from.color == PlayerColor::WHITE && mv.from.y == 4)
-                        || (mv.from.color == PlayerColor::BLACK && mv.from.y == 3)))
-        });
+        self.moves.push(*mv);
+        let board_after_move = board_state.after_move(mv);
+        // en passant lands on an empty square, but it's still a diagonal pawn move
+        let was_takes = board_state.get_piece_at_square(mv.to.pos()).is_some()
+            || (mv.from.piece_type == PieceType::PAWN && mv.from.x != mv.to.x);
         let result = MoveDescription {
             was_check: board_after_move.evaluate_is_check(None),
-            was_checkmate: board_after_move.evaluate_is_checkmate(),
             was_takes: was_takes,
         };
         self.board_states.push(board_after_move);
@@ -131,7 +204,6 @@ impl GameHistory {
     }
 }
 
-// TODO: is_square_attacked function
 impl BoardState {
     pub fn new(
         pieces: Vec<Piece>,
@@ -139,30 +211,116 @@ impl BoardState {
         castling_rules: CastlingRules,
         can_take_en_passant: Option<i8>,
     ) -> BoardState {
-        let mut squares = Vec::<Option<Piece>>::with_capacity(64);
-        for i in 0..64 {
-            squares.push(None);
-        }
-        for piece in pieces.iter() {
-            let index: usize = (piece.x as usize) + (piece.y as usize) * 8;
-            squares[index] = Some(piece.clone());
-        }
         let mut state = BoardState {
-            squares: squares,
+            piece_bitboards: [0; 6],
+            color_bitboards: [0; 2],
             player_to_move: player_to_move,
             castling_rules: castling_rules,
-            can_castle_k_this_move: false,
-            can_castle_q_this_move: false,
             can_take_en_passant: can_take_en_passant,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            zobrist_key: 0,
         };
-        state.can_castle_k_this_move = state.evaluate_can_king_side_castle(state.player_to_move);
-        state.can_castle_q_this_move = state.evaluate_can_queen_side_castle(state.player_to_move);
+        for piece in pieces.iter() {
+            state.put_piece(piece);
+        }
+        state.zobrist_key ^= state.get_state_key();
         state
     }
+    /** The Zobrist key of the position, laid out like Polyglot's (see zobrist.rs). */
+    pub fn hash(&self) -> u64 {
+        self.zobrist_key
+    }
+    // the part of the key that isn't about pieces, it's swapped out whole around every move
+    fn get_state_key(&self) -> u64 {
+        castling_key(&self.castling_rules)
+            ^ en_passant_key(self.get_en_passant_capture_file())
+            ^ turn_key(self.player_to_move)
+    }
+    fn put_piece(&mut self, piece: &Piece) {
+        let bit = square_bit(piece.x, piece.y);
+        self.piece_bitboards[piece.piece_type.index()] |= bit;
+        self.color_bitboards[piece.color.index()] |= bit;
+        self.zobrist_key ^= piece_key(piece);
+    }
+    fn remove_piece(&mut self, piece: &Piece) {
+        let mask = !square_bit(piece.x, piece.y);
+        self.piece_bitboards[piece.piece_type.index()] &= mask;
+        self.color_bitboards[piece.color.index()] &= mask;
+        self.zobrist_key ^= piece_key(piece);
+    }
+    pub fn get_bitboard(&self, piece_type: PieceType, color: PlayerColor) -> Bitboard {
+        self.piece_bitboards[piece_type.index()] & self.color_bitboards[color.index()]
+    }
+    pub fn get_occupied(&self) -> Bitboard {
+        self.color_bitboards[0] | self.color_bitboards[1]
+    }
     pub fn evaluate_is_checkmate(&self) -> bool {
-        self.get_all_legal_moves().len() == 0
+        self.get_all_legal_moves().is_empty() && self.evaluate_is_check(None)
+    }
+    pub fn evaluate_is_stalemate(&self) -> bool {
+        self.get_all_legal_moves().is_empty() && !self.evaluate_is_check(None)
+    }
+    // neither side can checkmate with what's left: K v K, K+minor v K, or only bishops on one color
+    pub fn evaluate_is_insufficient_material(&self) -> bool {
+        BoardState::is_insufficient_material(self.get_pieces_vec())
+    }
+    /** Whether the side's own pieces can't checkmate, by the same rule as for the draw, so that the
+    opponent running out of time against them is a draw. */
+    pub fn has_insufficient_mating_material(&self, color: PlayerColor) -> bool {
+        let mut pieces = self.get_pieces_vec();
+        pieces.retain(|piece| piece.color == color);
+        BoardState::is_insufficient_material(pieces)
+    }
+    fn is_insufficient_material(pieces: Vec<Piece>) -> bool {
+        let mut minor_pieces = 0;
+        let mut bishop_square_colors = Vec::<i8>::with_capacity(4);
+        for piece in pieces.iter() {
+            match piece.piece_type {
+                PieceType::KING => {}
+                PieceType::KNIGHT => minor_pieces += 1,
+                PieceType::BISHOP => {
+                    minor_pieces += 1;
+                    bishop_square_colors.push((piece.x + piece.y) % 2);
+                }
+                _ => return false,
+            }
+        }
+        if minor_pieces <= 1 {
+            return true;
+        }
+        minor_pieces == bishop_square_colors.len()
+            && bishop_square_colors
+                .iter()
+                .all(|c| *c == bishop_square_colors[0])
+    }
+    /** Whether the positions are the same for the purposes of repetition: same pieces, side to move,
+    castling rights and en passant captures. */
+    pub fn is_same_position(&self, other: &BoardState) -> bool {
+        self.zobrist_key == other.zobrist_key
+            && self.player_to_move == other.player_to_move
+            && self.castling_rules == other.castling_rules
+            && self.piece_bitboards == other.piece_bitboards
+            && self.color_bitboards == other.color_bitboards
+            && self.get_en_passant_capture_file() == other.get_en_passant_capture_file()
+    }
+    // a double step only matters to repetitions when the pawn can actually be taken en passant
+    fn get_en_passant_capture_file(&self) -> Option<i8> {
+        let x = self.can_take_en_passant?;
+        let y = match self.player_to_move {
+            PlayerColor::WHITE => 4,
+            PlayerColor::BLACK => 3,
+        };
+        let can_be_taken = [x - 1, x + 1].iter().any(|x| {
+            self.try_get_piece(Vec2::new(*x, y))
+                .is_some_and(|p| p.piece_type == PieceType::PAWN && p.color == self.player_to_move)
+        });
+        if can_be_taken {
+            return Some(x);
+        }
+        None
     }
-    pub fn score_for_current_player(&self, score_amount: f32) -> f32 {
+    pub fn score_for_current_player(&self, score_amount: i32) -> i32 {
         let result = match self.player_to_move {
             PlayerColor::WHITE => score_amount,
             PlayerColor::BLACK => -score_amount,
@@ -171,120 +329,160 @@ impl BoardState {
     }
     /** This is an unsafe funtion, validate the moves yourself! */
     pub fn after_move(&self, mv: &ChessMove) -> BoardState {
-        // TODO: check castles get disabled properly
-        let mut has_taken_en_passant: bool = false;
-        if self.can_take_en_passant.is_some()
-            && mv.to.piece_type == PieceType::PAWN
-            && mv.to.x == self.can_take_en_passant.unwrap()
-            && ((mv.to.color == PlayerColor::WHITE && mv.from.y == 4)
-                || (mv.to.color == PlayerColor::BLACK && mv.from.y == 3))
-        {
-            has_taken_en_passant = true;
+        let mut board = self.clone();
+        board.make_move(mv);
+        board
+    }
+    /** Plays the move on this board, like after_move does on a copy. Just as unsafe, the move must be legal. */
+    pub fn make_move(&mut self, mv: &ChessMove) -> UndoInfo {
+        let mut captured = self.get_piece_at_square(mv.to.pos());
+        let was_takes = captured.is_some();
+        // a pawn moving diagonally onto an empty square takes en passant
+        let has_taken_en_passant =
+            mv.from.piece_type == PieceType::PAWN && mv.from.x != mv.to.x && !was_takes;
+        if has_taken_en_passant {
+            captured = self.get_piece_at_square(Vec2::new(mv.to.x, mv.from.y));
+        }
+        let undo_info = UndoInfo {
+            captured,
+            castling_rules: self.castling_rules,
+            can_take_en_passant: self.can_take_en_passant,
+            halfmove_clock: self.halfmove_clock,
+            zobrist_key: self.zobrist_key,
+        };
+        self.zobrist_key ^= self.get_state_key();
+        self.remove_piece(&mv.from);
+        if let Some(captured) = captured {
+            self.remove_piece(&captured);
+        }
+        self.put_piece(&mv.to);
+        if mv.is_king_side_castles() {
+            self.remove_piece(&Piece::new(7, mv.from.y, PieceType::ROOK, mv.from.color));
+            self.put_piece(&Piece::new(5, mv.from.y, PieceType::ROOK, mv.from.color));
+        }
+        if mv.is_queen_side_castles() {
+            self.remove_piece(&Piece::new(0, mv.from.y, PieceType::ROOK, mv.from.color));
+            self.put_piece(&Piece::new(3, mv.from.y, PieceType::ROOK, mv.from.color));
         }
 
-        let mut can_take_en_passant: Option<i8> = None;
-        if mv.from.piece_type == PieceType::PAWN {
+        self.can_take_en_passant = None;
+        if mv.from.piece_type == PieceType::PAWN && (mv.to.y - mv.from.y).abs() == 2 {
+            self.can_take_en_passant = Some(mv.from.x);
+        }
+        if mv.from.piece_type == PieceType::KING {
             match self.player_to_move {
                 PlayerColor::WHITE => {
-                    if mv.from.y == 1 && mv.to.y == 3 {
-                        can_take_en_passant = Some(mv.from.x);
-                    }
+                    self.castling_rules.white_can_still_castle_k = false;
+                    self.castling_rules.white_can_still_castle_q = false;
                 }
                 PlayerColor::BLACK => {
-                    if mv.from.y == 6 && mv.to.y == 4 {
-                        can_take_en_passant = Some(mv.from.x);
-                    }
+                    self.castling_rules.black_can_still_castle_k = false;
+                    self.castling_rules.black_can_still_castle_q = false;
                 }
             }
         }
-        let mut pieces = Vec::<Piece>::with_capacity(32);
-        let self_pieces = self.get_pieces_vec();
-        for i in &self_pieces {
-            if has_taken_en_passant
-                && PlayerColor::opposite(self.player_to_move) == i.color
-                && i.piece_type == PieceType::PAWN
-                && i.x == mv.to.x
-                && ((i.color == PlayerColor::BLACK && i.y == 4)
-                    || (i.color == PlayerColor::WHITE && i.y == 3))
-            {
-                continue;
-            }
-            if mv.to.pos() == i.pos() {
-                continue;
+        // a rook leaving its corner or getting captured there takes its castling right with it
+        for pos in [mv.from.pos(), mv.to.pos()].iter() {
+            match (pos.x, pos.y) {
+                (0, 0) => self.castling_rules.white_can_still_castle_q = false,
+                (7, 0) => self.castling_rules.white_can_still_castle_k = false,
+                (0, 7) => self.castling_rules.black_can_still_castle_q = false,
+                (7, 7) => self.castling_rules.black_can_still_castle_k = false,
+                _ => {}
             }
-            if mv.from.pos() == i.pos() {
-                continue;
-            }
-            pieces.push(i.clone());
         }
-        pieces.push(mv.to);
-        let did_q_castle: bool = mv.is_queen_side_castles();
-        let did_k_castle: bool = mv.is_king_side_castles();
-
-        let did_break_castling_k = mv.from.piece_type == PieceType::KING
-            || (mv.from.piece_type == PieceType::ROOK
-                && (mv.from.y == 0 || mv.from.y == 7)
-                && (mv.from.x == 7));
-        let did_break_castling_q = mv.from.piece_type == PieceType::KING
-            || (mv.from.piece_type == PieceType::ROOK
-                && (mv.from.y == 0 || mv.from.y == 7)
-                && (mv.from.x == 0));
-        let mut position_after_move = BoardState::new(
-            pieces,
-            PlayerColor::opposite(self.player_to_move),
-            CastlingRules::new(
-                self.castling_rules.white_can_still_castle_q
-                    && ((!did_break_castling_q && !did_q_castle)
-                        || self.player_to_move != PlayerColor::WHITE),
-                self.castling_rules.white_can_still_castle_k
-                    && ((!did_break_castling_k && !did_k_castle)
-                        || self.player_to_move != PlayerColor::WHITE),
-                self.castling_rules.black_can_still_castle_q
-                    && ((!did_break_castling_q && !did_q_castle)
-                        || self.player_to_move != PlayerColor::BLACK),
-                self.castling_rules.black_can_still_castle_k
-                    && ((!did_break_castling_k && !did_k_castle)
-                        || self.player_to_move != PlayerColor::BLACK),
-            ),
-            can_take_en_passant,
-        );
-        if did_k_castle {
-            position_after_move.player_to_move = self.player_to_move;
-            let y = match self.player_to_move {
-                PlayerColor::WHITE => 0,
-                PlayerColor::BLACK => 7,
-            };
-            let piece_from = Piece::new(7, y, PieceType::ROOK, self.player_to_move);
-            let piece_to = Piece::new(5, y, PieceType::ROOK, self.player_to_move);
-            let mut rook_moved =
-                position_after_move.after_move(&ChessMove::new(piece_from, piece_to));
-            rook_moved.player_to_move = PlayerColor::opposite(self.player_to_move);
-            return rook_moved;
-        }
-        if did_q_castle {
-            position_after_move.player_to_move = self.player_to_move;
-            let y = match self.player_to_move {
-                PlayerColor::WHITE => 0,
-                PlayerColor::BLACK => 7,
-            };
-            let piece_from = Piece::new(0, y, PieceType::ROOK, self.player_to_move);
-            let piece_to = Piece::new(3, y, PieceType::ROOK, self.player_to_move);
-            let mut rook_moved =
-                position_after_move.after_move(&ChessMove::new(piece_from, piece_to));
-            rook_moved.player_to_move = PlayerColor::opposite(self.player_to_move);
-            return rook_moved;
+        if was_takes || has_taken_en_passant || mv.from.piece_type == PieceType::PAWN {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if self.player_to_move == PlayerColor::BLACK {
+            self.fullmove_number += 1;
         }
-        position_after_move
+        self.player_to_move = PlayerColor::opposite(self.player_to_move);
+        self.zobrist_key ^= self.get_state_key();
+        undo_info
+    }
+    /** Takes back the last move made with make_move, restoring the position exactly. */
+    pub fn unmake_move(&mut self, mv: &ChessMove, undo_info: UndoInfo) {
+        self.player_to_move = PlayerColor::opposite(self.player_to_move);
+        if self.player_to_move == PlayerColor::BLACK {
+            self.fullmove_number -= 1;
+        }
+        self.remove_piece(&mv.to);
+        self.put_piece(&mv.from);
+        if let Some(captured) = undo_info.captured {
+            self.put_piece(&captured);
+        }
+        if mv.is_king_side_castles() {
+            self.remove_piece(&Piece::new(5, mv.from.y, PieceType::ROOK, mv.from.color));
+            self.put_piece(&Piece::new(7, mv.from.y, PieceType::ROOK, mv.from.color));
+        }
+        if mv.is_queen_side_castles() {
+            self.remove_piece(&Piece::new(3, mv.from.y, PieceType::ROOK, mv.from.color));
+            self.put_piece(&Piece::new(0, mv.from.y, PieceType::ROOK, mv.from.color));
+        }
+        self.castling_rules = undo_info.castling_rules;
+        self.can_take_en_passant = undo_info.can_take_en_passant;
+        self.halfmove_clock = undo_info.halfmove_clock;
+        self.zobrist_key = undo_info.zobrist_key;
+    }
+    /** Passes the turn without moving anything, for the search to see what a free move would give the
+    opponent. Never legal in a game. */
+    pub fn make_null_move(&mut self) -> UndoInfo {
+        let undo_info = UndoInfo {
+            captured: None,
+            castling_rules: self.castling_rules,
+            can_take_en_passant: self.can_take_en_passant,
+            halfmove_clock: self.halfmove_clock,
+            zobrist_key: self.zobrist_key,
+        };
+        self.zobrist_key ^= self.get_state_key();
+        self.can_take_en_passant = None;
+        self.halfmove_clock += 1;
+        if self.player_to_move == PlayerColor::BLACK {
+            self.fullmove_number += 1;
+        }
+        self.player_to_move = PlayerColor::opposite(self.player_to_move);
+        self.zobrist_key ^= self.get_state_key();
+        undo_info
+    }
+    /** Takes back a move made with make_null_move. */
+    pub fn unmake_null_move(&mut self, undo_info: UndoInfo) {
+        self.player_to_move = PlayerColor::opposite(self.player_to_move);
+        if self.player_to_move == PlayerColor::BLACK {
+            self.fullmove_number -= 1;
+        }
+        self.can_take_en_passant = undo_info.can_take_en_passant;
+        self.halfmove_clock = undo_info.halfmove_clock;
+        self.zobrist_key = undo_info.zobrist_key;
     }
     pub fn get_pieces_vec(&self) -> Vec<Piece> {
-        self.squares
-            .iter()
-            .filter(|thing| thing.is_some())
-            .map(|thing| thing.unwrap())
+        squares(self.get_occupied())
+            .map(|square| self.get_piece_at_square(square_to_pos(square)).unwrap())
             .collect::<Vec<_>>()
     }
     pub fn get_piece_at_square(&self, pos: Vec2<i8>) -> Option<Piece> {
-        self.squares[(pos.x + pos.y * 8) as usize]
+        let bit = square_bit(pos.x, pos.y);
+        let color = if self.color_bitboards[PlayerColor::WHITE.index()] & bit != 0 {
+            PlayerColor::WHITE
+        } else if self.color_bitboards[PlayerColor::BLACK.index()] & bit != 0 {
+            PlayerColor::BLACK
+        } else {
+            return None;
+        };
+        let piece_type = PIECE_TYPES
+            .iter()
+            .find(|piece_type| self.piece_bitboards[piece_type.index()] & bit != 0)
+            .unwrap();
+        Some(Piece::new(pos.x, pos.y, *piece_type, color))
+    }
+    // like get_piece_at_square, but squares off the board are just empty
+    fn try_get_piece(&self, pos: Vec2<i8>) -> Option<Piece> {
+        if !is_within_chessboard(pos) {
+            return None;
+        }
+        self.get_piece_at_square(pos)
     }
     fn evaluate_can_king_side_castle(&self, player_color: PlayerColor) -> bool {
         let y = match player_color {
@@ -301,19 +499,18 @@ impl BoardState {
         {
             return false;
         }
-        // TODO: check for attacked squares.
         let piece_at_4 = self.get_piece_at_square(Vec2::new(4, y));
         let piece_at_7 = self.get_piece_at_square(Vec2::new(7, y));
-        if piece_at_4.is_some()
-            && piece_at_4.unwrap().piece_type == PieceType::KING
-            && piece_at_7.is_some()
-            && piece_at_7.unwrap().piece_type == PieceType::ROOK
-            && self.get_piece_at_square(Vec2::new(5, y)).is_none()
-            && self.get_piece_at_square(Vec2::new(6, y)).is_none()
+        if piece_at_4 != Some(Piece::new(4, y, PieceType::KING, player_color))
+            || piece_at_7 != Some(Piece::new(7, y, PieceType::ROOK, player_color))
+            || self.get_piece_at_square(Vec2::new(5, y)).is_some()
+            || self.get_piece_at_square(Vec2::new(6, y)).is_some()
         {
-            return true;
+            return false;
         }
-        false
+        // the king may not castle out of, through or into check
+        let opponent = PlayerColor::opposite(player_color);
+        (4..=6).all(|x| !self.is_square_attacked(Vec2::new(x, y), opponent))
     }
     fn evaluate_can_queen_side_castle(&self, player_color: PlayerColor) -> bool {
         let y = match player_color {
@@ -332,407 +529,158 @@ impl BoardState {
             return false;
         }
 
-        // TODO: check for attacked squares.
         let piece_there_ey = self.get_piece_at_square(Vec2::new(4, y));
         let piece_there_ay = self.get_piece_at_square(Vec2::new(0, y));
-        if piece_there_ey.is_some()
-            && piece_there_ey.unwrap().piece_type == PieceType::KING
-            && piece_there_ay.is_some()
-            && piece_there_ay.unwrap().piece_type == PieceType::ROOK
-            && self.get_piece_at_square(Vec2::new(1, y)).is_none()
-            && self.get_piece_at_square(Vec2::new(2, y)).is_none()
+        if piece_there_ey != Some(Piece::new(4, y, PieceType::KING, player_color))
+            || piece_there_ay != Some(Piece::new(0, y, PieceType::ROOK, player_color))
+            || (1..=3).any(|x| self.get_piece_at_square(Vec2::new(x, y)).is_some())
         {
-            return true;
-        }
-        false
-    }
-    fn get_move_position_plausibility(&self, p: Vec2<i8>) -> MovePlausibility {
-        // TODO: use 2D array to precompute unoccupied squares
-        // FIXME: retarded clone() usage
-        if !is_within_chessboard(p) {
-            return MovePlausibility::IMPOSSIBLE;
-        }
-        if let Some(piece) = self.get_piece_at_square(p) {
-            if piece.color == self.player_to_move {
-                return MovePlausibility::IMPOSSIBLE;
-            }
-            return MovePlausibility::TAKES;
+            return false;
         }
-
-        MovePlausibility::MOVE
+        // only the squares the king crosses matter, the rook may pass an attacked b-file square
+        let opponent = PlayerColor::opposite(player_color);
+        (2..=4).all(|x| !self.is_square_attacked(Vec2::new(x, y), opponent))
     }
     pub fn evaluate_is_check(&self, color: Option<PlayerColor>) -> bool {
-        if color.is_none() {
-            return self.evaluate_is_check(Some(self.player_to_move));
-        }
-        let self_pieces = self.get_pieces_vec();
-        let king = self_pieces
-            .iter()
-            .find(|thing| thing.piece_type == PieceType::KING && thing.color == color.unwrap());
-        if king.is_none() {
+        let color = color.unwrap_or(self.player_to_move);
+        let king = self.get_bitboard(PieceType::KING, color);
+        if king == 0 {
             panic!("There is no king!");
         }
-        let king_pos = king.unwrap().pos();
-        let moves = self.get_all_plausible_moves();
-        for mv in moves {
-            if mv.to.pos() == king_pos && mv.to.color == PlayerColor::opposite(color.unwrap()) {
-                return true;
-            }
-        }
-        false
-    }
-    // returns boolean to assist with breaking out of loops where it's used.
-    fn add_move_if_legal(
-        &self,
-        piece_to: &Piece,
-        moves: &mut Vec<ChessMove>,
-        piece_from: &Piece,
-    ) -> bool {
-        let move_plausibility =
-            self.get_move_position_plausibility(Vec2::<i8>::new(piece_to.x, piece_to.y));
-        if move_plausibility == MovePlausibility::IMPOSSIBLE {
-            return false;
-        }
-        moves.push(ChessMove::new(piece_from.clone(), piece_to.clone()));
-        if move_plausibility == MovePlausibility::TAKES {
-            return false;
-        }
-        true
+        let king_pos = square_to_pos(king.trailing_zeros() as usize);
+        self.is_square_attacked(king_pos, PlayerColor::opposite(color))
+    }
+    /** Whether any piece of the given color attacks the square, regardless of whose turn it is. */
+    pub fn is_square_attacked(&self, pos: Vec2<i8>, by: PlayerColor) -> bool {
+        self.get_attackers_bitboard(square_index(pos.x, pos.y), self.get_occupied())
+            & self.color_bitboards[by.index()]
+            != 0
+    }
+    /** All pieces of either color that attack the square. Pinned pieces still count as attackers. */
+    pub fn attackers_of(&self, pos: Vec2<i8>) -> Vec<Piece> {
+        squares(self.get_attackers_bitboard(square_index(pos.x, pos.y), self.get_occupied()))
+            .map(|square| self.get_piece_at_square(square_to_pos(square)).unwrap())
+            .collect()
+    }
+    /** The pieces of either color attacking the square if only the occupied squares held pieces,
+    letting sliders see through pieces that are taken off, like in an exchange. */
+    pub fn get_attackers_bitboard(&self, square: usize, occupied: Bitboard) -> Bitboard {
+        let pieces = |piece_type: PieceType| self.piece_bitboards[piece_type.index()];
+        // a pawn attacks the square if a pawn of the other color there would attack the pawn
+        let pawns = (PAWN_ATTACKS[PlayerColor::BLACK.index()][square]
+            & self.get_bitboard(PieceType::PAWN, PlayerColor::WHITE))
+            | (PAWN_ATTACKS[PlayerColor::WHITE.index()][square]
+                & self.get_bitboard(PieceType::PAWN, PlayerColor::BLACK));
+        pawns
+            | (KNIGHT_ATTACKS[square] & pieces(PieceType::KNIGHT))
+            | (KING_ATTACKS[square] & pieces(PieceType::KING))
+            | (bishop_attacks(square, occupied)
+                & (pieces(PieceType::BISHOP) | pieces(PieceType::QUEEN)))
+            | (rook_attacks(square, occupied)
+                & (pieces(PieceType::ROOK) | pieces(PieceType::QUEEN)))
     }
     pub fn get_plausible_moves(&self, p: &Piece) -> Vec<ChessMove> {
         let mut moves = Vec::<ChessMove>::with_capacity(32);
-        // let mut moves = Vec::<ChessMove>::new();
-        let mut in_piece: Piece = p.clone();
-        match in_piece.piece_type {
-            PieceType::BISHOP => {
-                for i in 1..8 {
-                    let pos = Vec2::<i8>::new(p.x + i, p.y + i);
-                    in_piece.x = pos.x;
-                    in_piece.y = pos.y;
-                    if !self.add_move_if_legal(&in_piece, &mut moves, &p) {
-                        break;
-                    }
-                }
-                for i in 1..8 {
-                    let pos = Vec2::<i8>::new(p.x + i, p.y - i);
-                    in_piece.x = pos.x;
-                    in_piece.y = pos.y;
-                    if !self.add_move_if_legal(&in_piece, &mut moves, &p) {
-                        break;
-                    }
-                }
-                for i in 1..8 {
-                    let pos = Vec2::<i8>::new(p.x - i, p.y + i);
-                    in_piece.x = pos.x;
-                    in_piece.y = pos.y;
-                    if !self.add_move_if_legal(&in_piece, &mut moves, &p) {
-                        break;
-                    }
-                }
-                for i in 1..8 {
-                    let pos = Vec2::<i8>::new(p.x - i, p.y - i);
-                    in_piece.x = pos.x;
-                    in_piece.y = pos.y;
-                    if !self.add_move_if_legal(&in_piece, &mut moves, &p) {
-                        break;
-                    }
-                }
-            }
-            PieceType::KNIGHT => {
-                let positions = vec![
-                    Vec2::new(in_piece.x + 2, in_piece.y + 1),
-                    Vec2::new(in_piece.x - 2, in_piece.y + 1),
-                    Vec2::new(in_piece.x + 2, in_piece.y - 1),
-                    Vec2::new(in_piece.x + 1, in_piece.y + 2),
-                    Vec2::new(in_piece.x - 1, in_piece.y + 2),
-                    Vec2::new(in_piece.x - 1, in_piece.y - 2),
-                    Vec2::new(in_piece.x + 1, in_piece.y - 2),
-                    Vec2::new(in_piece.x - 2, in_piece.y - 1),
-                ];
-                for pos in positions {
-                    if !is_within_chessboard(pos) {
-                        continue;
-                    }
-                    let piece_there = self.get_piece_at_square(pos);
-                    if piece_there.is_some() && piece_there.unwrap().color == in_piece.color {
-                        continue;
-                    }
-                    let piece_to = Piece::new(pos.x, pos.y, PieceType::KNIGHT, in_piece.color);
-                    moves.push(ChessMove::new(in_piece, piece_to));
-                }
-            }
+        let square = square_index(p.x, p.y);
+        let occupied = self.get_occupied();
+        let targets = match p.piece_type {
+            PieceType::KNIGHT => KNIGHT_ATTACKS[square],
+            PieceType::BISHOP => bishop_attacks(square, occupied),
+            PieceType::ROOK => rook_attacks(square, occupied),
+            PieceType::QUEEN => queen_attacks(square, occupied),
             PieceType::KING => {
-                // TODO: castling
-                let positions = vec![
-                    Vec2::new(in_piece.x, in_piece.y + 1),
-                    Vec2::new(in_piece.x, in_piece.y - 1),
-                    Vec2::new(in_piece.x + 1, in_piece.y - 1),
-                    Vec2::new(in_piece.x + 1, in_piece.y + 1),
-                    Vec2::new(in_piece.x - 1, in_piece.y + 1),
-                    Vec2::new(in_piece.x - 1, in_piece.y - 1),
-                    Vec2::new(in_piece.x + 1, in_piece.y),
-                    Vec2::new(in_piece.x - 1, in_piece.y),
-                ];
-                for pos in positions {
-                    let piece_to = Piece::new(pos.x, pos.y, PieceType::KING, in_piece.color);
-                    if self.get_move_position_plausibility(Vec2::new(piece_to.x, piece_to.y))
-                        != MovePlausibility::IMPOSSIBLE
-                    {
-                        moves.push(ChessMove::new(in_piece, piece_to));
-                    }
-                }
-                // TODO: intercept castling execution
-                if self.player_to_move == PlayerColor::BLACK {
-                    if self.can_castle_k_this_move {
-                        moves.push(ChessMove::new(
-                            p.clone(),
-                            Piece::new(6, 7, p.piece_type, p.color),
-                        ));
-                    }
-                    if self.can_castle_q_this_move {
-                        moves.push(ChessMove::new(
-                            p.clone(),
-                            Piece::new(2, 7, p.piece_type, p.color),
-                        ));
-                    }
-                }
-                if self.player_to_move == PlayerColor::WHITE {
-                    if self.can_castle_k_this_move {
-                        moves.push(ChessMove::new(
-                            p.clone(),
-                            Piece::new(6, 0, p.piece_type, p.color),
-                        ));
-                    }
-                    if self.can_castle_q_this_move {
-                        moves.push(ChessMove::new(
-                            p.clone(),
-                            Piece::new(2, 0, p.piece_type, p.color),
-                        ));
-                    }
-                }
+                self.add_castling_moves(&mut moves, p);
+                KING_ATTACKS[square]
             }
-            PieceType::ROOK => {
-                for i in 1..8 {
-                    let pos = Vec2::new(p.x, p.y + i);
-                    in_piece.x = pos.x;
-                    in_piece.y = pos.y;
-                    if !self.add_move_if_legal(&in_piece, &mut moves, &p) {
-                        break;
-                    }
-                }
-                for i in 1..8 {
-                    let pos = Vec2::new(p.x, p.y - i);
-                    in_piece.x = pos.x;
-                    in_piece.y = pos.y;
-                    if !self.add_move_if_legal(&in_piece, &mut moves, &p) {
-                        break;
-                    }
-                }
-                for i in 1..8 {
-                    let pos = Vec2::new(p.x + i, p.y);
-                    in_piece.x = pos.x;
-                    in_piece.y = pos.y;
-                    if !self.add_move_if_legal(&in_piece, &mut moves, &p) {
-                        break;
-                    }
-                }
-                for i in 1..8 {
-                    let pos = Vec2::new(p.x - i, p.y);
-                    in_piece.x = pos.x;
-                    in_piece.y = pos.y;
-                    if !self.add_move_if_legal(&in_piece, &mut moves, &p) {
-                        break;
-                    }
-                }
+            PieceType::PAWN => {
+                self.add_pawn_moves(&mut moves, p);
+                return moves;
             }
-            PieceType::QUEEN => {
-                let bishop_moves = self.get_plausible_moves(&Piece::new(
-                    in_piece.x,
-                    in_piece.y,
-                    PieceType::BISHOP,
-                    in_piece.color,
-                ));
-                let convert_move = |b_move: &ChessMove| {
-                    let piece_from = in_piece;
-                    let piece_to =
-                        Piece::new(b_move.to.x, b_move.to.y, PieceType::QUEEN, b_move.to.color);
-                    ChessMove::new(piece_from, piece_to)
-                };
-                let queen_diagonal_moves = bishop_moves.iter().map(convert_move);
-                let rook_moves = self.get_plausible_moves(&Piece::new(
-                    in_piece.x,
-                    in_piece.y,
-                    PieceType::ROOK,
-                    in_piece.color,
+        };
+        for target in squares(targets & !self.color_bitboards[p.color.index()]) {
+            let pos = square_to_pos(target);
+            moves.push(ChessMove::new(
+                *p,
+                Piece::new(pos.x, pos.y, p.piece_type, p.color),
+            ));
+        }
+        moves
+    }
+    fn add_castling_moves(&self, moves: &mut Vec<ChessMove>, king: &Piece) {
+        if king.color != self.player_to_move {
+            return;
+        }
+        if self.evaluate_can_king_side_castle(king.color) {
+            moves.push(ChessMove::new(
+                *king,
+                Piece::new(6, king.y, PieceType::KING, king.color),
+            ));
+        }
+        if self.evaluate_can_queen_side_castle(king.color) {
+            moves.push(ChessMove::new(
+                *king,
+                Piece::new(2, king.y, PieceType::KING, king.color),
+            ));
+        }
+    }
+    fn add_pawn_moves(&self, moves: &mut Vec<ChessMove>, pawn: &Piece) {
+        let (direction, starting_rank, en_passant_rank) = match pawn.color {
+            PlayerColor::WHITE => (1, 1, 4),
+            PlayerColor::BLACK => (-1, 6, 3),
+        };
+        // normal move
+        let one_step = Vec2::new(pawn.x, pawn.y + direction);
+        if self.get_piece_at_square(one_step).is_none() {
+            BoardState::add_pawn_move(moves, pawn, one_step.x, one_step.y);
+            // double step
+            let two_steps = Vec2::new(pawn.x, pawn.y + 2 * direction);
+            if pawn.y == starting_rank && self.get_piece_at_square(two_steps).is_none() {
+                moves.push(ChessMove::new(
+                    *pawn,
+                    Piece::new(two_steps.x, two_steps.y, PieceType::PAWN, pawn.color),
                 ));
-                let queen_straight_moves = rook_moves.iter().map(convert_move);
-                moves.append(&mut queen_diagonal_moves.collect());
-                moves.append(&mut queen_straight_moves.collect());
             }
-            PieceType::PAWN => {
-                // TODO: promotions
-                // en passant
-                if let Some(en_passant_x_coord) = self.can_take_en_passant {
-                    if (en_passant_x_coord == in_piece.x + 1
-                        || en_passant_x_coord == in_piece.x - 1)
-                        && !(in_piece.color == PlayerColor::BLACK && in_piece.y != 3)
-                        && !(in_piece.color == PlayerColor::WHITE && in_piece.y != 4)
-                    {
-                        let y_to = match self.player_to_move {
-                            PlayerColor::BLACK => 2,
-                            PlayerColor::WHITE => 5,
-                        };
-                        let new_piece =
-                            Piece::new(en_passant_x_coord, y_to, PieceType::PAWN, in_piece.color);
-                        let move_to_make = ChessMove::new(in_piece.clone(), new_piece);
-                        moves.push(move_to_make);
-                    }
-                }
-
-                // taking
-                match in_piece.color {
-                    PlayerColor::WHITE => {
-                        match self.get_piece_at_square(Vec2::new(in_piece.x - 1, in_piece.y + 1)) {
-                            None => {}
-                            Some(i) => {
-                                if i.color != in_piece.color {
-                                    let piece_to =
-                                        Piece::new(i.x, i.y, PieceType::PAWN, in_piece.color);
-                                    moves.push(ChessMove::new(in_piece, piece_to));
-                                }
-                            }
-                        }
-                    }
-                    PlayerColor::BLACK => {
-                        match self.get_piece_at_square(Vec2::new(in_piece.x - 1, in_piece.y - 1)) {
-                            None => {}
-                            Some(i) => {
-                                if i.color != in_piece.color {
-                                    let piece_to = Piece::new(
-                                        i.x,
-                                        i.y,
-                                        PieceType::PAWN,
-                                        in_piece.color,
-                                    );
-                                    moves.push(ChessMove::new(in_piece, piece_to));
-                                }
-                            }
-                        }
-                    }
-                }
-                match in_piece.color {
-                    PlayerColor::WHITE => {
-                        match self.get_piece_at_square(Vec2::new(in_piece.x + 1, in_piece.y + 1)) {
-                            None => {}
-                            Some(i) => {
-                                if i.color != in_piece.color {
-                                    let piece_to = Piece::new(
-                                        in_piece.x + 1,
-                                        in_piece.y + 1,
-                                        PieceType::PAWN,
-                                        in_piece.color,
-                                    );
-                                    moves.push(ChessMove::new(in_piece, piece_to));
-                                }
-                            }
-                        }
-                    }
-                    PlayerColor::BLACK => {
-                        match self.get_piece_at_square(Vec2::new(in_piece.x + 1, in_piece.y - 1)) {
-                            None => {}
-                            Some(i) => {
-                                if i.color != in_piece.color {
-                                    let piece_to = Piece::new(
-                                        in_piece.x + 1,
-                                        in_piece.y - 1,
-                                        PieceType::PAWN,
-                                        in_piece.color,
-                                    );
-                                    moves.push(ChessMove::new(in_piece, piece_to));
-                                }
-                            }
-                        }
-                    }
-                }
-                // normal move
-                match in_piece.color {
-                    PlayerColor::WHITE => {
-                        match self.get_piece_at_square(Vec2::new(in_piece.x, in_piece.y + 1)) {
-                            None => {
-                                let piece_to = Piece::new(
-                                    in_piece.x,
-                                    in_piece.y + 1,
-                                    in_piece.piece_type,
-                                    in_piece.color,
-                                );
-                                moves.push(ChessMove::new(in_piece, piece_to));
-                                // double step
-                                if in_piece.y == 1 {
-                                    match self
-                                        .get_piece_at_square(Vec2::new(in_piece.x, in_piece.y + 2))
-                                    {
-                                        Some(_) => {}
-                                        None => {
-                                            let piece_to = Piece::new(
-                                                in_piece.x,
-                                                in_piece.y + 2,
-                                                PieceType::PAWN,
-                                                in_piece.color,
-                                            );
-                                            moves.push(ChessMove::new(in_piece, piece_to));
-                                        }
-                                    }
-                                }
-                            }
-                            Some(_) => {}
-                        }
-                    }
-                    PlayerColor::BLACK => {
-                        match self.get_piece_at_square(Vec2::new(in_piece.x, in_piece.y - 1)) {
-                            None => {
-                                let piece_to = Piece::new(
-                                    in_piece.x,
-                                    in_piece.y - 1,
-                                    in_piece.piece_type,
-                                    in_piece.color,
-                                );
-                                moves.push(ChessMove::new(in_piece, piece_to));
-                                // double step
-                                if in_piece.y == 6 {
-                                    match self
-                                        .get_piece_at_square(Vec2::new(in_piece.x, in_piece.y - 2))
-                                    {
-                                        Some(_) => {}
-                                        None => {
-                                            let piece_to = Piece::new(
-                                                in_piece.x,
-                                                in_piece.y - 2,
-                                                PieceType::PAWN,
-                                                in_piece.color,
-                                            );
-                                            moves.push(ChessMove::new(in_piece, piece_to));
-                                        }
-                                    }
-                                }
-                            }
-                            Some(_) => {}
-                        }
-                    }
-                }
+        }
+        // taking
+        let enemies = self.color_bitboards[PlayerColor::opposite(pawn.color).index()];
+        let attacks = PAWN_ATTACKS[pawn.color.index()][square_index(pawn.x, pawn.y)];
+        for target in squares(attacks & enemies) {
+            let pos = square_to_pos(target);
+            BoardState::add_pawn_move(moves, pawn, pos.x, pos.y);
+        }
+        // en passant
+        if let Some(en_passant_x_coord) = self.can_take_en_passant {
+            if pawn.color == self.player_to_move
+                && pawn.y == en_passant_rank
+                && (pawn.x - en_passant_x_coord).abs() == 1
+            {
+                moves.push(ChessMove::new(
+                    *pawn,
+                    Piece::new(
+                        en_passant_x_coord,
+                        pawn.y + direction,
+                        PieceType::PAWN,
+                        pawn.color,
+                    ),
+                ));
             }
         }
-        moves
     }
-    fn get_all_plausible_moves(&self) -> Vec<ChessMove> {
-        let mut moves = Vec::<ChessMove>::with_capacity(32);
-        // let mut moves = Vec::<ChessMove>::new();
-        let pieces = self.get_pieces_vec();
-        for piece in pieces {
-            if piece.color == self.player_to_move {
-                moves.append(&mut self.get_plausible_moves(&piece));
+    // pawns reaching the last rank get one move per piece they can promote to
+    fn add_pawn_move(moves: &mut Vec<ChessMove>, pawn: &Piece, x: i8, y: i8) {
+        if y == 0 || y == 7 {
+            for piece_type in PROMOTION_PIECE_TYPES.iter() {
+                moves.push(ChessMove::new(
+                    *pawn,
+                    Piece::new(x, y, *piece_type, pawn.color),
+                ));
             }
+            return;
         }
-        moves
+        moves.push(ChessMove::new(
+            *pawn,
+            Piece::new(x, y, PieceType::PAWN, pawn.color),
+        ));
     }
     pub fn get_legal_moves(&self, p: &Piece) -> Vec<ChessMove> {
         let plausible_moves: Vec<ChessMove> = self.get_plausible_moves(p);
@@ -748,15 +696,38 @@ impl BoardState {
     }
     pub fn get_all_legal_moves(&self) -> Vec<ChessMove> {
         let mut legal_moves = Vec::<ChessMove>::with_capacity(32);
-        // let mut legal_moves = Vec::<ChessMove>::new();
-        for p in self.get_pieces_vec().iter() {
-            if p.color != self.player_to_move {
-                continue;
-            }
-            legal_moves.append(&mut self.get_legal_moves(&p));
+        for square in squares(self.color_bitboards[self.player_to_move.index()]) {
+            let piece = self.get_piece_at_square(square_to_pos(square)).unwrap();
+            legal_moves.append(&mut self.get_legal_moves(&piece));
         }
         legal_moves
     }
+    /** The legal captures and promotions, the moves that change the material on the board. */
+    pub fn get_all_legal_captures(&self) -> Vec<ChessMove> {
+        let mut captures = Vec::<ChessMove>::with_capacity(16);
+        for square in squares(self.color_bitboards[self.player_to_move.index()]) {
+            let piece = self.get_piece_at_square(square_to_pos(square)).unwrap();
+            // filtering before the legality check saves making all the quiet moves
+            captures.extend(
+                self.get_plausible_moves(&piece)
+                    .into_iter()
+                    .filter(|mv| self.is_capture(mv) || mv.promotion().is_some())
+                    .filter(|mv| {
+                        !self
+                            .after_move(mv)
+                            .evaluate_is_check(Some(self.player_to_move))
+                    }),
+            );
+        }
+        captures
+    }
+    /** Whether the move takes a piece, en passant included. */
+    pub fn is_capture(&self, mv: &ChessMove) -> bool {
+        self.color_bitboards[PlayerColor::opposite(mv.from.color).index()]
+            & square_bit(mv.to.x, mv.to.y)
+            != 0
+            || (mv.from.piece_type == PieceType::PAWN && mv.from.x != mv.to.x)
+    }
 }
 impl Piece {
     pub fn new(x: i8, y: i8, piece_type: PieceType, color: PlayerColor) -> Piece {
@@ -775,6 +746,12 @@ impl ChessMove {
     pub fn new(from: Piece, to: Piece) -> ChessMove {
         ChessMove { from: from, to: to }
     }
+    pub fn promotion(&self) -> Option<PieceType> {
+        if self.from.piece_type != self.to.piece_type {
+            return Some(self.to.piece_type);
+        }
+        None
+    }
     pub fn is_king_side_castles(&self) -> bool {
         if self.from.piece_type != PieceType::KING {
             return false;
@@ -812,7 +789,25 @@ impl ChessMove {
         true
     }
 }
+impl PieceType {
+    pub fn index(self) -> usize {
+        match self {
+            PieceType::PAWN => 0,
+            PieceType::ROOK => 1,
+            PieceType::BISHOP => 2,
+            PieceType::KNIGHT => 3,
+            PieceType::KING => 4,
+            PieceType::QUEEN => 5,
+        }
+    }
+}
 impl PlayerColor {
+    pub fn index(self) -> usize {
+        match self {
+            PlayerColor::WHITE => 0,
+            PlayerColor::BLACK => 1,
+        }
+    }
     pub fn opposite(color: PlayerColor) -> PlayerColor {
         if color == PlayerColor::WHITE {
             return PlayerColor::BLACK;
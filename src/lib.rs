@@ -0,0 +1,19 @@
+pub mod bitboard;
+pub mod book;
+pub mod engine;
+pub mod evaluation;
+pub mod fen;
+pub mod game;
+pub mod game_types;
+pub mod move_ordering;
+pub mod perft;
+pub mod personality;
+pub mod pgn;
+pub mod random;
+pub mod review;
+pub mod san;
+pub mod search;
+pub mod see;
+pub mod tablebase;
+pub mod transposition;
+pub mod zobrist;
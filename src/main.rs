@@ -1,8 +1,8 @@
-mod game;
-mod game_types;
 mod scenes;
 mod ui;
-mod engine;
+use game_types::{BoardState, GameHistory};
+use lil_chess::{engine, game, game_types, personality, pgn, review, search};
+use pgn::PgnReader;
 use scenes::{MenuScene, Scene, Transition};
 use tetra::graphics;
 use tetra::graphics::text::Text;
@@ -17,8 +17,11 @@ struct GameState {
     scenes: Vec<Box<dyn Scene>>,
 }
 impl GameState {
-    fn new(ctx: &mut tetra::Context) -> tetra::Result<GameState> {
-        let initial_scene = MenuScene::new(ctx)?;
+    fn new(
+        ctx: &mut tetra::Context,
+        starting_game: Option<GameHistory>,
+    ) -> tetra::Result<GameState> {
+        let initial_scene = MenuScene::new(ctx, starting_game)?;
         Ok(GameState {
             scenes: vec![Box::new(initial_scene)],
         })
@@ -153,11 +156,48 @@ impl Assets {
     }
 }
 
+// games start from the position given as the first argument in FEN, if there is one
+fn load_position(fen: &str) -> BoardState {
+    match BoardState::from_fen(fen) {
+        Ok(board) => board,
+        Err(e) => {
+            eprintln!("Invalid starting position '{}': {}", fen, e);
+            std::process::exit(1);
+        }
+    }
+}
+// or they continue the first game of a PGN file
+fn load_game(path: &str) -> GameHistory {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("Can't read '{}': {}", path, e);
+            std::process::exit(1);
+        }
+    };
+    match PgnReader::new(&text).next() {
+        Some(Ok(game)) => game.history,
+        Some(Err(e)) => {
+            eprintln!("Can't load the game in '{}': {}", path, e);
+            std::process::exit(1);
+        }
+        None => {
+            eprintln!("There is no game in '{}'", path);
+            std::process::exit(1);
+        }
+    }
+}
+
 #[allow(unused_must_use)]
 fn main() -> tetra::Result {
+    let starting_game = match std::env::args().nth(1) {
+        Some(path) if path.ends_with(".pgn") => Some(load_game(&path)),
+        Some(fen) => Some(GameHistory::new(vec![load_position(&fen)], None)),
+        None => None,
+    };
     tetra::ContextBuilder::new("Lil' chess client", 1280, 720)
         .quit_on_escape(true)
         .show_mouse(true)
         .build()?
-        .run(|ctx| GameState::new(ctx))
+        .run(|ctx| GameState::new(ctx, starting_game))
 }
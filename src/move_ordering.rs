@@ -0,0 +1,266 @@
+use crate::engine::Engine;
+use crate::game_types::*;
+use crate::search::MAX_DEPTH;
+
+// history scores stay within this, so that recent cutoffs can still outweigh old ones
+const MAX_HISTORY: i32 = 16_384;
+const KILLER_MOVES_PER_PLY: usize = 2;
+
+/** Which of the heuristics for quiet moves are used. The hash move always goes first and captures are
+always ordered by MVV-LVA and SEE. */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct OrderingHeuristics {
+    pub killer_moves: bool,
+    pub history: bool,
+    pub counter_moves: bool,
+}
+/** What the search has learned about which quiet moves tend to cut off, kept between the nodes of a
+search to try those moves early elsewhere. */
+pub struct MoveOrdering {
+    pub heuristics: OrderingHeuristics,
+    // quiet moves that cut off at the same ply in other positions, the latest first
+    killer_moves: Vec<[Option<ChessMove>; KILLER_MOVES_PER_PLY]>,
+    // by color, from square and to square, how well a quiet move has done, weighted by depth
+    history: Vec<i32>,
+    // by the color and type of the piece the opponent just moved and where it went, the quiet move
+    // that refuted it
+    counter_moves: Vec<Option<ChessMove>>,
+}
+/** Hands out the moves of a node in stages, each only sorted when it's reached, since a cutoff often
+comes before the later ones are needed: the hash move, captures that don't lose material, killer
+moves and the counter move, the other quiet moves by history, and last captures that lose material. */
+pub struct MovePicker {
+    stage: Stage,
+    hash_move: Option<ChessMove>,
+    // captures and promotions with their MVV-LVA scores
+    captures: Vec<(ChessMove, i32)>,
+    // killer moves then the counter move, the ones that are legal quiet moves here
+    refutations: Vec<ChessMove>,
+    // with their history scores
+    quiets: Vec<(ChessMove, i32)>,
+    bad_captures: Vec<ChessMove>,
+}
+#[derive(Clone, Copy, PartialEq)]
+enum Stage {
+    HashMove,
+    GoodCaptures,
+    Refutations,
+    Quiets,
+    BadCaptures,
+}
+
+impl Default for OrderingHeuristics {
+    fn default() -> OrderingHeuristics {
+        OrderingHeuristics {
+            killer_moves: true,
+            history: true,
+            counter_moves: true,
+        }
+    }
+}
+impl OrderingHeuristics {
+    /** Only the hash move and the captures get ordered. */
+    pub fn none() -> OrderingHeuristics {
+        OrderingHeuristics {
+            killer_moves: false,
+            history: false,
+            counter_moves: false,
+        }
+    }
+}
+
+impl MoveOrdering {
+    pub fn new() -> MoveOrdering {
+        MoveOrdering {
+            heuristics: OrderingHeuristics::default(),
+            killer_moves: vec![[None; KILLER_MOVES_PER_PLY]; MAX_DEPTH as usize + 1],
+            history: vec![0; 2 * 64 * 64],
+            counter_moves: vec![None; 2 * 6 * 64],
+        }
+    }
+    /** Forgets everything, for a new game. */
+    pub fn clear(&mut self) {
+        *self = MoveOrdering {
+            heuristics: self.heuristics,
+            ..MoveOrdering::new()
+        };
+    }
+    /** Killer moves are about the positions of one search, the history carries over but counts less. */
+    pub fn new_search(&mut self) {
+        for killer_moves in self.killer_moves.iter_mut() {
+            *killer_moves = [None; KILLER_MOVES_PER_PLY];
+        }
+        for score in self.history.iter_mut() {
+            *score /= 2;
+        }
+    }
+    /** The quiet moves worth trying right after the captures, at the ply and after the opponent's move. */
+    pub fn get_refutations(&self, ply: i32, previous_move: Option<ChessMove>) -> Vec<ChessMove> {
+        let mut refutations = Vec::with_capacity(KILLER_MOVES_PER_PLY + 1);
+        if self.heuristics.killer_moves {
+            if let Some(killer_moves) = self.killer_moves.get(ply as usize) {
+                refutations.extend(killer_moves.iter().flatten());
+            }
+        }
+        if self.heuristics.counter_moves {
+            let counter_move = previous_move.and_then(|mv| self.counter_moves[counter_index(&mv)]);
+            if let Some(counter_move) = counter_move {
+                if !refutations.contains(&counter_move) {
+                    refutations.push(counter_move);
+                }
+            }
+        }
+        refutations
+    }
+    pub fn get_history(&self, mv: &ChessMove) -> i32 {
+        if !self.heuristics.history {
+            return 0;
+        }
+        self.history[history_index(mv)]
+    }
+    /** Remembers a quiet move that caused a beta cutoff, and that the quiet moves searched before it
+    didn't. */
+    pub fn record_cutoff(
+        &mut self,
+        mv: ChessMove,
+        ply: i32,
+        depth: u32,
+        previous_move: Option<ChessMove>,
+        searched_quiets: &[ChessMove],
+    ) {
+        if let Some(killer_moves) = self.killer_moves.get_mut(ply as usize) {
+            if killer_moves[0] != Some(mv) {
+                killer_moves[1] = killer_moves[0];
+                killer_moves[0] = Some(mv);
+            }
+        }
+        if let Some(previous_move) = previous_move {
+            self.counter_moves[counter_index(&previous_move)] = Some(mv);
+        }
+        let bonus = (depth * depth) as i32;
+        self.update_history(&mv, bonus);
+        for other in searched_quiets.iter().filter(|other| **other != mv) {
+            self.update_history(other, -bonus);
+        }
+    }
+    // the more a score already leans one way the less it moves further, so it never leaves the bounds
+    fn update_history(&mut self, mv: &ChessMove, bonus: i32) {
+        let bonus = bonus.clamp(-MAX_HISTORY, MAX_HISTORY);
+        let score = &mut self.history[history_index(mv)];
+        *score += bonus - *score * bonus.abs() / MAX_HISTORY;
+    }
+}
+impl Default for MoveOrdering {
+    fn default() -> MoveOrdering {
+        MoveOrdering::new()
+    }
+}
+fn history_index(mv: &ChessMove) -> usize {
+    let from = (mv.from.x + mv.from.y * 8) as usize;
+    let to = (mv.to.x + mv.to.y * 8) as usize;
+    mv.from.color.index() * 64 * 64 + from * 64 + to
+}
+fn counter_index(mv: &ChessMove) -> usize {
+    let to = (mv.to.x + mv.to.y * 8) as usize;
+    (mv.from.color.index() * 6 + mv.from.piece_type.index()) * 64 + to
+}
+/** Captures and promotions, the moves that change the material. */
+pub fn is_tactical(board: &BoardState, mv: &ChessMove) -> bool {
+    board.is_capture(mv) || mv.promotion().is_some()
+}
+/** Most valuable victim, least valuable attacker: the bigger the piece taken the better, and of the
+pieces that can take it the smaller the better. */
+pub fn get_mvv_lva_score(board: &BoardState, mv: &ChessMove) -> i32 {
+    let victim = match board.get_piece_at_square(mv.to.pos()) {
+        Some(victim) => Engine::get_piece_worth(&victim),
+        None if board.is_capture(mv) => Engine::get_piece_type_worth(PieceType::PAWN),
+        None => 0,
+    };
+    let promotion = mv.promotion().map_or(0, Engine::get_piece_type_worth);
+    (victim + promotion) * 100 - Engine::get_piece_type_worth(mv.from.piece_type)
+}
+
+impl MovePicker {
+    pub fn new(
+        board: &BoardState,
+        moves: Vec<ChessMove>,
+        hash_move: Option<ChessMove>,
+        refutations: &[ChessMove],
+        ordering: &MoveOrdering,
+    ) -> MovePicker {
+        let mut picker = MovePicker {
+            stage: Stage::HashMove,
+            hash_move: None,
+            captures: Vec::new(),
+            refutations: Vec::new(),
+            quiets: Vec::with_capacity(moves.len()),
+            bad_captures: Vec::new(),
+        };
+        for mv in moves {
+            if Some(mv) == hash_move {
+                picker.hash_move = hash_move;
+            } else if is_tactical(board, &mv) {
+                picker.captures.push((mv, get_mvv_lva_score(board, &mv)));
+            } else if refutations.contains(&mv) {
+                picker.refutations.push(mv);
+            } else {
+                picker.quiets.push((mv, ordering.get_history(&mv)));
+            }
+        }
+        // in the order they were given, the legal ones
+        picker
+            .refutations
+            .sort_by_key(|mv| refutations.iter().position(|other| other == mv));
+        picker
+    }
+    /** The next move to search, None once they've all been handed out. */
+    pub fn next(&mut self, board: &BoardState) -> Option<ChessMove> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::GoodCaptures;
+                    if self.hash_move.is_some() {
+                        return self.hash_move;
+                    }
+                }
+                Stage::GoodCaptures => match pick_best(&mut self.captures) {
+                    Some(mv) => {
+                        if mv.promotion().is_none() && board.static_exchange_evaluation(&mv) < 0 {
+                            self.bad_captures.push(mv);
+                            continue;
+                        }
+                        return Some(mv);
+                    }
+                    None => self.stage = Stage::Refutations,
+                },
+                Stage::Refutations => {
+                    if self.refutations.is_empty() {
+                        self.stage = Stage::Quiets;
+                    } else {
+                        return Some(self.refutations.remove(0));
+                    }
+                }
+                Stage::Quiets => match pick_best(&mut self.quiets) {
+                    Some(mv) => return Some(mv),
+                    None => self.stage = Stage::BadCaptures,
+                },
+                Stage::BadCaptures => {
+                    if self.bad_captures.is_empty() {
+                        return None;
+                    }
+                    return Some(self.bad_captures.remove(0));
+                }
+            }
+        }
+    }
+}
+// takes out the highest scored move, of equal ones the first
+fn pick_best(moves: &mut Vec<(ChessMove, i32)>) -> Option<ChessMove> {
+    let mut best: Option<(usize, i32)> = None;
+    for (i, (_, score)) in moves.iter().enumerate() {
+        if best.is_none_or(|(_, best_score)| *score > best_score) {
+            best = Some((i, *score));
+        }
+    }
+    best.map(|(i, _)| moves.remove(i).0)
+}
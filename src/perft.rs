@@ -0,0 +1,36 @@
+use crate::game_types::*;
+
+impl BoardState {
+    /** Counts the leaf nodes of the legal move tree, the standard way of checking a move generator. */
+    pub fn perft(&self, depth: u32) -> u64 {
+        self.clone().perft_in_place(depth)
+    }
+    // walking the tree with make_move/unmake_move checks that they restore the position too
+    fn perft_in_place(&mut self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.get_all_legal_moves();
+        // the leaves don't need to be made to be counted
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        let mut nodes = 0;
+        for mv in moves.iter() {
+            let undo_info = self.make_move(mv);
+            nodes += self.perft_in_place(depth - 1);
+            self.unmake_move(mv, undo_info);
+        }
+        nodes
+    }
+    /** Perft split by the first move, for finding which move the generator gets wrong. */
+    pub fn divide(&self, depth: u32) -> Vec<(ChessMove, u64)> {
+        if depth == 0 {
+            return Vec::new();
+        }
+        self.get_all_legal_moves()
+            .into_iter()
+            .map(|mv| (mv, self.after_move(&mv).perft(depth - 1)))
+            .collect()
+    }
+}
@@ -0,0 +1,324 @@
+use crate::evaluation::{EvaluationWeights, TaperedWeight};
+use crate::game_types::*;
+use crate::random::Random;
+use crate::search::{is_mate_score, SearchLimits, SearchResult};
+use std::thread;
+
+pub const MAX_SKILL_LEVEL: u32 = 10;
+// the number of moves a weakened engine chooses from
+const CANDIDATE_MOVES: usize = 4;
+// blunders need worse moves to pick from than the candidates
+const BLUNDER_CANDIDATE_MOVES: usize = 8;
+// a blunder is at least this much worse than the best move, if one that bad was searched
+const BLUNDER_MARGIN: i32 = 150;
+// how much worse than the best move a move may be and still get played, per level below the top
+const MARGIN_PER_LEVEL: i32 = 25;
+const SEARCH_NODES_AT_LEVEL_ZERO: u64 = 500;
+
+/** Playing styles, each shifting the evaluation weights towards what it cares about. */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EngineStyle {
+    Balanced,
+    Aggressive,
+    Positional,
+    Materialistic,
+}
+pub const ENGINE_STYLES: [EngineStyle; 4] = [
+    EngineStyle::Balanced,
+    EngineStyle::Aggressive,
+    EngineStyle::Positional,
+    EngineStyle::Materialistic,
+];
+/** How strong the engine plays and in what style. */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct EngineSettings {
+    // from 0 up to MAX_SKILL_LEVEL, which is the engine's full strength
+    pub skill_level: u32,
+    pub style: EngineStyle,
+    // every now and then play a clearly worse move, more often the lower the level
+    pub simulate_blunders: bool,
+    // how many threads search at once, only a single one plays a position the same way every time
+    pub threads: usize,
+}
+
+impl EngineStyle {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EngineStyle::Balanced => "Balanced",
+            EngineStyle::Aggressive => "Aggressive",
+            EngineStyle::Positional => "Positional",
+            EngineStyle::Materialistic => "Materialistic",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<EngineStyle> {
+        ENGINE_STYLES
+            .iter()
+            .copied()
+            .find(|style| style.name().eq_ignore_ascii_case(name))
+    }
+    /** The style after this one in ENGINE_STYLES, wrapping around. */
+    pub fn next(&self) -> EngineStyle {
+        let index = ENGINE_STYLES
+            .iter()
+            .position(|style| style == self)
+            .unwrap();
+        ENGINE_STYLES[(index + 1) % ENGINE_STYLES.len()]
+    }
+    /** Scales the weights the style cares about up, and the ones it doesn't down. */
+    pub fn apply(&self, weights: &mut EvaluationWeights) {
+        match self {
+            EngineStyle::Balanced => {}
+            EngineStyle::Aggressive => {
+                scale_all(&mut weights.king_attack, 200);
+                scale_all(&mut weights.mobility, 130);
+                scale(&mut weights.king_shelter, 60);
+            }
+            EngineStyle::Positional => {
+                scale_all(&mut weights.mobility, 150);
+                for table in weights.piece_square_tables.iter_mut() {
+                    scale_all(table, 130);
+                }
+                scale_all(&mut weights.passed_pawn, 130);
+                scale(&mut weights.doubled_pawn, 150);
+                scale(&mut weights.isolated_pawn, 150);
+                scale(&mut weights.bishop_pair, 150);
+                scale(&mut weights.rook_on_open_file, 150);
+                scale(&mut weights.rook_on_half_open_file, 150);
+                scale_all(&mut weights.king_attack, 70);
+            }
+            EngineStyle::Materialistic => {
+                scale_all(&mut weights.piece_values, 120);
+                scale_all(&mut weights.mobility, 50);
+                for table in weights.piece_square_tables.iter_mut() {
+                    scale_all(table, 60);
+                }
+                scale_all(&mut weights.king_attack, 50);
+            }
+        }
+    }
+}
+fn scale(weight: &mut TaperedWeight, percent: i32) {
+    *weight = TaperedWeight::new(
+        weight.midgame * percent / 100,
+        weight.endgame * percent / 100,
+    );
+}
+fn scale_all(weights: &mut [TaperedWeight], percent: i32) {
+    for weight in weights.iter_mut() {
+        scale(weight, percent);
+    }
+}
+
+impl Default for EngineSettings {
+    fn default() -> EngineSettings {
+        EngineSettings {
+            skill_level: MAX_SKILL_LEVEL,
+            style: EngineStyle::Balanced,
+            simulate_blunders: false,
+            threads: get_default_thread_count(),
+        }
+    }
+}
+/** The cores of the machine, the most threads it makes sense to search with. */
+pub fn get_max_thread_count() -> usize {
+    thread::available_parallelism().map_or(1, |cores| cores.get())
+}
+/** All the cores but one, which is left for drawing the game. */
+pub fn get_default_thread_count() -> usize {
+    (get_max_thread_count() - 1).max(1)
+}
+impl EngineSettings {
+    /** Full strength always plays the best move it finds. */
+    pub fn is_full_strength(&self) -> bool {
+        self.skill_level >= MAX_SKILL_LEVEL && !self.simulate_blunders
+    }
+    fn levels_below_max(&self) -> u32 {
+        MAX_SKILL_LEVEL - self.skill_level.min(MAX_SKILL_LEVEL)
+    }
+    /** The limits tightened to the skill level: lower levels see fewer plies and fewer nodes. */
+    pub fn limit_search(&self, limits: &SearchLimits) -> SearchLimits {
+        if self.skill_level >= MAX_SKILL_LEVEL {
+            return limits.clone();
+        }
+        let depth = self.skill_level + 1;
+        let nodes = SEARCH_NODES_AT_LEVEL_ZERO << self.skill_level;
+        SearchLimits {
+            depth: Some(limits.depth.map_or(depth, |limit| limit.min(depth))),
+            nodes: Some(limits.nodes.map_or(nodes, |limit| limit.min(nodes))),
+            ..limits.clone()
+        }
+    }
+    /** How many lines the search should give the move choice to pick from. */
+    pub fn multi_pv(&self) -> usize {
+        if self.is_full_strength() {
+            1
+        } else if self.simulate_blunders {
+            BLUNDER_CANDIDATE_MOVES
+        } else {
+            CANDIDATE_MOVES
+        }
+    }
+    fn blunder_chance(&self) -> f64 {
+        if !self.simulate_blunders {
+            return 0.0;
+        }
+        0.02 + 0.02 * self.levels_below_max() as f64
+    }
+    /** Picks the move to play among the lines of the search: a random one of those close enough to
+    the best, the margin growing as the level goes down, or on a blunder one that's clearly worse.
+    Mates are never passed up for a move that isn't one. */
+    pub fn choose_move(&self, result: &SearchResult, random: &mut Random) -> Option<ChessMove> {
+        if self.is_full_strength() || result.lines.len() < 2 {
+            return result.best_move;
+        }
+        let best_score = result.lines[0].score;
+        if is_mate_score(best_score) && best_score > 0 {
+            return result.best_move;
+        }
+        let candidates: Vec<_> = if random.chance(self.blunder_chance()) {
+            let blunders: Vec<_> = result
+                .lines
+                .iter()
+                .filter(|line| line.score <= best_score - BLUNDER_MARGIN)
+                .collect();
+            if blunders.is_empty() {
+                result.lines.last().into_iter().collect()
+            } else {
+                blunders
+            }
+        } else {
+            let margin = MARGIN_PER_LEVEL * self.levels_below_max() as i32;
+            result
+                .lines
+                .iter()
+                .take(CANDIDATE_MOVES)
+                .filter(|line| line.score >= best_score - margin)
+                .collect()
+        };
+        let chosen = candidates[random.below(candidates.len() as u64) as usize];
+        chosen.principal_variation.first().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::STARTING_POSITION_FEN;
+    use crate::search::{SearchLine, MATE_SCORE};
+    use std::time::Duration;
+
+    // a line for each score, the best first, with a different move in each
+    fn search_result(scores: &[i32]) -> SearchResult {
+        let board = BoardState::from_fen(STARTING_POSITION_FEN).unwrap();
+        let lines = board
+            .get_all_legal_moves()
+            .into_iter()
+            .zip(scores.iter())
+            .map(|(mv, score)| SearchLine {
+                score: *score,
+                principal_variation: vec![mv],
+            })
+            .collect::<Vec<_>>();
+        SearchResult {
+            best_move: Some(lines[0].principal_variation[0]),
+            score: scores[0],
+            depth: 5,
+            principal_variation: lines[0].principal_variation.clone(),
+            lines,
+            nodes: 1000,
+            elapsed: Duration::from_millis(10),
+            hash_hit_rate: 0.0,
+            hash_usage: 0,
+        }
+    }
+    fn settings(skill_level: u32, simulate_blunders: bool) -> EngineSettings {
+        EngineSettings {
+            skill_level,
+            simulate_blunders,
+            ..EngineSettings::default()
+        }
+    }
+    // the scores of the lines the moves were picked from, out of many tries
+    fn chosen_scores(settings: EngineSettings, result: &SearchResult) -> Vec<i32> {
+        let mut random = Random::new(1);
+        let mut scores = (0..1000)
+            .map(|_| {
+                let chosen = settings.choose_move(result, &mut random);
+                let line = result
+                    .lines
+                    .iter()
+                    .find(|line| line.principal_variation.first() == chosen.as_ref());
+                line.unwrap().score
+            })
+            .collect::<Vec<i32>>();
+        scores.sort_unstable();
+        scores.dedup();
+        scores
+    }
+
+    #[test]
+    fn full_strength_plays_the_best_move() {
+        let result = search_result(&[100, 99, 98, 97]);
+        assert_eq!(
+            chosen_scores(settings(MAX_SKILL_LEVEL, false), &result),
+            [100]
+        );
+        // with only one line there's nothing else to play
+        let result = search_result(&[100]);
+        assert_eq!(chosen_scores(settings(0, true), &result), [100]);
+    }
+
+    #[test]
+    fn mates_are_never_passed_up() {
+        let result = search_result(&[MATE_SCORE - 3, MATE_SCORE - 5, 50, -400]);
+        assert_eq!(chosen_scores(settings(0, true), &result), [MATE_SCORE - 3]);
+        // getting mated is no reason to play the best move
+        let result = search_result(&[-MATE_SCORE + 8, -MATE_SCORE + 6]);
+        let scores = chosen_scores(settings(0, false), &result);
+        assert_eq!(scores, [-MATE_SCORE + 6, -MATE_SCORE + 8]);
+    }
+
+    #[test]
+    fn candidates_stay_within_the_margin() {
+        // four levels down the margin is 100
+        let result = search_result(&[100, 80, 0, -10, -20]);
+        assert_eq!(chosen_scores(settings(6, false), &result), [0, 80, 100]);
+        assert_eq!(chosen_scores(settings(8, false), &result), [80, 100]);
+        // only the first few lines are candidates, however close the others are
+        let result = search_result(&[100, 99, 98, 97, 96, 95]);
+        assert_eq!(
+            chosen_scores(settings(0, false), &result),
+            [97, 98, 99, 100]
+        );
+    }
+
+    #[test]
+    fn blunders_are_clearly_worse() {
+        // 40 is too bad for a candidate two levels down, and not bad enough for a blunder
+        let result = search_result(&[100, 70, 40, -60, -100, -300]);
+        let scores = chosen_scores(settings(8, true), &result);
+        assert_eq!(scores, [-300, -100, -60, 70, 100]);
+        let mut blunders = scores.iter().filter(|score| **score < 70);
+        assert!(blunders.all(|score| *score <= 100 - BLUNDER_MARGIN));
+    }
+
+    #[test]
+    fn search_limits_per_level() {
+        let limits = SearchLimits::move_time(Duration::from_secs(2));
+        let full_strength = settings(MAX_SKILL_LEVEL, true).limit_search(&limits);
+        assert_eq!((full_strength.depth, full_strength.nodes), (None, None));
+        let lowest = settings(0, false).limit_search(&limits);
+        assert_eq!((lowest.depth, lowest.nodes), (Some(1), Some(500)));
+        let limited = settings(3, false).limit_search(&limits);
+        assert_eq!((limited.depth, limited.nodes), (Some(4), Some(4000)));
+        assert_eq!(limited.move_time, Some(Duration::from_secs(2)));
+        // tighter limits than the level's are kept
+        let tight = SearchLimits {
+            depth: Some(2),
+            nodes: Some(100),
+            ..limits
+        };
+        let limited = settings(3, false).limit_search(&tight);
+        assert_eq!((limited.depth, limited.nodes), (Some(2), Some(100)));
+    }
+}
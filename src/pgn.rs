@@ -0,0 +1,455 @@
+use crate::fen::{FenError, STARTING_POSITION_FEN};
+use crate::game_types::*;
+use crate::san::SanError;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const SEVEN_TAG_ROSTER: [&str; 7] =
+    ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+const RESULT_TOKENS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+const MAX_LINE_LENGTH: usize = 80;
+
+#[derive(Debug)]
+pub enum PgnError {
+    InvalidTag(String),
+    InvalidFen(FenError),
+    IllegalMove { ply: usize, error: SanError },
+    UnterminatedComment,
+    UnbalancedVariation,
+}
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::InvalidTag(line) => write!(f, "'{}' is not a valid tag pair", line),
+            PgnError::InvalidFen(e) => write!(f, "the FEN tag is invalid: {}", e),
+            PgnError::IllegalMove { ply, error } => {
+                write!(f, "move {} of the game can't be played: {}", ply, error)
+            }
+            PgnError::UnterminatedComment => write!(f, "a comment is missing its closing brace"),
+            PgnError::UnbalancedVariation => {
+                write!(f, "the parentheses around variations don't match")
+            }
+        }
+    }
+}
+impl std::error::Error for PgnError {}
+
+#[derive(Clone, Default)]
+pub struct MoveAnnotation {
+    pub nags: Vec<u8>,
+    pub comment: Option<String>,
+}
+#[derive(Clone)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub history: GameHistory,
+    // one per move in the history, variations are skipped
+    pub annotations: Vec<MoveAnnotation>,
+    pub initial_comment: Option<String>,
+}
+impl MoveAnnotation {
+    fn add_comment(&mut self, comment: &str) {
+        self.comment = Some(match self.comment.take() {
+            Some(previous) => format!("{} {}", previous, comment),
+            None => comment.to_owned(),
+        });
+    }
+}
+impl PgnGame {
+    /** Wraps a game with the seven tag roster filled with unknown values, apart from the date and result. */
+    pub fn new(history: GameHistory) -> PgnGame {
+        let result = PgnGame::get_result_of_final_position(&history).to_owned();
+        let annotations = vec![MoveAnnotation::default(); history.moves.len()];
+        let mut game = PgnGame {
+            tags: Vec::new(),
+            history,
+            annotations,
+            initial_comment: None,
+        };
+        for name in SEVEN_TAG_ROSTER.iter() {
+            game.set_tag(name, "?");
+        }
+        game.set_tag("Date", &get_todays_pgn_date());
+        game.set_tag("Result", &result);
+        game
+    }
+    fn get_result_of_final_position(history: &GameHistory) -> &'static str {
+        let status = history.get_status();
+        if !status.is_over() {
+            return "*";
+        }
+        match status.get_winner() {
+            Some(PlayerColor::WHITE) => "1-0",
+            Some(PlayerColor::BLACK) => "0-1",
+            None => "1/2-1/2",
+        }
+    }
+    pub fn get_tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag_name, _)| tag_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag_name, _)| tag_name == name) {
+            Some(tag) => tag.1 = value.to_owned(),
+            None => self.tags.push((name.to_owned(), value.to_owned())),
+        }
+    }
+    pub fn get_result(&self) -> &str {
+        self.get_tag("Result").unwrap_or("*")
+    }
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+        let write_tag = |pgn: &mut String, name: &str, value: &str| {
+            let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, escaped));
+        };
+        for name in SEVEN_TAG_ROSTER.iter() {
+            write_tag(&mut pgn, name, self.get_tag(name).unwrap_or("?"));
+        }
+        let starting_fen = self.history.board_states[0].to_fen();
+        if starting_fen != STARTING_POSITION_FEN {
+            write_tag(&mut pgn, "SetUp", "1");
+            write_tag(&mut pgn, "FEN", &starting_fen);
+        }
+        for (name, value) in self.tags.iter() {
+            let is_written = SEVEN_TAG_ROSTER.contains(&name.as_str())
+                || (starting_fen != STARTING_POSITION_FEN && (name == "SetUp" || name == "FEN"));
+            if !is_written {
+                write_tag(&mut pgn, name, value);
+            }
+        }
+        pgn.push('\n');
+
+        let mut tokens = Vec::<String>::new();
+        if let Some(comment) = &self.initial_comment {
+            tokens.push(format!("{{{}}}", comment));
+        }
+        let san_moves = self.history.get_san_moves();
+        // black's moves need their number too when something stands between them and white's move
+        let mut needs_move_number = true;
+        for (i, san) in san_moves.iter().enumerate() {
+            let board = &self.history.board_states[i];
+            // the move number stays on the same line as its move
+            match board.player_to_move {
+                PlayerColor::WHITE => tokens.push(format!("{}. {}", board.fullmove_number, san)),
+                PlayerColor::BLACK if needs_move_number => {
+                    tokens.push(format!("{}... {}", board.fullmove_number, san))
+                }
+                PlayerColor::BLACK => tokens.push(san.clone()),
+            }
+            needs_move_number = false;
+            if let Some(annotation) = self.annotations.get(i) {
+                for nag in annotation.nags.iter() {
+                    tokens.push(format!("${}", nag));
+                    needs_move_number = true;
+                }
+                if let Some(comment) = &annotation.comment {
+                    tokens.push(format!("{{{}}}", comment));
+                    needs_move_number = true;
+                }
+            }
+        }
+        tokens.push(self.get_result().to_owned());
+
+        let mut line_length = 0;
+        for token in tokens {
+            if line_length > 0 && line_length + 1 + token.len() > MAX_LINE_LENGTH {
+                pgn.push('\n');
+                line_length = 0;
+            }
+            if line_length > 0 {
+                pgn.push(' ');
+                line_length += 1;
+            }
+            line_length += token.len();
+            pgn.push_str(&token);
+        }
+        pgn.push_str("\n\n");
+        pgn
+    }
+}
+/** Reads the games of a PGN file one by one. A game that fails to parse is skipped over, so the following ones can still be read. */
+pub struct PgnReader<'a> {
+    text: &'a str,
+    position: usize,
+}
+impl<'a> PgnReader<'a> {
+    pub fn new(text: &'a str) -> PgnReader<'a> {
+        PgnReader { text, position: 0 }
+    }
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+    fn is_at_line_start(&self) -> bool {
+        self.position == 0 || self.text.as_bytes()[self.position - 1] == b'\n'
+    }
+    fn skip_rest_of_line(&mut self) {
+        match self.text[self.position..].find('\n') {
+            Some(offset) => self.position += offset + 1,
+            None => self.position = self.text.len(),
+        }
+    }
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '%' && self.is_at_line_start() {
+                // escaped lines are meant for other software
+                self.skip_rest_of_line();
+            } else if c.is_whitespace() {
+                self.position += c.len_utf8();
+            } else {
+                break;
+            }
+        }
+    }
+    fn read_tag(&mut self) -> Result<(String, String), PgnError> {
+        let start = self.position;
+        let mut is_quoted = false;
+        let mut is_escaped = false;
+        let mut end: Option<usize> = None;
+        for (offset, c) in self.text[start..].char_indices() {
+            match c {
+                '\n' => break,
+                _ if is_escaped => is_escaped = false,
+                '\\' if is_quoted => is_escaped = true,
+                '"' => is_quoted = !is_quoted,
+                ']' if !is_quoted => {
+                    end = Some(start + offset + 1);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let end = match end {
+            Some(end) => end,
+            None => {
+                self.skip_rest_of_line();
+                let line = self.text[start..self.position].trim();
+                return Err(PgnError::InvalidTag(line.to_owned()));
+            }
+        };
+        self.position = end;
+        let tag = &self.text[start..end];
+        let invalid = || PgnError::InvalidTag(tag.to_owned());
+        let inner = tag[1..tag.len() - 1].trim();
+        let name_end = inner.find(char::is_whitespace).ok_or_else(invalid)?;
+        let name = &inner[..name_end];
+        let quoted = inner[name_end..].trim();
+        if quoted.len() < 2 || !quoted.starts_with('"') || !quoted.ends_with('"') {
+            return Err(invalid());
+        }
+        let mut value = String::with_capacity(quoted.len());
+        let mut chars = quoted[1..quoted.len() - 1].chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                value.extend(chars.next());
+            } else {
+                value.push(c);
+            }
+        }
+        Ok((name.to_owned(), value))
+    }
+    fn read_comment(&mut self) -> Result<String, PgnError> {
+        // skip the opening brace
+        self.position += 1;
+        match self.text[self.position..].find('}') {
+            Some(offset) => {
+                let comment = self.text[self.position..self.position + offset].trim();
+                self.position += offset + 1;
+                Ok(comment.split_whitespace().collect::<Vec<&str>>().join(" "))
+            }
+            None => {
+                self.position = self.text.len();
+                Err(PgnError::UnterminatedComment)
+            }
+        }
+    }
+    fn read_symbol(&mut self) -> &'a str {
+        let start = self.position;
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || "{}();[$".contains(c) {
+                break;
+            }
+            self.position += c.len_utf8();
+        }
+        &self.text[start..self.position]
+    }
+    // the next game starts with the first tag after this one's movetext, which a bad tag leaves ahead
+    fn skip_to_next_game(&mut self, is_in_tags: bool) {
+        let mut is_in_tags = is_in_tags;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None => return,
+                Some('[') if is_in_tags => self.skip_rest_of_line(),
+                Some('[') if self.is_at_line_start() => return,
+                Some('{') => {
+                    let _ = self.read_comment();
+                }
+                Some(c) => {
+                    self.position += c.len_utf8();
+                    self.read_symbol();
+                    is_in_tags = false;
+                }
+            }
+        }
+    }
+    fn read_game(&mut self) -> Result<PgnGame, PgnError> {
+        let mut tags = Vec::<(String, String)>::new();
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('[') {
+                break;
+            }
+            tags.push(self.read_tag()?);
+        }
+        let fen = tags
+            .iter()
+            .find(|(name, _)| name == "FEN")
+            .map_or(STARTING_POSITION_FEN, |(_, value)| value.as_str());
+        let starting_position = BoardState::from_fen(fen).map_err(PgnError::InvalidFen)?;
+        let mut game = PgnGame {
+            tags,
+            history: GameHistory::new(vec![starting_position], None),
+            annotations: Vec::new(),
+            initial_comment: None,
+        };
+        let mut variation_depth = 0;
+        loop {
+            self.skip_whitespace();
+            let c = match self.peek() {
+                Some(c) => c,
+                None => break,
+            };
+            match c {
+                '[' if self.is_at_line_start() => break,
+                '{' => {
+                    let comment = self.read_comment()?;
+                    if variation_depth == 0 {
+                        match game.annotations.last_mut() {
+                            Some(annotation) => annotation.add_comment(&comment),
+                            None => {
+                                game.initial_comment = Some(match game.initial_comment.take() {
+                                    Some(previous) => format!("{} {}", previous, comment),
+                                    None => comment,
+                                })
+                            }
+                        }
+                    }
+                }
+                ';' => self.skip_rest_of_line(),
+                '(' => {
+                    self.position += c.len_utf8();
+                    variation_depth += 1;
+                }
+                ')' => {
+                    self.position += c.len_utf8();
+                    if variation_depth == 0 {
+                        return Err(PgnError::UnbalancedVariation);
+                    }
+                    variation_depth -= 1;
+                }
+                '$' => {
+                    self.position += c.len_utf8();
+                    let nag = self.read_symbol().parse::<u8>();
+                    if let (Ok(nag), Some(annotation), 0) =
+                        (nag, game.annotations.last_mut(), variation_depth)
+                    {
+                        annotation.nags.push(nag);
+                    }
+                }
+                _ => {
+                    let symbol = self.read_symbol();
+                    if symbol.is_empty() {
+                        // a stray character no other branch takes
+                        self.position += c.len_utf8();
+                        continue;
+                    }
+                    if variation_depth > 0 {
+                        continue;
+                    }
+                    if RESULT_TOKENS.contains(&symbol) {
+                        if game.get_tag("Result").is_none() {
+                            game.set_tag("Result", symbol);
+                        }
+                        break;
+                    }
+                    PgnReader::play_symbol(&mut game, symbol)?;
+                }
+            }
+        }
+        if variation_depth != 0 {
+            return Err(PgnError::UnbalancedVariation);
+        }
+        Ok(game)
+    }
+    fn play_symbol(game: &mut PgnGame, symbol: &str) -> Result<(), PgnError> {
+        // move numbers may be glued to the move, like "12.e4", but "0-0" is castling
+        let after_number = symbol.trim_start_matches(|c: char| c.is_ascii_digit());
+        let san = if after_number.starts_with('.') {
+            after_number.trim_start_matches('.')
+        } else {
+            symbol
+        };
+        if san.is_empty() {
+            return Ok(());
+        }
+        let suffix_start = san.find(&['!', '?'][..]).unwrap_or(san.len());
+        let nag = match &san[suffix_start..] {
+            "!" => Some(1),
+            "?" => Some(2),
+            "!!" => Some(3),
+            "??" => Some(4),
+            "!?" => Some(5),
+            "?!" => Some(6),
+            _ => None,
+        };
+        let ply = game.history.moves.len() + 1;
+        let mv = game
+            .history
+            .get_board()
+            .move_from_san(&san[..suffix_start])
+            .map_err(|error| PgnError::IllegalMove { ply, error })?;
+        game.history.execute_move(&mv);
+        let mut annotation = MoveAnnotation::default();
+        annotation.nags.extend(nag);
+        game.annotations.push(annotation);
+        Ok(())
+    }
+}
+impl<'a> Iterator for PgnReader<'a> {
+    type Item = Result<PgnGame, PgnError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.skip_whitespace();
+        self.peek()?;
+        let game = self.read_game();
+        if let Err(e) = &game {
+            self.skip_to_next_game(matches!(e, PgnError::InvalidTag(_)));
+        }
+        Some(game)
+    }
+}
+// PGN dates look like "2021.02.14"
+pub fn get_todays_pgn_date() -> String {
+    let seconds = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(_) => return "????.??.??".to_owned(),
+    };
+    // days since 1970-01-01 to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let days = seconds / 86400 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
@@ -0,0 +1,119 @@
+use crate::game_types::*;
+use crate::search::{CancellationToken, SearchLimits, Searcher};
+use std::time::Duration;
+
+// every position of the game is searched this deep, or for this long if that's reached first
+pub const REVIEW_DEPTH: u32 = 10;
+pub const REVIEW_MOVE_TIME: Duration = Duration::from_millis(500);
+// how many centipawns worse than the best move a move may be before it's an inaccuracy, a mistake
+// or a blunder
+const INACCURACY_THRESHOLD: i32 = 50;
+const MISTAKE_THRESHOLD: i32 = 100;
+const BLUNDER_THRESHOLD: i32 = 250;
+// past this the game is as good as decided, mates count as no more than it
+const DECISIVE_SCORE: i32 = 1000;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Judgement {
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+/** What the engine thinks of one of the game's moves. */
+#[derive(Clone)]
+pub struct MoveReview {
+    // how many centipawns the side that moved gave away compared to the best move
+    pub score_loss: i32,
+    pub judgement: Option<Judgement>,
+    // the move the engine would have played, unless it's the one that was played
+    pub best_move: Option<ChessMove>,
+}
+/** The engine's view of a whole game. */
+#[derive(Clone)]
+pub struct GameReview {
+    // of every position, the last one included, in centipawns from White's side
+    pub scores: Vec<i32>,
+    // of every move
+    pub moves: Vec<MoveReview>,
+}
+
+impl Judgement {
+    pub fn from_score_loss(score_loss: i32) -> Option<Judgement> {
+        if score_loss >= BLUNDER_THRESHOLD {
+            Some(Judgement::Blunder)
+        } else if score_loss >= MISTAKE_THRESHOLD {
+            Some(Judgement::Mistake)
+        } else if score_loss >= INACCURACY_THRESHOLD {
+            Some(Judgement::Inaccuracy)
+        } else {
+            None
+        }
+    }
+    /** How it's written after the move, e.g. "Nf3?!". */
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Judgement::Inaccuracy => "?!",
+            Judgement::Mistake => "?",
+            Judgement::Blunder => "??",
+        }
+    }
+}
+
+/** Searches every position of the game, and judges each move by how much worse it is than the one
+the engine would have played. None if it's cancelled before it's done. */
+pub fn review_game(
+    searcher: &mut Searcher,
+    history: &GameHistory,
+    limits: &SearchLimits,
+    cancellation: &CancellationToken,
+) -> Option<GameReview> {
+    let mut results = Vec::with_capacity(history.board_states.len());
+    for (i, board) in history.board_states.iter().enumerate() {
+        searcher.previous_positions = history.board_states[..i]
+            .iter()
+            .map(|board| board.hash())
+            .collect();
+        let result = searcher.search(board, limits, cancellation);
+        if cancellation.is_cancelled() {
+            searcher.previous_positions.clear();
+            return None;
+        }
+        results.push(result);
+    }
+    searcher.previous_positions.clear();
+    // the search doesn't know a game drawn by repetition or the fifty-move rule is over
+    let status = history.get_status();
+    let is_drawn = status.is_over() && status.get_winner().is_none();
+    // from the side to move
+    let mut scores = results
+        .iter()
+        .map(|result| result.score.clamp(-DECISIVE_SCORE, DECISIVE_SCORE))
+        .collect::<Vec<i32>>();
+    if is_drawn {
+        *scores.last_mut().unwrap() = 0;
+    }
+    let moves = history
+        .moves
+        .iter()
+        .enumerate()
+        .map(|(i, mv)| {
+            let best_move = results[i].best_move.filter(|best_move| best_move != mv);
+            // the score after the move is the opponent's
+            let score_loss = match best_move {
+                Some(_) => (scores[i] + scores[i + 1]).max(0),
+                None => 0,
+            };
+            MoveReview {
+                score_loss,
+                judgement: Judgement::from_score_loss(score_loss),
+                best_move,
+            }
+        })
+        .collect();
+    let scores = scores
+        .iter()
+        .zip(history.board_states.iter())
+        .map(|(score, board)| board.score_for_current_player(*score))
+        .collect();
+    Some(GameReview { scores, moves })
+}
@@ -0,0 +1,265 @@
+use crate::fen::square_name;
+use crate::game_types::*;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum SanError {
+    Empty,
+    InvalidSyntax(String),
+    IllegalMove(String),
+    AmbiguousMove(String),
+}
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SanError::Empty => write!(f, "the move is empty"),
+            SanError::InvalidSyntax(san) => write!(f, "'{}' is not a move in SAN", san),
+            SanError::IllegalMove(san) => write!(f, "'{}' is not a legal move here", san),
+            SanError::AmbiguousMove(san) => {
+                write!(f, "'{}' could mean more than one legal move", san)
+            }
+        }
+    }
+}
+impl std::error::Error for SanError {}
+
+fn piece_letter(piece_type: PieceType) -> Option<char> {
+    match piece_type {
+        PieceType::PAWN => None,
+        PieceType::KNIGHT => Some('N'),
+        PieceType::BISHOP => Some('B'),
+        PieceType::ROOK => Some('R'),
+        PieceType::QUEEN => Some('Q'),
+        PieceType::KING => Some('K'),
+    }
+}
+fn piece_type_from_letter(letter: char) -> Option<PieceType> {
+    match letter {
+        'N' => Some(PieceType::KNIGHT),
+        'B' => Some(PieceType::BISHOP),
+        'R' => Some(PieceType::ROOK),
+        'Q' => Some(PieceType::QUEEN),
+        'K' => Some(PieceType::KING),
+        _ => None,
+    }
+}
+fn file_from_char(c: char) -> Option<i8> {
+    match c {
+        'a'..='h' => Some(c as i8 - 'a' as i8),
+        _ => None,
+    }
+}
+fn rank_from_char(c: char) -> Option<i8> {
+    match c {
+        '1'..='8' => Some(c as i8 - '1' as i8),
+        _ => None,
+    }
+}
+impl BoardState {
+    /** Renders a legal move in Standard Algebraic Notation, e.g. "Nf3", "exd5", "O-O-O" or "e8=Q+". */
+    pub fn move_to_san(&self, mv: &ChessMove) -> String {
+        let mut san = String::with_capacity(8);
+        if mv.is_king_side_castles() {
+            san.push_str("O-O");
+        } else if mv.is_queen_side_castles() {
+            san.push_str("O-O-O");
+        } else {
+            // en passant lands on an empty square, but it's still a diagonal pawn move
+            let was_takes = self.get_piece_at_square(mv.to.pos()).is_some()
+                || (mv.from.piece_type == PieceType::PAWN && mv.from.x != mv.to.x);
+            match piece_letter(mv.from.piece_type) {
+                Some(letter) => {
+                    san.push(letter);
+                    san.push_str(&self.get_disambiguation(mv));
+                }
+                None => {
+                    if was_takes {
+                        san.push((b'a' + mv.from.x as u8) as char);
+                    }
+                }
+            }
+            if was_takes {
+                san.push('x');
+            }
+            san.push_str(&square_name(mv.to.x, mv.to.y));
+            if let Some(promoted_to) = mv.promotion() {
+                san.push('=');
+                san.push(piece_letter(promoted_to).unwrap());
+            }
+        }
+        let board_after_move = self.after_move(mv);
+        if board_after_move.evaluate_is_check(None) {
+            if board_after_move.get_all_legal_moves().is_empty() {
+                san.push('#');
+            } else {
+                san.push('+');
+            }
+        }
+        san
+    }
+    // the file if it tells the pieces apart, otherwise the rank, otherwise both
+    fn get_disambiguation(&self, mv: &ChessMove) -> String {
+        let rivals = self
+            .get_all_legal_moves()
+            .into_iter()
+            .filter(|other| {
+                other.from.piece_type == mv.from.piece_type
+                    && other.to.pos() == mv.to.pos()
+                    && other.from.pos() != mv.from.pos()
+            })
+            .collect::<Vec<ChessMove>>();
+        if rivals.is_empty() {
+            return String::new();
+        }
+        let square = square_name(mv.from.x, mv.from.y);
+        if rivals.iter().all(|other| other.from.x != mv.from.x) {
+            return square[..1].to_owned();
+        }
+        if rivals.iter().all(|other| other.from.y != mv.from.y) {
+            return square[1..].to_owned();
+        }
+        square
+    }
+    /** Finds the unique legal move described by a move in Standard Algebraic Notation. */
+    pub fn move_from_san(&self, san: &str) -> Result<ChessMove, SanError> {
+        let san = san.trim();
+        let body = san.trim_end_matches(&['+', '#', '!', '?'][..]);
+        let body = body.trim_end_matches("e.p.").trim_end();
+        if body.is_empty() {
+            return Err(SanError::Empty);
+        }
+        let invalid = || SanError::InvalidSyntax(san.to_owned());
+        let legal_moves = self.get_all_legal_moves();
+        let pick = |candidates: Vec<ChessMove>| match candidates.len() {
+            0 => Err(SanError::IllegalMove(san.to_owned())),
+            1 => Ok(candidates[0]),
+            _ => Err(SanError::AmbiguousMove(san.to_owned())),
+        };
+        match body {
+            "O-O" | "0-0" => {
+                return pick(
+                    legal_moves
+                        .into_iter()
+                        .filter(|mv| mv.is_king_side_castles())
+                        .collect(),
+                );
+            }
+            "O-O-O" | "0-0-0" => {
+                return pick(
+                    legal_moves
+                        .into_iter()
+                        .filter(|mv| mv.is_queen_side_castles())
+                        .collect(),
+                );
+            }
+            _ => {}
+        }
+
+        let mut chars = body.chars().collect::<Vec<char>>();
+        // both "e8=Q" and "e8Q" are common
+        let mut promoted_to: Option<PieceType> = None;
+        if chars.len() > 2 {
+            if let Some(piece_type) = piece_type_from_letter(chars[chars.len() - 1]) {
+                if piece_type == PieceType::KING {
+                    return Err(invalid());
+                }
+                promoted_to = Some(piece_type);
+                chars.pop();
+                if chars.last() == Some(&'=') {
+                    chars.pop();
+                }
+            }
+        }
+        let piece_type = match chars.first().and_then(|c| piece_type_from_letter(*c)) {
+            Some(piece_type) => {
+                chars.remove(0);
+                piece_type
+            }
+            None => PieceType::PAWN,
+        };
+        chars.retain(|c| *c != 'x' && *c != ':' && *c != '-');
+        if chars.len() < 2 || chars.len() > 4 {
+            return Err(invalid());
+        }
+        let to_x = file_from_char(chars[chars.len() - 2]).ok_or_else(invalid)?;
+        let to_y = rank_from_char(chars[chars.len() - 1]).ok_or_else(invalid)?;
+        let mut from_x: Option<i8> = None;
+        let mut from_y: Option<i8> = None;
+        for c in chars[..chars.len() - 2].iter() {
+            if let Some(x) = file_from_char(*c) {
+                from_x = Some(x);
+            } else if let Some(y) = rank_from_char(*c) {
+                from_y = Some(y);
+            } else {
+                return Err(invalid());
+            }
+        }
+        pick(
+            legal_moves
+                .into_iter()
+                .filter(|mv| {
+                    mv.from.piece_type == piece_type
+                        && mv.to.x == to_x
+                        && mv.to.y == to_y
+                        && mv.promotion() == promoted_to
+                        && from_x.is_none_or(|x| mv.from.x == x)
+                        && from_y.is_none_or(|y| mv.from.y == y)
+                })
+                .collect(),
+        )
+    }
+    /** Finds the legal move written in long algebraic notation, the way UCI sends moves. */
+    pub fn move_from_long_algebraic(&self, text: &str) -> Result<ChessMove, SanError> {
+        let text = text.trim().to_ascii_lowercase();
+        if text.is_empty() {
+            return Err(SanError::Empty);
+        }
+        self.get_all_legal_moves()
+            .into_iter()
+            .find(|mv| mv.to_long_algebraic() == text)
+            .ok_or(SanError::IllegalMove(text))
+    }
+}
+impl BoardState {
+    /** The moves played one after the other from this position, numbered like in PGN, e.g.
+    "12... Nc6 13. Bb5 a6". It stops at the first move that isn't legal where it's played. */
+    pub fn line_to_san(&self, moves: &[ChessMove]) -> String {
+        let mut board = self.clone();
+        let mut tokens = Vec::<String>::with_capacity(moves.len());
+        for mv in moves.iter() {
+            if !board.get_all_legal_moves().contains(mv) {
+                break;
+            }
+            let san = board.move_to_san(mv);
+            match board.player_to_move {
+                PlayerColor::WHITE => tokens.push(format!("{}. {}", board.fullmove_number, san)),
+                PlayerColor::BLACK if tokens.is_empty() => {
+                    tokens.push(format!("{}... {}", board.fullmove_number, san))
+                }
+                PlayerColor::BLACK => tokens.push(san),
+            }
+            board.make_move(mv);
+        }
+        tokens.join(" ")
+    }
+}
+impl GameHistory {
+    pub fn get_san_moves(&self) -> Vec<String> {
+        self.moves
+            .iter()
+            .zip(self.board_states.iter())
+            .map(|(mv, board)| board.move_to_san(mv))
+            .collect()
+    }
+}
+impl ChessMove {
+    /** The move in the long algebraic notation UCI uses, e.g. "e2e4", "e1g1" or "e7e8q". */
+    pub fn to_long_algebraic(&self) -> String {
+        let mut text = square_name(self.from.x, self.from.y);
+        text.push_str(&square_name(self.to.x, self.to.y));
+        if let Some(promoted_to) = self.promotion() {
+            text.push(piece_letter(promoted_to).unwrap().to_ascii_lowercase());
+        }
+        text
+    }
+}
@@ -1,11 +1,20 @@
 use crate::engine::*;
 use crate::game::*;
 use crate::game_types::*;
+use crate::personality::{get_max_thread_count, EngineSettings, MAX_SKILL_LEVEL};
+use crate::pgn::PgnGame;
+use crate::review::GameReview;
+use crate::search::{get_mate_in, Clock, SearchLimits, SearchResult};
 use crate::ui::{MenuButton, UIFlexBox, UIImage, UIText};
 use crate::Assets;
+use std::cell::Cell;
+use std::io::Write;
+use std::rc::Rc;
+use std::time::Duration;
 use tetra::graphics;
 use tetra::graphics::text::VectorFontBuilder;
 use tetra::graphics::Canvas;
+use tetra::graphics::DrawParams;
 use tetra::graphics::Texture;
 use tetra::math::Vec4;
 use tetra::{
@@ -28,15 +37,19 @@ pub struct MenuScene {
 }
 
 impl MenuScene {
-    pub fn new(ctx: &mut Context) -> tetra::Result<MenuScene> {
+    pub fn new(ctx: &mut Context, starting_game: Option<GameHistory>) -> tetra::Result<MenuScene> {
         let font = Assets::load_assets(ctx)?.font;
         let size = 32.0;
         let borders = Vec2::new(18, 18);
         let btn_layout_y_padding = Vec2::new(0, 70);
         let local_mp_btn_pos = Vec2::new(300, 200);
         let local_mp_btn_text = Text::new("Local game", font.with_size(ctx, size)?);
-        let local_mp_btn_on_click =
-            Box::new(|s: &mut _| Transition::Push(Box::new(GameScene::new(s, None).unwrap())));
+        let mp_starting_game = starting_game.clone();
+        let local_mp_btn_on_click = Box::new(move |s: &mut _| {
+            Transition::Push(Box::new(
+                GameScene::new(s, None, mp_starting_game.clone()).unwrap(),
+            ))
+        });
         let local_mp_btn = MenuButton::new(
             borders,
             local_mp_btn_pos,
@@ -46,8 +59,11 @@ impl MenuScene {
 
         let local_sp_btn_pos = btn_layout_y_padding + local_mp_btn_pos;
         let local_sp_btn_text = Text::new("Play an engine", font.with_size(ctx, size)?);
-        let local_sp_btn_on_click = Box::new(|s: &mut _| {
-            Transition::Push(Box::new(GameScene::new(s, Some(Engine::new())).unwrap()))
+        let sp_starting_game = starting_game.clone();
+        let local_sp_btn_on_click = Box::new(move |s: &mut _| {
+            Transition::Push(Box::new(
+                EngineSetupScene::new(s, sp_starting_game.clone()).unwrap(),
+            ))
         });
         let local_sp_btn = MenuButton::new(
             borders,
@@ -56,7 +72,22 @@ impl MenuScene {
             local_sp_btn_on_click,
         );
 
-        let quit_btn_pos = btn_layout_y_padding + local_sp_btn_pos;
+        let analysis_btn_pos = btn_layout_y_padding + local_sp_btn_pos;
+        let analysis_btn_text = Text::new("Analysis", font.with_size(ctx, size)?);
+        let analysis_starting_game = starting_game.clone();
+        let analysis_btn_on_click = Box::new(move |s: &mut _| {
+            Transition::Push(Box::new(
+                AnalysisScene::new(s, analysis_starting_game.clone()).unwrap(),
+            ))
+        });
+        let analysis_btn = MenuButton::new(
+            borders,
+            analysis_btn_pos,
+            analysis_btn_text,
+            analysis_btn_on_click,
+        );
+
+        let quit_btn_pos = btn_layout_y_padding + analysis_btn_pos;
         let quit_btn_text = Text::new("Quit", font.with_size(ctx, size)?);
         let quit_btn_on_click = Box::new(|_: &mut _| Transition::Pop);
         let quit_btn = MenuButton::new(borders, quit_btn_pos, quit_btn_text, quit_btn_on_click);
@@ -64,7 +95,7 @@ impl MenuScene {
         let unit = 1.0 / 255.;
         Ok(MenuScene {
             bcg_color: Color::rgb(unit * 196., unit * 196., unit * 196.),
-            buttons: vec![local_mp_btn, local_sp_btn, quit_btn],
+            buttons: vec![local_mp_btn, local_sp_btn, analysis_btn, quit_btn],
         })
     }
 }
@@ -92,6 +123,160 @@ impl Scene for MenuScene {
         Ok(Transition::None)
     }
 }
+// picks the engine's strength and style before the game starts
+struct EngineSetupScene {
+    bcg_color: Color,
+    buttons: Vec<MenuButton>,
+    // shared with the buttons' click handlers
+    settings: Rc<Cell<EngineSettings>>,
+}
+impl EngineSetupScene {
+    fn new(
+        ctx: &mut Context,
+        starting_game: Option<GameHistory>,
+    ) -> tetra::Result<EngineSetupScene> {
+        let font = Assets::load_assets(ctx)?.font;
+        let size = 32.0;
+        let borders = Vec2::new(18, 18);
+        let btn_layout_y_padding = Vec2::new(0, 70);
+        let settings = Rc::new(Cell::new(EngineSettings::default()));
+
+        let level_btn_pos = Vec2::new(300, 130);
+        let level_btn_text = Text::new("", font.with_size(ctx, size)?);
+        let level_settings = settings.clone();
+        let level_btn_on_click = Box::new(move |_: &mut _| {
+            let mut settings = level_settings.get();
+            settings.skill_level = (settings.skill_level + 1) % (MAX_SKILL_LEVEL + 1);
+            level_settings.set(settings);
+            Transition::None
+        });
+        let level_btn = MenuButton::new(borders, level_btn_pos, level_btn_text, level_btn_on_click);
+
+        let style_btn_pos = btn_layout_y_padding + level_btn_pos;
+        let style_btn_text = Text::new("", font.with_size(ctx, size)?);
+        let style_settings = settings.clone();
+        let style_btn_on_click = Box::new(move |_: &mut _| {
+            let mut settings = style_settings.get();
+            settings.style = settings.style.next();
+            style_settings.set(settings);
+            Transition::None
+        });
+        let style_btn = MenuButton::new(borders, style_btn_pos, style_btn_text, style_btn_on_click);
+
+        let blunders_btn_pos = btn_layout_y_padding + style_btn_pos;
+        let blunders_btn_text = Text::new("", font.with_size(ctx, size)?);
+        let blunders_settings = settings.clone();
+        let blunders_btn_on_click = Box::new(move |_: &mut _| {
+            let mut settings = blunders_settings.get();
+            settings.simulate_blunders = !settings.simulate_blunders;
+            blunders_settings.set(settings);
+            Transition::None
+        });
+        let blunders_btn = MenuButton::new(
+            borders,
+            blunders_btn_pos,
+            blunders_btn_text,
+            blunders_btn_on_click,
+        );
+
+        let threads_btn_pos = btn_layout_y_padding + blunders_btn_pos;
+        let threads_btn_text = Text::new("", font.with_size(ctx, size)?);
+        let threads_settings = settings.clone();
+        let threads_btn_on_click = Box::new(move |_: &mut _| {
+            let mut settings = threads_settings.get();
+            settings.threads = settings.threads % get_max_thread_count() + 1;
+            threads_settings.set(settings);
+            Transition::None
+        });
+        let threads_btn = MenuButton::new(
+            borders,
+            threads_btn_pos,
+            threads_btn_text,
+            threads_btn_on_click,
+        );
+
+        let start_btn_pos = btn_layout_y_padding + threads_btn_pos;
+        let start_btn_text = Text::new("Start game", font.with_size(ctx, size)?);
+        let start_settings = settings.clone();
+        let start_btn_on_click = Box::new(move |s: &mut _| {
+            let engine = Engine::with_settings(start_settings.get());
+            Transition::Push(Box::new(
+                GameScene::new(s, Some(engine), starting_game.clone()).unwrap(),
+            ))
+        });
+        let start_btn = MenuButton::new(borders, start_btn_pos, start_btn_text, start_btn_on_click);
+
+        let back_btn_pos = btn_layout_y_padding + start_btn_pos;
+        let back_btn_text = Text::new("Back", font.with_size(ctx, size)?);
+        let back_btn_on_click = Box::new(|_: &mut _| Transition::Pop);
+        let back_btn = MenuButton::new(borders, back_btn_pos, back_btn_text, back_btn_on_click);
+
+        let unit = 1.0 / 255.;
+        let mut scene = EngineSetupScene {
+            bcg_color: Color::rgb(unit * 196., unit * 196., unit * 196.),
+            buttons: vec![
+                level_btn,
+                style_btn,
+                blunders_btn,
+                threads_btn,
+                start_btn,
+                back_btn,
+            ],
+            settings,
+        };
+        scene.update_labels();
+        Ok(scene)
+    }
+    // the first four buttons show the setting they change
+    fn update_labels(&mut self) {
+        let settings = self.settings.get();
+        let labels = [
+            format!(
+                "Skill level: {} / {}",
+                settings.skill_level, MAX_SKILL_LEVEL
+            ),
+            format!("Style: {}", settings.style.name()),
+            format!(
+                "Blunders: {}",
+                if settings.simulate_blunders {
+                    "on"
+                } else {
+                    "off"
+                }
+            ),
+            format!("Threads: {}", settings.threads),
+        ];
+        for (button, label) in self.buttons.iter_mut().zip(labels.iter()) {
+            button.set_text_content(label.as_str());
+        }
+    }
+}
+impl Scene for EngineSetupScene {
+    fn draw(&mut self, ctx: &mut Context) -> tetra::Result<Transition> {
+        graphics::clear(ctx, self.bcg_color);
+        for i in self.buttons.iter_mut() {
+            i.draw(ctx)?;
+        }
+        Ok(Transition::None)
+    }
+
+    fn update(&mut self, ctx: &mut Context) -> tetra::Result<Transition> {
+        for i in self.buttons.iter_mut() {
+            match i.update(ctx) {
+                Ok(Transition::Pop) => return Ok(Transition::Pop),
+                Ok(Transition::Push(s)) => return Ok(Transition::Push(s)),
+                _ => continue,
+            }
+        }
+        self.update_labels();
+        Ok(Transition::None)
+    }
+}
+const HISTORY_LINES_SHOWN: usize = 24;
+const SAVED_GAMES_PATH: &str = "./saved_games.pgn";
+// the evaluation bar stands left of the board, as tall as it is
+const EVALUATION_BAR_POS: Vec2<f32> = Vec2::new(70., 100.);
+const EVALUATION_BAR_SIZE: Vec2<i32> = Vec2::new(20, 400);
 struct GameScene {
     assets: Assets,
     game: GameContainer,
@@ -102,23 +287,40 @@ struct GameScene {
     selected_piece: Option<Piece>,
     should_rerender_pieces: bool,
     should_clear_notes: bool,
-    white_time_limit: f32,
-    black_time_limit: f32,
     white_time_remaining: f32,
     black_time_remaining: f32,
     player_whose_time_is_ticking: Option<PlayerColor>,
+    // without a clock the game goes on for as long as it takes
+    is_timed: bool,
     is_selectable: bool,
     engine: Option<Engine>,
+    promotion_choices: Vec<ChessMove>,
+    promotion_background: Texture,
+    // thinks about the position on the board in the background for the evaluation bar, and goes
+    // through the moves once the game is over
+    analysis_engine: Option<Engine>,
+    // the moves leading to the position the analysis was started on
+    analysed_moves: Option<Vec<ChessMove>>,
+    has_started_review: bool,
+    review: Option<GameReview>,
+    // of the position on the board, in centipawns from White's side
+    evaluation: Option<i32>,
+    evaluation_text: Text,
+    evaluation_bar_black: Texture,
+    evaluation_bar_white: Texture,
 }
 impl GameScene {
-    fn new(ctx: &mut Context, engine: Option<Engine>) -> tetra::Result<GameScene> {
+    fn new(
+        ctx: &mut Context,
+        engine: Option<Engine>,
+        starting_game: Option<GameHistory>,
+    ) -> tetra::Result<GameScene> {
         // TODO: setup engine
         let white_time_limit = 300.0;
         let black_time_limit = 300.0;
 
         let assets = Assets::load_assets(ctx)?;
         let board_size = Vec2::<f32>::new(400.0, 400.0);
-        let font = &assets.font;
 
         let shader =
             graphics::Shader::from_fragment_file(ctx, "./res/shaders/chessfrag.frag").unwrap();
@@ -137,7 +339,10 @@ impl GameScene {
             Vec4::<f32>::new(0.0, 0.0, 0.0, 0.0),
             2,
         )?;
-        let game = GameContainer::new();
+        let game = match starting_game {
+            Some(history) => GameContainer::from_history(history),
+            None => GameContainer::new(),
+        };
         let notes_box = UIFlexBox::new(
             ctx,
             board_size,
@@ -146,22 +351,35 @@ impl GameScene {
             2,
         )?;
 
-        let mut history_box = UIFlexBox::new(
+        let history_box = UIFlexBox::new(
             ctx,
             Vec2::new(400., 500.),
             Vec2::new(740., 100.),
             Vec4::<f32>::new(1.0, 0.0, 0.0, 1.0),
             3,
         )?;
-        let text1 = Text::new("bruh", font.with_size(ctx, 16.0)?);
-        history_box.children.push(Box::new(UIText::new(
+        let promotion_background =
+            Texture::from_rgba(ctx, 50, 50, &[196u8, 196, 196, 255].repeat(50 * 50))?;
+        let bar_area = (EVALUATION_BAR_SIZE.x * EVALUATION_BAR_SIZE.y) as usize;
+        let evaluation_bar_black = Texture::from_rgba(
             ctx,
-            Vec2::<f32>::new(0., 0.),
-            text1,
-            Box::new(|_: &mut _| Transition::None),
-            Box::new(|_: &mut _| Transition::None),
-        )?));
-        Ok(GameScene {
+            EVALUATION_BAR_SIZE.x,
+            EVALUATION_BAR_SIZE.y,
+            &[40u8, 40, 40, 255].repeat(bar_area),
+        )?;
+        let evaluation_bar_white = Texture::from_rgba(
+            ctx,
+            EVALUATION_BAR_SIZE.x,
+            EVALUATION_BAR_SIZE.y,
+            &[240u8, 240, 240, 255].repeat(bar_area),
+        )?;
+        let evaluation_text = Text::new("", assets.font.with_size(ctx, 16.0)?);
+        // a single thread, so that it doesn't take much from the engine the game is against
+        let analysis_engine = Engine::with_settings(EngineSettings {
+            threads: 1,
+            ..EngineSettings::default()
+        });
+        let mut scene = GameScene {
             assets,
             game,
             canvas: board_canvas,
@@ -171,14 +389,25 @@ impl GameScene {
             selected_piece: None,
             should_rerender_pieces: true,
             should_clear_notes: true,
-            white_time_limit: white_time_limit,
-            black_time_limit: black_time_limit,
             white_time_remaining: white_time_limit,
             black_time_remaining: black_time_limit,
             player_whose_time_is_ticking: None,
+            is_timed: true,
             is_selectable: true,
             engine,
-        })
+            promotion_choices: Vec::new(),
+            promotion_background,
+            analysis_engine: Some(analysis_engine),
+            analysed_moves: None,
+            has_started_review: false,
+            review: None,
+            evaluation: None,
+            evaluation_text,
+            evaluation_bar_black,
+            evaluation_bar_white,
+        };
+        scene.update_history_box(ctx)?;
+        Ok(scene)
     }
     pub fn draw_timers(&self, ctx: &mut Context) -> tetra::Result<Transition> {
         // FIXME: No, don't copy the font for each timer
@@ -224,29 +453,54 @@ impl GameScene {
             }
         }
     }
-    pub fn on_checkmate(&mut self) -> Option<PlayerColor> {
-        println!("It's checkmate!");
-        let color = self.game.get_board().player_to_move;
-        self.on_win(PlayerColor::opposite(color));
+    pub fn on_game_over(&mut self, status: GameStatus) -> Option<PlayerColor> {
+        match status {
+            GameStatus::Ongoing => {
+                return Some(self.game.get_board().player_to_move);
+            }
+            GameStatus::Checkmate { winner } => {
+                println!("It's checkmate!");
+                self.on_win(winner);
+            }
+            GameStatus::Timeout { winner } => {
+                println!("Time's up!");
+                self.on_win(winner);
+            }
+            GameStatus::Stalemate => println!("It's a draw by stalemate!"),
+            GameStatus::FiftyMoveRule => println!("It's a draw by the fifty-move rule!"),
+            GameStatus::ThreefoldRepetition => println!("It's a draw by threefold repetition!"),
+            GameStatus::InsufficientMaterial => {
+                println!("It's a draw by insufficient material!")
+            }
+        }
+        self.is_selectable = false;
+        self.selected_piece = None;
+        // a flag fall can end the game while the engine is still thinking
+        if let Some(engine) = self.engine.as_mut() {
+            engine.cancel();
+        }
         None
     }
+    // the engine plays Black and manages its time like a player would
+    fn get_engine_search_limits(&self) -> SearchLimits {
+        SearchLimits::clock(Clock {
+            time_left: Duration::from_secs_f32(self.black_time_remaining.max(0.0)),
+            increment: Duration::from_secs(0),
+            moves_to_go: None,
+        })
+    }
     pub fn on_piece_taken(&mut self) -> () {
         println!("I've taken a piece");
     }
     pub fn execute_move(&mut self, mv: ChessMove) -> Option<PlayerColor> {
-        let board_state = self.game.get_board();
         let move_info = self.game.history.execute_move(&mv);
         if move_info.was_takes {
             self.on_piece_taken();
         }
         if move_info.was_check {
-            // FIXME: doesn't run
             self.on_check();
         }
-        if move_info.was_checkmate {
-            return self.on_checkmate();
-        }
-        Some(PlayerColor::opposite(board_state.player_to_move))
+        self.on_game_over(self.game.history.get_status())
     }
     pub fn handle_move(
         &mut self,
@@ -282,6 +536,7 @@ impl GameScene {
                 Some(PlayerColor::opposite(self.game.get_board().player_to_move));
             self.player_whose_time_is_ticking = self.execute_move(k);
             self.should_rerender_pieces = true;
+            self.update_history_box(ctx)?;
         }
         if self.should_rerender_pieces {
             let mut new_pieces: Vec<Box<dyn Scene>> = Vec::new();
@@ -299,7 +554,162 @@ impl GameScene {
         }
         Ok(Transition::None)
     }
+    // the engine the game is against gets the machine to itself while it thinks, and the analysis
+    // makes way for the review once the game is over
+    fn update_analysis(&mut self, ctx: &mut Context) -> tetra::Result {
+        let board = self.game.get_board();
+        let is_engine_thinking = self.engine.is_some()
+            && self.player_whose_time_is_ticking.is_some()
+            && board.player_to_move == PlayerColor::BLACK;
+        let analysis_engine = match self.analysis_engine.as_mut() {
+            Some(analysis_engine) => analysis_engine,
+            None => return Ok(()),
+        };
+        if !self.is_selectable {
+            if !self.has_started_review {
+                analysis_engine.start_review(&self.game.history);
+                self.has_started_review = true;
+            }
+            if let Some(review) = analysis_engine.poll_review() {
+                if let Some(score) = review.scores.last() {
+                    self.set_evaluation(*score);
+                }
+                self.review = Some(review);
+                self.update_history_box(ctx)?;
+            }
+            return Ok(());
+        }
+        if is_engine_thinking {
+            analysis_engine.cancel();
+            self.analysed_moves = None;
+            return Ok(());
+        }
+        if self.analysed_moves.as_ref() != Some(&self.game.history.moves) {
+            self.analysed_moves = Some(self.game.history.moves.clone());
+            analysis_engine.start_analysis(&self.game.history, 1);
+        }
+        if let Some(result) = analysis_engine.poll_analysis() {
+            self.set_evaluation(board.score_for_current_player(result.score));
+        }
+        Ok(())
+    }
+    fn set_evaluation(&mut self, evaluation: i32) {
+        self.evaluation = Some(evaluation);
+        self.evaluation_text
+            .set_content(format_score(evaluation, PlayerColor::WHITE));
+    }
+    // White's share of the bar grows from the bottom, like its winning chances
+    fn draw_evaluation_bar(&mut self, ctx: &mut Context) {
+        let evaluation = match self.evaluation {
+            Some(evaluation) => evaluation,
+            None => return,
+        };
+        let white_share = 1.0 / (1.0 + 10f32.powf(-evaluation as f32 / 400.0));
+        let bar_height = EVALUATION_BAR_SIZE.y as f32;
+        self.evaluation_bar_black.draw(ctx, EVALUATION_BAR_POS);
+        self.evaluation_bar_white.draw(
+            ctx,
+            DrawParams::new()
+                .position(EVALUATION_BAR_POS + Vec2::new(0., bar_height * (1.0 - white_share)))
+                .scale(Vec2::new(1.0, white_share)),
+        );
+        self.evaluation_text
+            .draw(ctx, EVALUATION_BAR_POS + Vec2::new(-12., -24.));
+    }
+    // back to the position before the last move, which is up to be played again
+    fn take_back(&mut self, ctx: &mut Context) -> tetra::Result {
+        if self.game.history.take_back().is_none() {
+            return Ok(());
+        }
+        self.selected_piece = None;
+        self.promotion_choices.clear();
+        self.is_selectable = true;
+        self.should_rerender_pieces = true;
+        self.should_clear_notes = true;
+        self.update_history_box(ctx)
+    }
+    fn save_game(&self) -> std::io::Result<()> {
+        let mut game = PgnGame::new(self.game.history.clone());
+        game.set_tag("Event", "Casual game");
+        game.set_tag("Site", "LilChess");
+        game.set_tag("White", "Player");
+        match self.engine {
+            Some(_) => game.set_tag("Black", "LilChess engine"),
+            None => game.set_tag("Black", "Player"),
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(SAVED_GAMES_PATH)?;
+        file.write_all(game.to_pgn().as_bytes())
+    }
+    fn update_history_box(&mut self, ctx: &mut Context) -> tetra::Result {
+        let history = &self.game.history;
+        let mut lines = Vec::<String>::new();
+        for (i, (board, san)) in history
+            .board_states
+            .iter()
+            .zip(history.get_san_moves())
+            .enumerate()
+        {
+            // once reviewed, the moves that gave something away are marked, with what was better
+            let mut san = san;
+            let move_review = self.review.as_ref().and_then(|review| review.moves.get(i));
+            if let Some(move_review) = move_review {
+                if let (Some(judgement), Some(best_move)) =
+                    (move_review.judgement, move_review.best_move)
+                {
+                    san = format!(
+                        "{}{} ({})",
+                        san,
+                        judgement.symbol(),
+                        board.move_to_san(&best_move)
+                    );
+                }
+            }
+            match (board.player_to_move, lines.last_mut()) {
+                (PlayerColor::BLACK, Some(line)) => {
+                    line.push(' ');
+                    line.push_str(&san);
+                }
+                (PlayerColor::BLACK, None) => {
+                    lines.push(format!("{}... {}", board.fullmove_number, san))
+                }
+                (PlayerColor::WHITE, _) => {
+                    lines.push(format!("{}. {}", board.fullmove_number, san))
+                }
+            }
+        }
+        // only the latest moves fit into the box
+        let font = self.assets.font.with_size(ctx, 16.0)?;
+        let first_shown_line = lines.len().saturating_sub(HISTORY_LINES_SHOWN);
+        let mut children: Vec<Box<dyn Scene>> = Vec::new();
+        for (i, line) in lines[first_shown_line..].iter().enumerate() {
+            children.push(Box::new(UIText::new(
+                ctx,
+                Vec2::new(10., 10. + 20. * i as f32),
+                Text::new(line.as_str(), font.clone()),
+                Box::new(|_: &mut _| Transition::None),
+                Box::new(|_: &mut _| Transition::None),
+            )?));
+        }
+        self.history_box.children = children;
+        Ok(())
+    }
     fn get_image(piece: &Piece, a: &Assets, ctx: &mut Context) -> tetra::Result<UIImage> {
+        let back = UIImage::new(
+            ctx,
+            Vec2::new(
+                (piece.x as i32 * 50) as f32,
+                ((7 - piece.y) as i32 * 50) as f32,
+            ),
+            GameScene::get_texture(piece, a).clone(),
+            Box::new(|_: &mut _| Transition::None),
+            Box::new(|_: &mut _| Transition::None),
+        )?;
+        Ok(back)
+    }
+    fn get_texture<'a>(piece: &Piece, a: &'a Assets) -> &'a Texture {
         let i: &Texture;
         type P = PieceType;
         match piece.color {
@@ -320,17 +730,32 @@ impl GameScene {
                 P::PAWN => i = &a.w_p,
             },
         }
-        let back = UIImage::new(
-            ctx,
-            Vec2::new(
-                (piece.x as i32 * 50) as f32,
-                ((7 - piece.y) as i32 * 50) as f32,
-            ),
-            i.clone(),
-            Box::new(|_: &mut _| Transition::None),
-            Box::new(|_: &mut _| Transition::None),
-        )?;
-        Ok(back)
+        i
+    }
+    // the choices are stacked from the promotion square towards the middle of the board
+    fn get_promotion_choice_square(mv: &ChessMove, index: usize) -> Vec2<i8> {
+        let direction = match mv.to.color {
+            PlayerColor::WHITE => -1,
+            PlayerColor::BLACK => 1,
+        };
+        Vec2::new(mv.to.x, mv.to.y + direction * index as i8)
+    }
+    fn pick_promotion(&mut self, square: Vec2<i8>) -> Option<ChessMove> {
+        let choices = std::mem::take(&mut self.promotion_choices);
+        choices
+            .into_iter()
+            .enumerate()
+            .find(|(i, mv)| GameScene::get_promotion_choice_square(mv, *i) == square)
+            .map(|(_, mv)| mv)
+    }
+    fn draw_promotion_choices(&self, ctx: &mut Context) {
+        for (i, mv) in self.promotion_choices.iter().enumerate() {
+            let square = GameScene::get_promotion_choice_square(mv, i);
+            let pos =
+                self.pieces_box.pos + Vec2::new(50. * square.x as f32, 50. * (7 - square.y) as f32);
+            self.promotion_background.draw(ctx, pos);
+            GameScene::get_texture(&mv.to, &self.assets).draw(ctx, pos);
+        }
     }
     fn is_hovered(&self, ctx: &mut Context, pos: &Vec2<i32>, size: Vec2<f32>) -> bool {
         let pos = pos.as_();
@@ -338,16 +763,16 @@ impl GameScene {
         mp.x >= pos.x && mp.x < pos.x + size.x && mp.y >= pos.y && mp.y < pos.y + size.y
     }
     fn get_selected_square(&mut self, ctx: &mut Context) -> Option<Vec2<i8>> {
-        if tetra::input::is_mouse_button_pressed(ctx, tetra::input::MouseButton::Left) {
-            if self.is_hovered(ctx, &self.pieces_box.pos.as_(), self.pieces_box.size.as_()) {
-                let mp = tetra::input::get_mouse_position(ctx);
-                let x = mp.x - self.pieces_box.pos.x;
-                let y = self.pieces_box.pos.y + self.pieces_box.size.y - mp.y;
-                if x > 400. || y > 400. {
-                    return None;
-                }
-                return Some(Vec2::<i8>::new((x / 50.) as i8, (y / 50.) as i8));
+        if tetra::input::is_mouse_button_pressed(ctx, tetra::input::MouseButton::Left)
+            && self.is_hovered(ctx, &self.pieces_box.pos.as_(), self.pieces_box.size.as_())
+        {
+            let mp = tetra::input::get_mouse_position(ctx);
+            let x = mp.x - self.pieces_box.pos.x;
+            let y = self.pieces_box.pos.y + self.pieces_box.size.y - mp.y;
+            if x > 400. || y > 400. {
+                return None;
             }
+            return Some(Vec2::<i8>::new((x / 50.) as i8, (y / 50.) as i8));
         }
         None
     }
@@ -364,8 +789,12 @@ impl Scene for GameScene {
             graphics::reset_canvas(ctx);
         }
         self.pieces_box.draw(ctx)?;
+        self.draw_promotion_choices(ctx);
         self.history_box.draw(ctx)?;
-        self.draw_timers(ctx)?;
+        self.draw_evaluation_bar(ctx);
+        if self.is_timed {
+            self.draw_timers(ctx)?;
+        }
         Ok(Transition::None)
     }
     fn update(&mut self, ctx: &mut Context) -> tetra::Result<Transition> {
@@ -373,31 +802,38 @@ impl Scene for GameScene {
         let mut move_to_make: Option<ChessMove> = None;
         let board_state: BoardState = self.game.get_board();
         let increment: f32 = tetra::time::get_delta_time(ctx).as_millis() as f32 / 1000.0;
+        if tetra::input::is_key_pressed(ctx, tetra::input::Key::S) {
+            match self.save_game() {
+                Ok(()) => println!("Game saved to {}", SAVED_GAMES_PATH),
+                Err(e) => println!("Couldn't save the game: {}", e),
+            }
+        }
+        self.update_analysis(ctx)?;
         if !self.is_selectable {
             return Ok(Transition::None);
         }
-        if let Some(player_color) = self.player_whose_time_is_ticking {
+        if let (true, Some(player_color)) = (self.is_timed, self.player_whose_time_is_ticking) {
             let timer_ref = match player_color {
                 PlayerColor::BLACK => &mut self.black_time_remaining,
                 PlayerColor::WHITE => &mut self.white_time_remaining,
             };
             *timer_ref -= increment;
             if *timer_ref <= 0.0 {
-                self.on_win(PlayerColor::opposite(player_color));
-                self.player_whose_time_is_ticking = None;
-                self.is_selectable = false;
-                self.selected_piece = None;
+                self.game.history.player_out_of_time = Some(player_color);
+                self.player_whose_time_is_ticking =
+                    self.on_game_over(self.game.history.get_status());
             }
         }
         if self.engine.is_some()
             && self.player_whose_time_is_ticking.is_some()
             && board_state.player_to_move == PlayerColor::BLACK
         {
+            let limits = self.get_engine_search_limits();
             move_to_make = self
                 .engine
                 .as_mut()
                 .unwrap()
-                .maybe_calculate_move(board_state);
+                .maybe_calculate_move(&self.game.history, limits);
             // DEBUG:
             if move_to_make.is_some() {
                 println!("Engine made a move!");
@@ -405,9 +841,24 @@ impl Scene for GameScene {
             return self.post_update(move_to_make, ctx);
         }
         if let Some(newly_selected_square) = self.get_selected_square(ctx) {
-            if let Some(selected_piece) = self.selected_piece {
+            if !self.promotion_choices.is_empty() {
+                move_to_make = self.pick_promotion(newly_selected_square);
+                self.should_clear_notes = true;
+            } else if let Some(selected_piece) = self.selected_piece {
                 // make a move if you can here:
                 let moves = board_state.get_legal_moves(&selected_piece);
+                let promotions = moves
+                    .iter()
+                    .filter(|mv| mv.to.pos() == newly_selected_square && mv.promotion().is_some())
+                    .copied()
+                    .collect::<Vec<ChessMove>>();
+                if !promotions.is_empty() {
+                    // let the player pick the piece before making the move
+                    self.promotion_choices = promotions;
+                    self.selected_piece = None;
+                    self.should_clear_notes = true;
+                    return self.post_update(None, ctx);
+                }
                 for avlbl_move in moves {
                     if avlbl_move.to.pos() == newly_selected_square {
                         move_to_make = Some(avlbl_move);
@@ -457,3 +908,133 @@ impl Scene for GameScene {
         self.post_update(move_to_make, ctx)
     }
 }
+
+const DEFAULT_ANALYSIS_LINES: usize = 3;
+const MAX_ANALYSIS_LINES: usize = 5;
+// the plies of each line that fit into the panel
+const ANALYSIS_LINE_PLIES: usize = 8;
+// the engine thinks about whatever position is on the board, for as long as it stays there
+struct AnalysisScene {
+    board: GameScene,
+    engine: Engine,
+    panel: UIFlexBox,
+    // how many of the best moves get a line
+    lines: usize,
+    // the moves leading to the position being analysed, None to start over
+    analysed_moves: Option<Vec<ChessMove>>,
+    result: Option<SearchResult>,
+}
+impl AnalysisScene {
+    fn new(ctx: &mut Context, starting_game: Option<GameHistory>) -> tetra::Result<AnalysisScene> {
+        let mut board = GameScene::new(ctx, None, starting_game)?;
+        board.is_timed = false;
+        // the scene's own analysis feeds the evaluation bar
+        board.analysis_engine = None;
+        let panel = UIFlexBox::new(
+            ctx,
+            Vec2::new(400., 200.),
+            Vec2::new(100., 510.),
+            Vec4::<f32>::new(1.0, 0.0, 0.0, 1.0),
+            3,
+        )?;
+        let mut scene = AnalysisScene {
+            board,
+            engine: Engine::new(),
+            panel,
+            lines: DEFAULT_ANALYSIS_LINES,
+            analysed_moves: None,
+            result: None,
+        };
+        scene.update_panel(ctx)?;
+        Ok(scene)
+    }
+    fn update_panel(&mut self, ctx: &mut Context) -> tetra::Result {
+        let board = self.board.game.get_board();
+        let mut rows = vec![format!(
+            "{} lines (up/down), backspace takes back",
+            self.lines
+        )];
+        match &self.result {
+            Some(result) => {
+                rows.push(format!(
+                    "Depth {}   {}   {}k nodes",
+                    result.depth,
+                    format_score(result.score, board.player_to_move),
+                    result.nodes / 1000
+                ));
+                for line in result.lines.iter() {
+                    let plies = line.principal_variation.len().min(ANALYSIS_LINE_PLIES);
+                    rows.push(format!(
+                        "{}   {}",
+                        format_score(line.score, board.player_to_move),
+                        board.line_to_san(&line.principal_variation[..plies])
+                    ));
+                }
+            }
+            None => rows.push("Thinking...".to_owned()),
+        }
+        let font = self.board.assets.font.with_size(ctx, 16.0)?;
+        let mut children: Vec<Box<dyn Scene>> = Vec::new();
+        for (i, row) in rows.iter().enumerate() {
+            children.push(Box::new(UIText::new(
+                ctx,
+                Vec2::new(10., 10. + 20. * i as f32),
+                Text::new(row.as_str(), font.clone()),
+                Box::new(|_: &mut _| Transition::None),
+                Box::new(|_: &mut _| Transition::None),
+            )?));
+        }
+        self.panel.children = children;
+        Ok(())
+    }
+}
+// from White's side, in pawns or as the moves until mate
+fn format_score(score: i32, player_to_move: PlayerColor) -> String {
+    let score = match player_to_move {
+        PlayerColor::WHITE => score,
+        PlayerColor::BLACK => -score,
+    };
+    match get_mate_in(score) {
+        Some(mate_in) if mate_in >= 0 => format!("#{}", mate_in),
+        Some(mate_in) => format!("#-{}", -mate_in),
+        None => format!("{:+.2}", score as f32 / 100.0),
+    }
+}
+impl Scene for AnalysisScene {
+    fn draw(&mut self, ctx: &mut Context) -> tetra::Result<Transition> {
+        self.board.draw(ctx)?;
+        self.panel.draw(ctx)?;
+        Ok(Transition::None)
+    }
+    fn update(&mut self, ctx: &mut Context) -> tetra::Result<Transition> {
+        use tetra::input::{is_key_pressed, Key};
+        if is_key_pressed(ctx, Key::Backspace) {
+            self.board.take_back(ctx)?;
+        }
+        if is_key_pressed(ctx, Key::Up) && self.lines < MAX_ANALYSIS_LINES {
+            self.lines += 1;
+            self.analysed_moves = None;
+        }
+        if is_key_pressed(ctx, Key::Down) && self.lines > 1 {
+            self.lines -= 1;
+            self.analysed_moves = None;
+        }
+        let transition = self.board.update(ctx)?;
+        let moves = &self.board.game.history.moves;
+        if self.analysed_moves.as_ref() != Some(moves) {
+            self.analysed_moves = Some(moves.clone());
+            self.engine
+                .start_analysis(&self.board.game.history, self.lines);
+            self.result = None;
+            self.update_panel(ctx)?;
+        }
+        if let Some(result) = self.engine.poll_analysis() {
+            let board = self.board.game.get_board();
+            self.board
+                .set_evaluation(board.score_for_current_player(result.score));
+            self.result = Some(result);
+            self.update_panel(ctx)?;
+        }
+        Ok(transition)
+    }
+}
@@ -0,0 +1,963 @@
+use crate::engine::Engine;
+use crate::evaluation::EvaluationWeights;
+use crate::game_types::*;
+use crate::move_ordering::*;
+use crate::tablebase::{Tablebases, Wdl, MAX_RANK};
+use crate::transposition::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// scores are in centipawns for the side to move, a mate is MATE_SCORE less the plies it takes
+pub const MATE_SCORE: i32 = 30_000;
+const INFINITE_SCORE: i32 = 31_000;
+pub const MAX_DEPTH: u32 = 64;
+// a win the tablebases know of, less the plies it takes to get to it, sits right below the mates
+pub const TABLEBASE_WIN_SCORE: i32 = MATE_SCORE - 2 * MAX_DEPTH as i32;
+// nodes between looking at the clock and the stop signal
+const TIME_CHECK_INTERVAL: u64 = 2048;
+// kept off the clock for the time it takes the move to get played
+const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+// the number of moves the time left is split over when the time control doesn't say
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+// how much a capture may gain on top of the taken piece's worth through positional factors
+const DELTA_MARGIN: i32 = 200;
+// a null move is searched this much shallower than a real one, and more so the deeper it goes
+const NULL_MOVE_MIN_DEPTH: u32 = 3;
+const NULL_MOVE_REDUCTION: u32 = 2;
+// how far below alpha a position may be, per ply left, before its quiet moves aren't searched
+const FUTILITY_MAX_DEPTH: u32 = 3;
+const FUTILITY_MARGIN: i32 = 150;
+// how far above beta a position has to be, per ply left, to be cut off without searching it
+const REVERSE_FUTILITY_MAX_DEPTH: u32 = 6;
+const REVERSE_FUTILITY_MARGIN: i32 = 100;
+// the first moves, and any near the horizon, are always searched to the full depth
+const LATE_MOVE_MIN_DEPTH: u32 = 3;
+const FULL_DEPTH_MOVES: usize = 3;
+// how narrow the window around the last depth's score starts, it doubles every time it fails
+const ASPIRATION_WINDOW: i32 = 25;
+const ASPIRATION_MIN_DEPTH: u32 = 5;
+
+pub type IterationCallback = Box<dyn FnMut(&SearchResult) + Send>;
+
+/** When a search should stop, whichever limit is reached first. Without any it goes on until it's
+cancelled or reaches MAX_DEPTH. The first depth is always finished, so there's a move to play. */
+#[derive(Clone, Debug, Default)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub move_time: Option<Duration>,
+    pub clock: Option<Clock>,
+}
+/** The time the side to move has for the rest of the game, or until the next time control. */
+#[derive(Clone, Copy, Debug)]
+pub struct Clock {
+    pub time_left: Duration,
+    pub increment: Duration,
+    pub moves_to_go: Option<u32>,
+}
+/** Stops a search from another thread, the search then returns the best move it has found so far. */
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+/** Which ways of searching some moves less, or more, than the others are used. All of them are on by
+default, turning them off one at a time shows what each is worth. */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Selectivity {
+    // let the opponent move twice, if that still doesn't get them below beta a real move won't either
+    pub null_move_pruning: bool,
+    // search the quiet moves that come late in the order shallower, unless they turn out to be good
+    pub late_move_reductions: bool,
+    // right before the horizon, skip the quiet moves of a position too far below alpha
+    pub futility_pruning: bool,
+    // cut off a position so far above beta that it's unlikely to drop below it
+    pub reverse_futility_pruning: bool,
+    // search checks a ply deeper, so that the horizon doesn't hide what they lead to
+    pub check_extensions: bool,
+    // start each depth with a narrow window around the last one's score
+    pub aspiration_windows: bool,
+}
+/** One of the best moves with the line expected to follow it. */
+#[derive(Clone)]
+pub struct SearchLine {
+    pub score: i32,
+    pub principal_variation: Vec<ChessMove>,
+}
+#[derive(Clone)]
+pub struct SearchResult {
+    pub best_move: Option<ChessMove>,
+    pub score: i32,
+    pub depth: u32,
+    pub principal_variation: Vec<ChessMove>,
+    // the best line first, then as many runners-up as multi_pv asks for
+    pub lines: Vec<SearchLine>,
+    pub nodes: u64,
+    pub elapsed: Duration,
+    pub hash_hit_rate: f64,
+    // permill of the transposition table filled in by this search
+    pub hash_usage: u32,
+}
+pub struct Searcher {
+    // shared with the helper threads
+    pub transposition_table: Arc<TranspositionTable>,
+    pub evaluation_weights: EvaluationWeights,
+    // keys of the game's positions before the searched one, repeating them is a draw too
+    pub previous_positions: Vec<u64>,
+    // called with the result of every finished depth
+    pub on_iteration: Option<IterationCallback>,
+    // how many of the best moves get a line of their own, the others only need to be refuted
+    pub multi_pv: usize,
+    // endgames with few enough pieces are looked up instead of searched
+    pub tablebases: Option<Arc<Tablebases>>,
+    pub move_ordering: MoveOrdering,
+    pub selectivity: Selectivity,
+    // with more than one the search is run by as many threads, see Searcher::search
+    pub threads: usize,
+    // 0 for the main thread, which reports and decides when to stop, helpers count up from 1
+    thread_index: usize,
+    cancellation: CancellationToken,
+    nodes: u64,
+    max_nodes: Option<u64>,
+    deadline: Option<Instant>,
+    // a search can only be cut short once it has a move to play
+    can_abort: bool,
+    is_aborted: bool,
+    // the line of the last finished depth, searched first in the next one
+    previous_principal_variation: Vec<ChessMove>,
+    // the moves that already have a line at the current depth
+    excluded_root_moves: Vec<ChessMove>,
+    // keys of the positions leading to the current node, for spotting repetitions
+    position_hashes: Vec<u64>,
+    // the moves leading to the current node from the root, None for a null move
+    move_stack: Vec<Option<ChessMove>>,
+    // where in position_hashes the positions after the last null move start, none before it can repeat
+    null_move_position: usize,
+    hash_probes: u64,
+    hash_hits: u64,
+}
+// where a node of negamax sits in the tree, handed down from its parent
+#[derive(Clone, Copy)]
+struct Node {
+    ply: i32,
+    is_on_principal_variation: bool,
+    // two null moves in a row would only search the same position shallower
+    allows_null_move: bool,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u32) -> SearchLimits {
+        SearchLimits {
+            depth: Some(depth),
+            ..SearchLimits::default()
+        }
+    }
+    pub fn move_time(move_time: Duration) -> SearchLimits {
+        SearchLimits {
+            move_time: Some(move_time),
+            ..SearchLimits::default()
+        }
+    }
+    pub fn clock(clock: Clock) -> SearchLimits {
+        SearchLimits {
+            clock: Some(clock),
+            ..SearchLimits::default()
+        }
+    }
+    /** The longest the search may take, out of the move time and the share of the clock. */
+    pub fn get_time_limit(&self) -> Option<Duration> {
+        let clock_time = self.clock.map(|clock| clock.allocate_time());
+        match (self.move_time, clock_time) {
+            (Some(move_time), Some(clock_time)) => Some(move_time.min(clock_time)),
+            (move_time, clock_time) => move_time.or(clock_time),
+        }
+    }
+}
+impl Clock {
+    /** A share of the time left plus most of the increment, never more than what's on the clock. */
+    pub fn allocate_time(&self) -> Duration {
+        let usable = self
+            .time_left
+            .checked_sub(MOVE_OVERHEAD)
+            .unwrap_or_default();
+        let moves_to_go = self.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        (usable / moves_to_go + self.increment * 3 / 4).min(usable)
+    }
+}
+impl Default for Selectivity {
+    fn default() -> Selectivity {
+        Selectivity {
+            null_move_pruning: true,
+            late_move_reductions: true,
+            futility_pruning: true,
+            reverse_futility_pruning: true,
+            check_extensions: true,
+            aspiration_windows: true,
+        }
+    }
+}
+impl Selectivity {
+    /** Every move is searched to the same depth, like a plain alpha-beta search. */
+    pub fn none() -> Selectivity {
+        Selectivity {
+            null_move_pruning: false,
+            late_move_reductions: false,
+            futility_pruning: false,
+            reverse_futility_pruning: false,
+            check_extensions: false,
+            aspiration_windows: false,
+        }
+    }
+}
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+impl Node {
+    fn root() -> Node {
+        Node {
+            ply: 0,
+            is_on_principal_variation: true,
+            allows_null_move: true,
+        }
+    }
+    fn child(self, is_on_principal_variation: bool) -> Node {
+        Node {
+            ply: self.ply + 1,
+            is_on_principal_variation,
+            allows_null_move: true,
+        }
+    }
+    fn after_null_move(self) -> Node {
+        Node {
+            ply: self.ply + 1,
+            is_on_principal_variation: false,
+            allows_null_move: false,
+        }
+    }
+}
+
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() >= MATE_SCORE - MAX_DEPTH as i32
+}
+/** The number of moves until mate, negative when the side to move is getting mated. */
+pub fn get_mate_in(score: i32) -> Option<i32> {
+    if !is_mate_score(score) {
+        return None;
+    }
+    if score > 0 {
+        Some((MATE_SCORE - score + 1) / 2)
+    } else {
+        Some(-(MATE_SCORE + score) / 2)
+    }
+}
+// the later a move comes and the deeper the search, the less likely it's the best one
+fn get_late_move_reduction(depth: u32, searched_moves: usize) -> u32 {
+    ((depth as f64).ln() * (searched_moves as f64).ln() / 2.0).max(1.0) as u32
+}
+// in pawn endings having to move is often what loses, so passing would prove nothing there
+fn has_non_pawn_material(board: &BoardState) -> bool {
+    let pawns_and_kings = board.piece_bitboards[PieceType::PAWN.index()]
+        | board.piece_bitboards[PieceType::KING.index()];
+    board.color_bitboards[board.player_to_move.index()] & !pawns_and_kings != 0
+}
+impl Searcher {
+    pub fn new(hash_size_mb: usize) -> Searcher {
+        Searcher {
+            transposition_table: Arc::new(TranspositionTable::new(hash_size_mb)),
+            evaluation_weights: EvaluationWeights::default(),
+            previous_positions: Vec::new(),
+            on_iteration: None,
+            multi_pv: 1,
+            tablebases: None,
+            move_ordering: MoveOrdering::new(),
+            selectivity: Selectivity::default(),
+            threads: 1,
+            thread_index: 0,
+            cancellation: CancellationToken::new(),
+            nodes: 0,
+            max_nodes: None,
+            deadline: None,
+            can_abort: false,
+            is_aborted: false,
+            previous_principal_variation: Vec::new(),
+            excluded_root_moves: Vec::new(),
+            position_hashes: Vec::new(),
+            move_stack: Vec::new(),
+            null_move_position: 0,
+            hash_probes: 0,
+            hash_hits: 0,
+        }
+    }
+    // another thread's searcher, with the same table, weights and tables, and its own move ordering
+    fn create_helper(&self, thread_index: usize) -> Searcher {
+        Searcher {
+            transposition_table: self.transposition_table.clone(),
+            evaluation_weights: self.evaluation_weights.clone(),
+            previous_positions: self.previous_positions.clone(),
+            tablebases: self.tablebases.clone(),
+            selectivity: self.selectivity,
+            thread_index,
+            ..Searcher::new(0)
+        }
+    }
+    /** Searches one ply deeper at a time until it reaches one of the limits or is cancelled, the
+    result of the last finished depth is kept.
+
+    With more than one thread, Lazy SMP: the helpers search the same position at the same time
+    without any further coordination, all they share is the transposition table, which they fill
+    with what the others would otherwise have to search themselves. The main thread keeps to the
+    limits, and once it's done the result of whichever thread finished the deepest depth is played.
+    Only a single thread is deterministic. */
+    pub fn search(
+        &mut self,
+        board: &BoardState,
+        limits: &SearchLimits,
+        cancellation: &CancellationToken,
+    ) -> SearchResult {
+        let started_at = Instant::now();
+        self.transposition_table.new_search();
+        if let Some(result) = self.probe_root(board, started_at) {
+            return result;
+        }
+        // the lines a weakened engine picks from only come from the main thread
+        if self.threads <= 1 || self.multi_pv > 1 {
+            return self.iterative_deepening(board, limits, cancellation, started_at);
+        }
+        let mut helpers = (1..self.threads)
+            .map(|thread_index| self.create_helper(thread_index))
+            .collect::<Vec<_>>();
+        // the helpers go on until the main thread is done
+        let helper_limits = SearchLimits {
+            depth: limits.depth,
+            ..SearchLimits::default()
+        };
+        let helper_cancellation = CancellationToken::new();
+        let (mut result, helper_results) = thread::scope(|scope| {
+            let handles = helpers
+                .iter_mut()
+                .map(|helper| {
+                    let helper_limits = &helper_limits;
+                    let helper_cancellation = &helper_cancellation;
+                    scope.spawn(move || {
+                        helper.iterative_deepening(
+                            board,
+                            helper_limits,
+                            helper_cancellation,
+                            started_at,
+                        )
+                    })
+                })
+                .collect::<Vec<_>>();
+            let result = self.iterative_deepening(board, limits, cancellation, started_at);
+            helper_cancellation.cancel();
+            let helper_results = handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>();
+            (result, helper_results)
+        });
+        let nodes = result.nodes + helper_results.iter().map(|other| other.nodes).sum::<u64>();
+        // a deeper depth saw more, of equal ones the main thread's is kept
+        if let Some(deepest) = helper_results
+            .into_iter()
+            .filter(|other| other.best_move.is_some() && other.depth > result.depth)
+            .max_by_key(|other| other.depth)
+        {
+            result = SearchResult {
+                hash_hit_rate: result.hash_hit_rate,
+                ..deepest
+            };
+            if let Some(on_iteration) = self.on_iteration.as_mut() {
+                on_iteration(&SearchResult {
+                    nodes,
+                    ..result.clone()
+                });
+            }
+        }
+        result.nodes = nodes;
+        result
+    }
+    fn iterative_deepening(
+        &mut self,
+        board: &BoardState,
+        limits: &SearchLimits,
+        cancellation: &CancellationToken,
+        started_at: Instant,
+    ) -> SearchResult {
+        let time_limit = limits.get_time_limit();
+        let mut board = board.clone();
+        self.nodes = 0;
+        self.can_abort = false;
+        self.is_aborted = false;
+        self.max_nodes = limits.nodes;
+        self.deadline = time_limit.map(|time_limit| started_at + time_limit);
+        self.cancellation = cancellation.clone();
+        self.previous_principal_variation.clear();
+        self.position_hashes = self.previous_positions.clone();
+        self.position_hashes.push(board.hash());
+        self.move_ordering.new_search();
+        self.move_stack.clear();
+        self.null_move_position = 0;
+        self.hash_probes = 0;
+        self.hash_hits = 0;
+        let mut result = SearchResult {
+            best_move: None,
+            score: 0,
+            depth: 0,
+            principal_variation: Vec::new(),
+            lines: Vec::new(),
+            nodes: 0,
+            elapsed: Duration::from_secs(0),
+            hash_hit_rate: 0.0,
+            hash_usage: 0,
+        };
+        let max_depth = limits.depth.unwrap_or(MAX_DEPTH).min(MAX_DEPTH);
+        for depth in 1..=max_depth {
+            // half the helpers skip every other depth, so that the threads aren't all on the same one
+            if self.thread_index % 2 == 1 && depth > 1 && depth % 2 == 0 {
+                continue;
+            }
+            let mut lines = Vec::<SearchLine>::with_capacity(self.multi_pv);
+            // a position without moves has a score but no line
+            let mut score = 0;
+            self.excluded_root_moves.clear();
+            for i in 0..self.multi_pv.max(1) {
+                self.previous_principal_variation = result
+                    .lines
+                    .get(i)
+                    .map(|line| line.principal_variation.clone())
+                    .unwrap_or_default();
+                let mut principal_variation = Vec::new();
+                let previous_score = result.lines.get(i).map(|line| line.score);
+                let line_score =
+                    self.search_root(&mut board, depth, previous_score, &mut principal_variation);
+                if i == 0 {
+                    score = line_score;
+                }
+                if self.is_aborted || principal_variation.is_empty() {
+                    break;
+                }
+                self.excluded_root_moves.push(principal_variation[0]);
+                lines.push(SearchLine {
+                    score: line_score,
+                    principal_variation,
+                });
+            }
+            if self.is_aborted {
+                break;
+            }
+            lines.sort_by_key(|line| -line.score);
+            let principal_variation = lines
+                .first()
+                .map(|line| line.principal_variation.clone())
+                .unwrap_or_default();
+            result = SearchResult {
+                best_move: principal_variation.first().copied(),
+                score: lines.first().map_or(score, |line| line.score),
+                depth,
+                principal_variation,
+                lines,
+                nodes: self.nodes,
+                elapsed: started_at.elapsed(),
+                hash_hit_rate: self.get_hash_hit_rate(),
+                hash_usage: self.transposition_table.usage_permill(),
+            };
+            if let Some(on_iteration) = self.on_iteration.as_mut() {
+                on_iteration(&result);
+            }
+            // the first depth always finishes, so there's a move to play no matter what
+            self.can_abort = true;
+            // deeper searches can't find a shorter mate
+            if let Some(mate_in) = get_mate_in(result.score) {
+                if (mate_in.abs() * 2) as u32 <= depth {
+                    break;
+                }
+            }
+            if result.best_move.is_none() {
+                break;
+            }
+            // on the clock, a depth that won't finish in time would only waste it
+            if let (Some(_), Some(time_limit)) = (limits.clock, time_limit) {
+                if started_at.elapsed() > time_limit / 2 {
+                    break;
+                }
+            }
+        }
+        result.nodes = self.nodes;
+        result.elapsed = started_at.elapsed();
+        result.hash_hit_rate = self.get_hash_hit_rate();
+        result
+    }
+    // around the score of the last depth the window is narrow, which cuts off more, but a score
+    // outside of it only says which side it's on, so then it's searched again with a wider one
+    fn search_root(
+        &mut self,
+        board: &mut BoardState,
+        depth: u32,
+        previous_score: Option<i32>,
+        principal_variation: &mut Vec<ChessMove>,
+    ) -> i32 {
+        let mut alpha = -INFINITE_SCORE;
+        let mut beta = INFINITE_SCORE;
+        if let Some(previous_score) = previous_score {
+            if self.selectivity.aspiration_windows
+                && depth >= ASPIRATION_MIN_DEPTH
+                && !is_mate_score(previous_score)
+            {
+                alpha = previous_score - ASPIRATION_WINDOW;
+                beta = previous_score + ASPIRATION_WINDOW;
+            }
+        }
+        let mut window = ASPIRATION_WINDOW;
+        loop {
+            principal_variation.clear();
+            let score = self.negamax(board, depth, Node::root(), alpha, beta, principal_variation);
+            if self.is_aborted {
+                return score;
+            }
+            if score <= alpha && alpha > -INFINITE_SCORE {
+                alpha = (score - window).max(-INFINITE_SCORE);
+            } else if score >= beta && beta < INFINITE_SCORE {
+                beta = (score + window).min(INFINITE_SCORE);
+            } else {
+                return score;
+            }
+            window *= 2;
+        }
+    }
+    // with the position in the tablebases the best moves are known without searching
+    fn probe_root(&mut self, board: &BoardState, started_at: Instant) -> Option<SearchResult> {
+        let root_moves = self.tablebases.as_ref()?.probe_root(board)?;
+        let lines = root_moves
+            .iter()
+            .take(self.multi_pv.max(1))
+            .map(|root_move| SearchLine {
+                score: if root_move.rank == MAX_RANK {
+                    TABLEBASE_WIN_SCORE - root_move.dtz
+                } else if root_move.rank == -MAX_RANK {
+                    -TABLEBASE_WIN_SCORE - root_move.dtz
+                } else {
+                    0
+                },
+                principal_variation: vec![root_move.mv],
+            })
+            .collect::<Vec<_>>();
+        // a mate or a stalemate, the search has to score it
+        if lines.is_empty() {
+            return None;
+        }
+        let result = SearchResult {
+            best_move: Some(lines[0].principal_variation[0]),
+            score: lines[0].score,
+            depth: 1,
+            principal_variation: lines[0].principal_variation.clone(),
+            lines,
+            nodes: 0,
+            elapsed: started_at.elapsed(),
+            hash_hit_rate: 0.0,
+            hash_usage: self.transposition_table.usage_permill(),
+        };
+        if let Some(on_iteration) = self.on_iteration.as_mut() {
+            on_iteration(&result);
+        }
+        Some(result)
+    }
+    fn negamax(
+        &mut self,
+        board: &mut BoardState,
+        depth: u32,
+        node: Node,
+        mut alpha: i32,
+        mut beta: i32,
+        principal_variation: &mut Vec<ChessMove>,
+    ) -> i32 {
+        let ply = node.ply;
+        if depth == 0 {
+            return self.quiescence(board, ply, alpha, beta);
+        }
+        self.count_node();
+        if self.is_aborted {
+            return 0;
+        }
+        if ply > 0 {
+            if self.is_draw(board) {
+                return 0;
+            }
+            // no line can be better than mating right now, or worse than getting mated right now
+            alpha = alpha.max(-MATE_SCORE + ply);
+            beta = beta.min(MATE_SCORE - ply - 1);
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+        let original_alpha = alpha;
+        self.hash_probes += 1;
+        let hash_move = match self.transposition_table.probe(board.hash(), ply) {
+            Some(entry) => {
+                self.hash_hits += 1;
+                // the root has to come up with a move, it's never cut off
+                if ply > 0 {
+                    if let Some(score) = entry.get_cutoff_score(depth, alpha, beta) {
+                        if entry.bound == Bound::Exact {
+                            principal_variation.extend(entry.best_move);
+                        }
+                        return score;
+                    }
+                }
+                entry.best_move
+            }
+            None => None,
+        };
+        // right after a capture or pawn move the result of the tables holds, the fifty-move rule
+        // included, so it bounds the score of any line from here
+        if ply > 0 && board.halfmove_clock == 0 {
+            let wdl = self
+                .tablebases
+                .as_ref()
+                .and_then(|tablebases| tablebases.probe_wdl(board));
+            if let Some(wdl) = wdl {
+                let (score, bound) = match wdl {
+                    Wdl::Win => (TABLEBASE_WIN_SCORE - ply, Bound::Lower),
+                    Wdl::Loss => (-TABLEBASE_WIN_SCORE + ply, Bound::Upper),
+                    _ => (0, Bound::Exact),
+                };
+                if bound == Bound::Exact
+                    || (bound == Bound::Lower && score >= beta)
+                    || (bound == Bound::Upper && score <= alpha)
+                {
+                    self.transposition_table.store(
+                        board.hash(),
+                        MAX_DEPTH,
+                        ply,
+                        score,
+                        bound,
+                        None,
+                    );
+                    return score;
+                }
+            }
+        }
+        let is_in_check = board.evaluate_is_check(None);
+        // the pruning leans on the static evaluation, which says little in check or on the line
+        // that's expected to be the best
+        let can_prune = ply > 0 && !node.is_on_principal_variation && !is_in_check;
+        let static_evaluation = if can_prune {
+            board.score_for_current_player(self.evaluation_weights.evaluate(board))
+        } else {
+            0
+        };
+        if self.selectivity.reverse_futility_pruning
+            && can_prune
+            && depth <= REVERSE_FUTILITY_MAX_DEPTH
+            && !is_mate_score(beta)
+            && static_evaluation - REVERSE_FUTILITY_MARGIN * depth as i32 >= beta
+        {
+            return static_evaluation;
+        }
+        if self.selectivity.null_move_pruning
+            && can_prune
+            && depth >= NULL_MOVE_MIN_DEPTH
+            && static_evaluation >= beta
+            && !is_mate_score(beta)
+            && node.allows_null_move
+            && has_non_pawn_material(board)
+        {
+            let reduction = NULL_MOVE_REDUCTION + depth / 4;
+            let undo_info = board.make_null_move();
+            self.position_hashes.push(board.hash());
+            self.move_stack.push(None);
+            let null_move_position = self.null_move_position;
+            self.null_move_position = self.position_hashes.len() - 1;
+            let score = -self.negamax(
+                board,
+                depth.saturating_sub(1 + reduction),
+                node.after_null_move(),
+                -beta,
+                -beta + 1,
+                &mut Vec::new(),
+            );
+            self.null_move_position = null_move_position;
+            self.move_stack.pop();
+            self.position_hashes.pop();
+            board.unmake_null_move(undo_info);
+            if self.is_aborted {
+                return 0;
+            }
+            // a mate found this way is no proof, a real move might not allow it
+            if score >= beta {
+                return beta;
+            }
+        }
+        let mut moves = board.get_all_legal_moves();
+        if moves.is_empty() {
+            if is_in_check {
+                return -MATE_SCORE + ply;
+            }
+            return 0;
+        }
+        if ply == 0 {
+            let excluded_root_moves = &self.excluded_root_moves;
+            moves.retain(|mv| !excluded_root_moves.contains(mv));
+            if moves.is_empty() {
+                return -INFINITE_SCORE;
+            }
+        }
+        let principal_move = if node.is_on_principal_variation {
+            self.previous_principal_variation.get(ply as usize).copied()
+        } else {
+            None
+        };
+        let previous_move = self.move_stack.last().copied().flatten();
+        let refutations = self.move_ordering.get_refutations(ply, previous_move);
+        let mut picker = MovePicker::new(
+            board,
+            moves,
+            principal_move.or(hash_move),
+            &refutations,
+            &self.move_ordering,
+        );
+
+        let futility_score = static_evaluation + FUTILITY_MARGIN * depth as i32;
+        let is_futile = self.selectivity.futility_pruning
+            && can_prune
+            && depth <= FUTILITY_MAX_DEPTH
+            && !is_mate_score(alpha)
+            && futility_score <= alpha;
+
+        let mut best_score = -INFINITE_SCORE;
+        let mut best_move = None;
+        let mut searched_quiets = Vec::new();
+        let mut searched_moves = 0;
+        while let Some(mv) = picker.next(board) {
+            let is_quiet = !is_tactical(board, &mv);
+            let undo_info = board.make_move(&mv);
+            let gives_check = board.evaluate_is_check(None);
+            // a quiet move won't make up for that much this close to the horizon, unless it's a check
+            if is_futile && is_quiet && !gives_check {
+                board.unmake_move(&mv, undo_info);
+                best_score = best_score.max(futility_score);
+                continue;
+            }
+            searched_moves += 1;
+            self.position_hashes.push(board.hash());
+            self.move_stack.push(Some(mv));
+            // extensions stop halfway, so that a run of checks can't go on forever
+            let extension =
+                if self.selectivity.check_extensions && gives_check && ply < MAX_DEPTH as i32 {
+                    1
+                } else {
+                    0
+                };
+            let new_depth = depth - 1 + extension;
+            let reduction = if self.selectivity.late_move_reductions
+                && ply > 0
+                && depth >= LATE_MOVE_MIN_DEPTH
+                && searched_moves > FULL_DEPTH_MOVES
+                && is_quiet
+                && !is_in_check
+                && !gives_check
+                && !refutations.contains(&mv)
+            {
+                get_late_move_reduction(depth, searched_moves).min(new_depth - 1)
+            } else {
+                0
+            };
+            let mut child_variation = Vec::new();
+            let mut score = 0;
+            let mut needs_full_search = true;
+            // the reduced search only has to show the move doesn't beat alpha
+            if reduction > 0 {
+                score = -self.negamax(
+                    board,
+                    new_depth - reduction,
+                    node.child(false),
+                    -alpha - 1,
+                    -alpha,
+                    &mut child_variation,
+                );
+                needs_full_search = score > alpha && !self.is_aborted;
+            }
+            if needs_full_search {
+                child_variation.clear();
+                score = -self.negamax(
+                    board,
+                    new_depth,
+                    node.child(principal_move == Some(mv)),
+                    -beta,
+                    -alpha,
+                    &mut child_variation,
+                );
+            }
+            self.move_stack.pop();
+            self.position_hashes.pop();
+            board.unmake_move(&mv, undo_info);
+            if self.is_aborted {
+                return 0;
+            }
+            if score > best_score {
+                best_score = score;
+                best_move = Some(mv);
+            }
+            if score > alpha {
+                alpha = score;
+                principal_variation.clear();
+                principal_variation.push(mv);
+                principal_variation.append(&mut child_variation);
+                if alpha >= beta {
+                    if is_quiet {
+                        self.move_ordering.record_cutoff(
+                            mv,
+                            ply,
+                            depth,
+                            previous_move,
+                            &searched_quiets,
+                        );
+                    }
+                    break;
+                }
+            }
+            if is_quiet {
+                searched_quiets.push(mv);
+            }
+        }
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        // failing low every move scores below alpha, so none of them is known to be the best
+        let best_move = if bound == Bound::Upper {
+            None
+        } else {
+            best_move
+        };
+        // with some moves left out the root's score isn't the position's
+        if ply > 0 || self.excluded_root_moves.is_empty() {
+            self.transposition_table
+                .store(board.hash(), depth, ply, best_score, bound, best_move);
+        }
+        best_score
+    }
+    // past the horizon only captures are searched, until the position is quiet, so that the score of
+    // the leaves doesn't hinge on a piece that's about to be taken
+    fn quiescence(&mut self, board: &mut BoardState, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+        self.count_node();
+        if self.is_aborted {
+            return 0;
+        }
+        if self.is_draw(board) {
+            return 0;
+        }
+        let static_evaluation =
+            board.score_for_current_player(self.evaluation_weights.evaluate(board));
+        if ply >= MAX_DEPTH as i32 * 2 {
+            return static_evaluation;
+        }
+        // in check every move has to be looked at, otherwise a mate would go unnoticed
+        let is_in_check = board.evaluate_is_check(None);
+        let mut best_score;
+        let mut moves;
+        if is_in_check {
+            moves = board.get_all_legal_moves();
+            if moves.is_empty() {
+                return -MATE_SCORE + ply;
+            }
+            best_score = -INFINITE_SCORE;
+        } else {
+            // stand pat, the side to move doesn't have to take anything
+            if static_evaluation >= beta {
+                return static_evaluation;
+            }
+            alpha = alpha.max(static_evaluation);
+            best_score = static_evaluation;
+            moves = board.get_all_legal_captures();
+        }
+        moves.sort_by_key(|mv| -get_mvv_lva_score(board, mv));
+
+        for mv in moves.iter() {
+            if !is_in_check && mv.promotion().is_none() {
+                // delta pruning, not even taking the piece for free would get the score up to alpha
+                let victim = match board.get_piece_at_square(mv.to.pos()) {
+                    Some(victim) => Engine::get_piece_worth(&victim),
+                    None => Engine::get_piece_type_worth(PieceType::PAWN),
+                };
+                if static_evaluation + victim + DELTA_MARGIN <= alpha {
+                    continue;
+                }
+                // captures that lose material in the exchange aren't worth following
+                if board.static_exchange_evaluation(mv) < 0 {
+                    continue;
+                }
+            }
+            let undo_info = board.make_move(mv);
+            self.position_hashes.push(board.hash());
+            let score = -self.quiescence(board, ply + 1, -beta, -alpha);
+            self.position_hashes.pop();
+            board.unmake_move(mv, undo_info);
+            if self.is_aborted {
+                return 0;
+            }
+            if score > best_score {
+                best_score = score;
+            }
+            if score > alpha {
+                alpha = score;
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+        best_score
+    }
+    // the share of probes since the start of the search that found their position
+    fn get_hash_hit_rate(&self) -> f64 {
+        if self.hash_probes == 0 {
+            return 0.0;
+        }
+        self.hash_hits as f64 / self.hash_probes as f64
+    }
+    fn count_node(&mut self) {
+        self.nodes += 1;
+        if !self.can_abort {
+            return;
+        }
+        if self
+            .max_nodes
+            .is_some_and(|max_nodes| self.nodes >= max_nodes)
+        {
+            self.is_aborted = true;
+        }
+        if self.nodes.is_multiple_of(TIME_CHECK_INTERVAL) {
+            self.is_aborted |= self.cancellation.is_cancelled()
+                || self
+                    .deadline
+                    .is_some_and(|deadline| Instant::now() >= deadline);
+        }
+    }
+    fn is_draw(&self, board: &BoardState) -> bool {
+        board.halfmove_clock >= 100 || self.is_repetition(board)
+    }
+    // the position occurred before since the last capture or pawn move, with the same side to move
+    fn is_repetition(&self, board: &BoardState) -> bool {
+        let hash = board.hash();
+        self.position_hashes
+            .iter()
+            .rev()
+            .take(
+                (board.halfmove_clock as usize + 1)
+                    .min(self.position_hashes.len() - self.null_move_position),
+            )
+            .skip(2)
+            .step_by(2)
+            .any(|other| *other == hash)
+    }
+}
@@ -0,0 +1,75 @@
+use crate::bitboard::*;
+use crate::engine::Engine;
+use crate::game_types::*;
+
+// the order attackers join an exchange in, cheapest first
+const EXCHANGE_ORDER: [PieceType; 6] = [
+    PieceType::PAWN,
+    PieceType::KNIGHT,
+    PieceType::BISHOP,
+    PieceType::ROOK,
+    PieceType::QUEEN,
+    PieceType::KING,
+];
+
+impl BoardState {
+    /** The material the side to move ends up winning, in centipawns, if both sides keep recapturing
+    on the target square of the move with their cheapest piece for as long as it pays off.
+    Pins and promotions aren't taken into account. */
+    pub fn static_exchange_evaluation(&self, mv: &ChessMove) -> i32 {
+        let target = square_index(mv.to.x, mv.to.y);
+        let mut occupied = self.get_occupied() ^ square_bit(mv.from.x, mv.from.y);
+        // gains[i] is what the side making the i-th capture is up if the exchange ends right there
+        let mut gains = [0; 32];
+        gains[0] = match self.get_piece_at_square(mv.to.pos()) {
+            Some(victim) => Engine::get_piece_worth(&victim),
+            None if mv.from.piece_type == PieceType::PAWN && mv.from.x != mv.to.x => {
+                // en passant, the taken pawn isn't on the target square
+                occupied ^= square_bit(mv.to.x, mv.from.y);
+                Engine::get_piece_type_worth(PieceType::PAWN)
+            }
+            None => 0,
+        };
+        let mut piece_on_target = mv.from.piece_type;
+        let mut side = PlayerColor::opposite(mv.from.color);
+        let mut depth = 0;
+        while depth + 1 < gains.len() {
+            let attackers = self.get_attackers_bitboard(target, occupied) & occupied;
+            let own_attackers = attackers & self.color_bitboards[side.index()];
+            let cheapest = EXCHANGE_ORDER.iter().find_map(|piece_type| {
+                let bitboard = own_attackers & self.piece_bitboards[piece_type.index()];
+                if bitboard == 0 {
+                    return None;
+                }
+                Some((*piece_type, bitboard & bitboard.wrapping_neg()))
+            });
+            let (piece_type, bit) = match cheapest {
+                Some(attacker) => attacker,
+                None => break,
+            };
+            // the king can only take when nothing takes it back
+            if piece_type == PieceType::KING {
+                let remaining = occupied ^ bit;
+                let opponent = PlayerColor::opposite(side);
+                if self.get_attackers_bitboard(target, remaining)
+                    & remaining
+                    & self.color_bitboards[opponent.index()]
+                    != 0
+                {
+                    break;
+                }
+            }
+            depth += 1;
+            gains[depth] = Engine::get_piece_type_worth(piece_on_target) - gains[depth - 1];
+            piece_on_target = piece_type;
+            occupied ^= bit;
+            side = PlayerColor::opposite(side);
+        }
+        // going backwards, each side either takes or stops when taking would lose material
+        while depth > 0 {
+            gains[depth - 1] = -(-gains[depth - 1]).max(gains[depth]);
+            depth -= 1;
+        }
+        gains[0]
+    }
+}
@@ -0,0 +1,1256 @@
+use crate::bitboard::*;
+use crate::game_types::*;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::ops::Neg;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// Probing of Syzygy tablebases, following the layout of the files as Stockfish and Fathom read them.
+// A table holds one value per position of its material, the positions are numbered by where the
+// pieces stand after mirroring them into a canonical corner, and the values are compressed with
+// Huffman codes of pairs of symbols in blocks that can be decompressed on their own.
+
+// where the engine looks for its tables, it plays without them if there's no such directory
+pub const TABLEBASE_PATH: &str = "./res/syzygy";
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+const WDL_SUFFIX: &str = ".rtbw";
+const DTZ_SUFFIX: &str = ".rtbz";
+const MAX_PIECES: usize = 7;
+/** The rank of a root move that wins within the fifty-move rule, well above any DTZ. */
+pub const MAX_RANK: i32 = 1 << 18;
+// the order of the pieces in a table's name, like "KQRvKR"
+const NAME_ORDER: [(PieceType, char); 6] = [
+    (PieceType::KING, 'K'),
+    (PieceType::QUEEN, 'Q'),
+    (PieceType::ROOK, 'R'),
+    (PieceType::BISHOP, 'B'),
+    (PieceType::KNIGHT, 'N'),
+    (PieceType::PAWN, 'P'),
+];
+// the first byte of a table
+const TABLE_SPLIT: u8 = 1;
+const TABLE_HAS_PAWNS: u8 = 2;
+// the flags of the values for one side and file
+const FLAG_SIDE_TO_MOVE: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+/** The result of a position with perfect play, for the side to move. Cursed wins and blessed losses
+are wins and losses that take too long, the fifty-move rule makes them draws. */
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+/** A legal move at the root with what the tables say about it. */
+#[derive(Clone, Copy)]
+pub struct RootMove {
+    pub mv: ChessMove,
+    pub wdl: Wdl,
+    // plies until the next capture or pawn move that keeps the result, negative when losing
+    pub dtz: i32,
+    // higher is better: MAX_RANK for a win within the fifty-move rule, -MAX_RANK for a loss within it
+    pub rank: i32,
+}
+#[derive(Debug)]
+pub enum TablebaseError {
+    Io(std::io::Error),
+    InvalidFormat,
+    Truncated,
+}
+/** The Syzygy tables found in a directory, each one is read the first time it's needed. A position
+the tables don't cover, or whose table is missing or broken, is just not probed. */
+pub struct Tablebases {
+    directory: PathBuf,
+    wdl_names: HashSet<String>,
+    dtz_names: HashSet<String>,
+    max_pieces: usize,
+    encoding: Encoding,
+    // by name, None when the file couldn't be read
+    wdl_tables: Mutex<HashMap<String, Option<Arc<Table>>>>,
+    dtz_tables: Mutex<HashMap<String, Option<Arc<Table>>>>,
+}
+// the lookup tables for numbering positions
+struct Encoding {
+    // a2 to h7 numbered so that the highest is the leading pawn: the one nearest to the a or h file,
+    // and of those the one on the lowest rank
+    map_pawns: [usize; 64],
+    // the squares below the a1-h8 diagonal, 0 to 27
+    map_b1h1h7: [usize; 64],
+    // the squares in the a1-d1-d4 triangle, 0 to 9 with the ones on the diagonal last
+    map_a1d1d4: [usize; 64],
+    // the 462 legal placements of two kings with the first one in the a1-d1-d4 triangle
+    map_kk: [[usize; 64]; 10],
+    binomial: [[u64; 64]; MAX_PIECES],
+    lead_pawn_index: [[u64; 64]; MAX_PIECES],
+    // by the number of leading pawns and the file of the first one
+    lead_pawns_size: [[u64; 4]; MAX_PIECES],
+}
+struct Table {
+    data: Vec<u8>,
+    is_dtz: bool,
+    // the same pieces on both sides, so only white to move is stored
+    is_symmetric: bool,
+    has_pawns: bool,
+    // at least one piece besides the kings that's the only one of its kind on its side
+    has_unique_pieces: bool,
+    piece_count: usize,
+    // the pawns of the leading color first, that's the side with fewer pawns
+    pawn_count: [usize; 2],
+    // by side to move, then by the file of the leading pawn
+    pairs: Vec<Vec<PairsData>>,
+    // DTZ only, the values stored are indices into this map
+    map: usize,
+}
+// the compressed values for one side to move and file, the offsets are into Table::data
+#[derive(Clone, Default)]
+struct PairsData {
+    flags: u8,
+    block_size: usize,
+    // the sparse index has an entry for about every span values
+    span: u64,
+    block_count: usize,
+    min_symbol_length: usize,
+    lowest_symbol: usize,
+    btree: usize,
+    block_length: usize,
+    block_length_size: usize,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    blocks: usize,
+    // the lowest symbol of each length, padded to 64 bits
+    base64: Vec<u64>,
+    // how many values, less one, a symbol stands for
+    symbol_length: Vec<u32>,
+    pieces: [u8; MAX_PIECES],
+    group_index: [u64; MAX_PIECES + 1],
+    group_length: [usize; MAX_PIECES + 1],
+    map_index: [u16; 4],
+}
+enum TableValue {
+    Value(i32),
+    // DTZ tables only store one side to move, this isn't it
+    OtherSideToMove,
+}
+
+impl Neg for Wdl {
+    type Output = Wdl;
+    fn neg(self) -> Wdl {
+        match self {
+            Wdl::Loss => Wdl::Win,
+            Wdl::BlessedLoss => Wdl::CursedWin,
+            Wdl::Draw => Wdl::Draw,
+            Wdl::CursedWin => Wdl::BlessedLoss,
+            Wdl::Win => Wdl::Loss,
+        }
+    }
+}
+impl Wdl {
+    fn from_value(value: i32) -> Wdl {
+        match value {
+            -2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            1 => Wdl::CursedWin,
+            2 => Wdl::Win,
+            _ => Wdl::Draw,
+        }
+    }
+    fn signum(self) -> i32 {
+        match self {
+            Wdl::Loss | Wdl::BlessedLoss => -1,
+            Wdl::Draw => 0,
+            Wdl::CursedWin | Wdl::Win => 1,
+        }
+    }
+}
+impl fmt::Display for TablebaseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TablebaseError::Io(e) => write!(f, "{}", e),
+            TablebaseError::InvalidFormat => write!(f, "not a valid Syzygy table"),
+            TablebaseError::Truncated => write!(f, "the file ends too early"),
+        }
+    }
+}
+impl std::error::Error for TablebaseError {}
+impl From<std::io::Error> for TablebaseError {
+    fn from(e: std::io::Error) -> TablebaseError {
+        TablebaseError::Io(e)
+    }
+}
+
+impl Tablebases {
+    /** The tables in the directory. A directory that doesn't exist has no tables, and nothing is ever
+    probed. */
+    pub fn new(directory: &str) -> Tablebases {
+        let mut wdl_names = HashSet::new();
+        let mut dtz_names = HashSet::new();
+        if let Ok(entries) = fs::read_dir(directory) {
+            for entry in entries.flatten() {
+                let file_name = entry.file_name().to_string_lossy().into_owned();
+                if let Some(name) = file_name.strip_suffix(WDL_SUFFIX) {
+                    wdl_names.insert(name.to_owned());
+                } else if let Some(name) = file_name.strip_suffix(DTZ_SUFFIX) {
+                    dtz_names.insert(name.to_owned());
+                }
+            }
+        }
+        let max_pieces = wdl_names
+            .iter()
+            .map(|name| name.len() - 1)
+            .filter(|pieces| *pieces <= MAX_PIECES)
+            .max()
+            .unwrap_or(0);
+        Tablebases {
+            directory: PathBuf::from(directory),
+            wdl_names,
+            dtz_names,
+            max_pieces,
+            encoding: Encoding::new(),
+            wdl_tables: Mutex::new(HashMap::new()),
+            dtz_tables: Mutex::new(HashMap::new()),
+        }
+    }
+    /** The most pieces, kings included, of any WDL table found, 0 without tables. */
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+    /** Whether the position may be in the tables, they don't know about castling. */
+    pub fn can_probe(&self, board: &BoardState) -> bool {
+        let castling_rules = board.castling_rules;
+        board.get_occupied().count_ones() as usize <= self.max_pieces
+            && !castling_rules.white_can_still_castle_k
+            && !castling_rules.white_can_still_castle_q
+            && !castling_rules.black_can_still_castle_k
+            && !castling_rules.black_can_still_castle_q
+    }
+    /** The result of the position for the side to move, if the tables have it. */
+    pub fn probe_wdl(&self, board: &BoardState) -> Option<Wdl> {
+        if !self.can_probe(board) {
+            return None;
+        }
+        self.search(&mut board.clone(), false).map(|(wdl, _)| wdl)
+    }
+    /** The plies until the next capture or pawn move with perfect play, positive when the side to move
+    wins and negative when it loses, 0 for draws. Cursed wins and blessed losses are 100 further out,
+    so they're past the fifty-move rule. */
+    pub fn probe_dtz(&self, board: &BoardState) -> Option<i32> {
+        if !self.can_probe(board) {
+            return None;
+        }
+        self.dtz(&mut board.clone())
+    }
+    /** Every legal move of the position ranked by the tables, the best first: the quickest safe win,
+    or else a draw, or else the slowest loss. */
+    pub fn probe_root(&self, board: &BoardState) -> Option<Vec<RootMove>> {
+        if !self.can_probe(board) {
+            return None;
+        }
+        let mut board = board.clone();
+        let halfmove_clock = board.halfmove_clock as i32;
+        let mut root_moves = Vec::new();
+        for mv in board.get_all_legal_moves() {
+            let undo_info = board.make_move(&mv);
+            let probe = if board.halfmove_clock == 0 {
+                // after a capture or pawn move the count starts over
+                self.search(&mut board, false)
+                    .map(|(wdl, _)| (-wdl, dtz_before_zeroing(-wdl)))
+            } else {
+                self.search(&mut board, false).and_then(|(wdl, _)| {
+                    let dtz = -self.dtz(&mut board)?;
+                    Some((-wdl, dtz + dtz.signum()))
+                })
+            };
+            let is_mate = board.evaluate_is_checkmate();
+            board.unmake_move(&mv, undo_info);
+            let (wdl, mut dtz) = probe?;
+            if is_mate {
+                dtz = 1;
+            }
+            let rank = if dtz > 0 {
+                if dtz + halfmove_clock <= 99 {
+                    MAX_RANK
+                } else {
+                    MAX_RANK - (dtz + halfmove_clock)
+                }
+            } else if dtz < 0 {
+                if -dtz * 2 + halfmove_clock < 100 {
+                    -MAX_RANK
+                } else {
+                    -MAX_RANK + (-dtz + halfmove_clock)
+                }
+            } else {
+                0
+            };
+            root_moves.push(RootMove { mv, wdl, dtz, rank });
+        }
+        // among equal ranks, the shortest way to a win and the longest to a loss
+        root_moves.sort_by_key(|root_move| (-root_move.rank, root_move.dtz));
+        Some(root_moves)
+    }
+    // Tables leave out the values of positions where a capture wins, and may store a loss when a
+    // capture draws, whatever compresses better. So the captures have to be looked at first, and the
+    // table only decides when none of them is at least as good. With zeroing moves also checked, pawn
+    // moves are looked at too, as DTZ tables don't store values where one of those wins. The second
+    // value tells whether the best move is such a capture or pawn move.
+    fn search(&self, board: &mut BoardState, check_zeroing_moves: bool) -> Option<(Wdl, bool)> {
+        let moves = board.get_all_legal_moves();
+        let mut best = Wdl::Loss;
+        let mut searched = 0;
+        for mv in moves.iter() {
+            if !board.is_capture(mv)
+                && (!check_zeroing_moves || mv.from.piece_type != PieceType::PAWN)
+            {
+                continue;
+            }
+            searched += 1;
+            let undo_info = board.make_move(mv);
+            let result = self.search(board, false);
+            board.unmake_move(mv, undo_info);
+            let wdl = -result?.0;
+            if wdl > best {
+                best = wdl;
+                if wdl == Wdl::Win {
+                    return Some((wdl, true));
+                }
+            }
+        }
+        // with every move searched the table isn't needed, it may even be wrong, like after a
+        // double pawn push that allows taking en passant
+        let has_no_other_moves = searched > 0 && searched == moves.len();
+        let wdl = if has_no_other_moves {
+            best
+        } else {
+            match self.probe_table(board, false, Wdl::Draw)? {
+                TableValue::Value(value) => Wdl::from_value(value),
+                TableValue::OtherSideToMove => return None,
+            }
+        };
+        if best >= wdl {
+            return Some((best, best > Wdl::Draw || has_no_other_moves));
+        }
+        Some((wdl, false))
+    }
+    fn dtz(&self, board: &mut BoardState) -> Option<i32> {
+        let (wdl, is_zeroing_best) = self.search(board, true)?;
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+        if is_zeroing_best {
+            return Some(dtz_before_zeroing(wdl));
+        }
+        match self.probe_table(board, true, wdl)? {
+            TableValue::Value(dtz) => {
+                let fifty_moves = if wdl == Wdl::CursedWin || wdl == Wdl::BlessedLoss {
+                    100
+                } else {
+                    0
+                };
+                Some((dtz + fifty_moves) * wdl.signum())
+            }
+            // the table has the other side, so look one move ahead for the best of its values
+            TableValue::OtherSideToMove => {
+                let mut min_dtz = i32::MAX;
+                for mv in board.get_all_legal_moves() {
+                    let is_zeroing = board.is_capture(&mv) || mv.from.piece_type == PieceType::PAWN;
+                    let undo_info = board.make_move(&mv);
+                    let dtz = if is_zeroing {
+                        self.search(board, false)
+                            .map(|(wdl, _)| -dtz_before_zeroing(wdl))
+                    } else {
+                        self.dtz(board).map(|dtz| -dtz)
+                    };
+                    let is_mate = dtz == Some(1) && board.evaluate_is_checkmate();
+                    board.unmake_move(&mv, undo_info);
+                    let mut dtz = dtz?;
+                    if is_mate {
+                        min_dtz = 1;
+                    }
+                    if !is_zeroing {
+                        dtz += dtz.signum();
+                    }
+                    if dtz < min_dtz && dtz.signum() == wdl.signum() {
+                        min_dtz = dtz;
+                    }
+                }
+                // no legal moves is a mate
+                Some(if min_dtz == i32::MAX { -1 } else { min_dtz })
+            }
+        }
+    }
+    fn get_table(&self, name: &str, is_dtz: bool) -> Option<Arc<Table>> {
+        let (names, tables, suffix) = if is_dtz {
+            (&self.dtz_names, &self.dtz_tables, DTZ_SUFFIX)
+        } else {
+            (&self.wdl_names, &self.wdl_tables, WDL_SUFFIX)
+        };
+        if !names.contains(name) {
+            return None;
+        }
+        let mut tables = tables.lock().unwrap();
+        tables
+            .entry(name.to_owned())
+            .or_insert_with(|| {
+                let path = self.directory.join(format!("{}{}", name, suffix));
+                match Table::load(&path, name, is_dtz, &self.encoding) {
+                    Ok(table) => Some(Arc::new(table)),
+                    Err(e) => {
+                        eprintln!("Can't read the tablebase '{}': {}", path.display(), e);
+                        None
+                    }
+                }
+            })
+            .clone()
+    }
+    fn probe_table(&self, board: &BoardState, is_dtz: bool, wdl: Wdl) -> Option<TableValue> {
+        // a lone pair of kings has no table
+        if board.get_occupied().count_ones() == 2 {
+            return Some(TableValue::Value(0));
+        }
+        let white_first = get_material_name(board, PlayerColor::WHITE);
+        let black_first = get_material_name(board, PlayerColor::BLACK);
+        // the tables are named with their white first, the stronger side
+        let (table, is_black_stronger) = match self.get_table(&white_first, is_dtz) {
+            Some(table) => (table, false),
+            None => (
+                self.get_table(&black_first, is_dtz)?,
+                white_first != black_first,
+            ),
+        };
+        table.probe(board, is_black_stronger, wdl, &self.encoding)
+    }
+}
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Loss => -1,
+        Wdl::BlessedLoss => -101,
+        Wdl::Draw => 0,
+        Wdl::CursedWin => 101,
+        Wdl::Win => 1,
+    }
+}
+// like "KRPvKR", the given color's pieces first
+fn get_material_name(board: &BoardState, first: PlayerColor) -> String {
+    let mut name = String::new();
+    for color in [first, PlayerColor::opposite(first)].iter() {
+        if !name.is_empty() {
+            name.push('v');
+        }
+        for (piece_type, letter) in NAME_ORDER.iter() {
+            for _ in 0..board.get_bitboard(*piece_type, *color).count_ones() {
+                name.push(*letter);
+            }
+        }
+    }
+    name
+}
+// as the tables number them, 1 to 6 for white's pawn to king, black's the same plus 8
+fn get_piece_code(piece_type: PieceType, color: PlayerColor) -> u8 {
+    let code = match piece_type {
+        PieceType::PAWN => 1,
+        PieceType::KNIGHT => 2,
+        PieceType::BISHOP => 3,
+        PieceType::ROOK => 4,
+        PieceType::QUEEN => 5,
+        PieceType::KING => 6,
+    };
+    match color {
+        PlayerColor::WHITE => code,
+        PlayerColor::BLACK => code + 8,
+    }
+}
+fn get_piece_code_at(board: &BoardState, square: usize) -> u8 {
+    for (piece_type, _) in NAME_ORDER.iter() {
+        for color in [PlayerColor::WHITE, PlayerColor::BLACK].iter() {
+            if board.get_bitboard(*piece_type, *color) & (1 << square) != 0 {
+                return get_piece_code(*piece_type, *color);
+            }
+        }
+    }
+    0
+}
+fn file_of(square: usize) -> usize {
+    square % 8
+}
+fn rank_of(square: usize) -> usize {
+    square / 8
+}
+// positive above the a1-h8 diagonal, 0 on it
+fn off_diagonal(square: usize) -> i32 {
+    rank_of(square) as i32 - file_of(square) as i32
+}
+
+impl Encoding {
+    fn new() -> Encoding {
+        let mut encoding = Encoding {
+            map_pawns: [0; 64],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; MAX_PIECES],
+            lead_pawn_index: [[0; 64]; MAX_PIECES],
+            lead_pawns_size: [[0; 4]; MAX_PIECES],
+        };
+        let mut code = 0;
+        for square in 0..64 {
+            if off_diagonal(square) < 0 {
+                encoding.map_b1h1h7[square] = code;
+                code += 1;
+            }
+        }
+        let mut diagonal = Vec::new();
+        code = 0;
+        for rank in 0..4 {
+            for file in 0..4 {
+                let square = file + rank * 8;
+                if off_diagonal(square) < 0 {
+                    encoding.map_a1d1d4[square] = code;
+                    code += 1;
+                } else if off_diagonal(square) == 0 {
+                    diagonal.push(square);
+                }
+            }
+        }
+        for square in diagonal {
+            encoding.map_a1d1d4[square] = code;
+            code += 1;
+        }
+        // with the first king on the diagonal the second is kept on or below it
+        let mut both_on_diagonal = Vec::new();
+        code = 0;
+        for index in 0..10 {
+            for (first, mapped) in encoding.map_a1d1d4.iter().enumerate().take(28) {
+                // b1 is mapped to 0 as well as the squares outside the triangle
+                if *mapped != index || (index == 0 && first != 1) {
+                    continue;
+                }
+                for second in 0..64 {
+                    if first == second || KING_ATTACKS[first] & (1 << second) != 0 {
+                        continue;
+                    }
+                    if off_diagonal(first) == 0 && off_diagonal(second) > 0 {
+                        continue;
+                    }
+                    if off_diagonal(first) == 0 && off_diagonal(second) == 0 {
+                        both_on_diagonal.push((index, second));
+                    } else {
+                        encoding.map_kk[index][second] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (index, second) in both_on_diagonal {
+            encoding.map_kk[index][second] = code;
+            code += 1;
+        }
+        encoding.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..MAX_PIECES.min(n + 1) {
+                encoding.binomial[k][n] = if k > 0 {
+                    encoding.binomial[k - 1][n - 1]
+                } else {
+                    0
+                } + if k < n {
+                    encoding.binomial[k][n - 1]
+                } else {
+                    0
+                };
+            }
+        }
+        let mut available_squares = 48;
+        for lead_pawn_count in 1..MAX_PIECES - 1 {
+            for file in 0..4 {
+                let mut index = 0;
+                for rank in 1..7 {
+                    let square = file + rank * 8;
+                    if lead_pawn_count == 1 {
+                        encoding.map_pawns[square] = available_squares - 1;
+                        encoding.map_pawns[square ^ 7] = available_squares - 2;
+                        available_squares -= 2;
+                    }
+                    encoding.lead_pawn_index[lead_pawn_count][square] = index;
+                    index += encoding.binomial[lead_pawn_count - 1][encoding.map_pawns[square]];
+                }
+                encoding.lead_pawns_size[lead_pawn_count][file] = index;
+            }
+        }
+        encoding
+    }
+}
+
+impl Table {
+    fn load(
+        path: &Path,
+        name: &str,
+        is_dtz: bool,
+        encoding: &Encoding,
+    ) -> Result<Table, TablebaseError> {
+        let data = fs::read(path)?;
+        let magic = if is_dtz { DTZ_MAGIC } else { WDL_MAGIC };
+        if data.len() < 5 || data[..4] != magic {
+            return Err(TablebaseError::InvalidFormat);
+        }
+        let mut sides = name.split('v');
+        let white = sides.next().unwrap_or_default();
+        let black = sides.next().unwrap_or_default();
+        let count = |side: &str, letter: char| side.chars().filter(|c| *c == letter).count();
+        let has_unique_pieces = [white, black].iter().any(|side| {
+            NAME_ORDER[1..]
+                .iter()
+                .any(|(_, letter)| count(side, *letter) == 1)
+        });
+        let white_pawns = count(white, 'P');
+        let black_pawns = count(black, 'P');
+        let is_white_leading = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        let mut table = Table {
+            data: Vec::new(),
+            is_dtz,
+            is_symmetric: white == black,
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces,
+            piece_count: white.len() + black.len(),
+            pawn_count: if is_white_leading {
+                [white_pawns, black_pawns]
+            } else {
+                [black_pawns, white_pawns]
+            },
+            pairs: Vec::new(),
+            map: 0,
+        };
+        if table.piece_count > MAX_PIECES {
+            return Err(TablebaseError::InvalidFormat);
+        }
+        table.read_layout(&data, encoding)?;
+        table.data = data;
+        Ok(table)
+    }
+    fn file_count(&self) -> usize {
+        if self.has_pawns {
+            4
+        } else {
+            1
+        }
+    }
+    fn side_count(&self) -> usize {
+        if self.is_dtz || self.is_symmetric {
+            1
+        } else {
+            2
+        }
+    }
+    // works out where everything is in the file, the layout is mostly implied by what came before
+    fn read_layout(&mut self, data: &[u8], encoding: &Encoding) -> Result<(), TablebaseError> {
+        let flags = read_u8(data, 4)?;
+        let is_split = flags & TABLE_SPLIT != 0;
+        if (flags & TABLE_HAS_PAWNS != 0) != self.has_pawns
+            || (!self.is_dtz && is_split == self.is_symmetric)
+        {
+            return Err(TablebaseError::InvalidFormat);
+        }
+        let mut offset = 5;
+        let sides = self.side_count();
+        let files = self.file_count();
+        let has_pawns_on_both_sides = self.has_pawns && self.pawn_count[1] > 0;
+        self.pairs = vec![vec![PairsData::default(); files]; sides];
+        for file in 0..files {
+            let first = read_u8(data, offset)?;
+            let second = if has_pawns_on_both_sides {
+                read_u8(data, offset + 1)?
+            } else {
+                0xff
+            };
+            let order = [[first & 0xf, second & 0xf], [first >> 4, second >> 4]];
+            offset += 1 + has_pawns_on_both_sides as usize;
+            for k in 0..self.piece_count {
+                let byte = read_u8(data, offset)?;
+                for side in 0..sides {
+                    self.pairs[side][file].pieces[k] =
+                        if side == 1 { byte >> 4 } else { byte & 0xf };
+                }
+                offset += 1;
+            }
+            for (side, order) in order.iter().enumerate().take(sides) {
+                self.set_groups(side, file, *order, encoding);
+            }
+        }
+        offset += offset & 1;
+        for file in 0..files {
+            for side in 0..sides {
+                offset = self.pairs[side][file].read_sizes(data, offset)?;
+            }
+        }
+        if self.is_dtz {
+            offset = self.read_dtz_map(data, offset)?;
+        }
+        for file in 0..files {
+            for side in 0..sides {
+                let pairs = &mut self.pairs[side][file];
+                pairs.sparse_index = offset;
+                offset += pairs.sparse_index_size * 6;
+            }
+        }
+        for file in 0..files {
+            for side in 0..sides {
+                let pairs = &mut self.pairs[side][file];
+                pairs.block_length = offset;
+                offset += pairs.block_length_size * 2;
+            }
+        }
+        for file in 0..files {
+            for side in 0..sides {
+                let pairs = &mut self.pairs[side][file];
+                offset = (offset + 0x3f) & !0x3f;
+                pairs.blocks = offset;
+                offset += pairs.block_count * pairs.block_size;
+            }
+        }
+        if offset > data.len() {
+            return Err(TablebaseError::Truncated);
+        }
+        Ok(())
+    }
+    // The pieces are numbered in groups: the leading pawns or the first pieces, then the rest of the
+    // pawns, then the rest of the pieces, identical pieces together. The index of a position combines
+    // the groups' indices, each one scaled by the number of placements of the groups after it.
+    fn set_groups(&mut self, side: usize, file: usize, order: [u8; 2], encoding: &Encoding) {
+        let has_pawns_on_both_sides = self.has_pawns && self.pawn_count[1] > 0;
+        let pairs = &mut self.pairs[side][file];
+        let mut n = 0;
+        let mut first_length: i32 = if self.has_pawns {
+            0
+        } else if self.has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        pairs.group_length[0] = 1;
+        for i in 1..self.piece_count {
+            first_length -= 1;
+            if first_length > 0 || pairs.pieces[i] == pairs.pieces[i - 1] {
+                pairs.group_length[n] += 1;
+            } else {
+                n += 1;
+                pairs.group_length[n] = 1;
+            }
+        }
+        n += 1;
+        pairs.group_length[n] = 0;
+        let mut next = if has_pawns_on_both_sides { 2 } else { 1 };
+        let mut free_squares = 64
+            - pairs.group_length[0]
+            - if has_pawns_on_both_sides {
+                pairs.group_length[1]
+            } else {
+                0
+            };
+        let mut index: u64 = 1;
+        let mut k = 0;
+        while next < n || k == order[0] || k == order[1] {
+            if k == order[0] {
+                pairs.group_index[0] = index;
+                index *= if self.has_pawns {
+                    encoding.lead_pawns_size[pairs.group_length[0]][file]
+                } else if self.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] {
+                pairs.group_index[1] = index;
+                index *= encoding.binomial[pairs.group_length[1]][48 - pairs.group_length[0]];
+            } else {
+                pairs.group_index[next] = index;
+                index *= encoding.binomial[pairs.group_length[next]][free_squares];
+                free_squares -= pairs.group_length[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        pairs.group_index[n] = index;
+    }
+    fn read_dtz_map(&mut self, data: &[u8], mut offset: usize) -> Result<usize, TablebaseError> {
+        self.map = offset;
+        for file in 0..self.file_count() {
+            let pairs = &mut self.pairs[0][file];
+            if pairs.flags & FLAG_MAPPED == 0 {
+                continue;
+            }
+            // four maps, for wins, losses, cursed wins and blessed losses, each with its length first
+            if pairs.flags & FLAG_WIDE != 0 {
+                offset += offset & 1;
+                for i in 0..4 {
+                    pairs.map_index[i] = ((offset - self.map) / 2 + 1) as u16;
+                    offset += 2 * read_u16_le(data, offset)? as usize + 2;
+                }
+            } else {
+                for i in 0..4 {
+                    pairs.map_index[i] = (offset - self.map + 1) as u16;
+                    offset += read_u8(data, offset)? as usize + 1;
+                }
+            }
+        }
+        Ok(offset + (offset & 1))
+    }
+    fn probe(
+        &self,
+        board: &BoardState,
+        is_black_stronger: bool,
+        wdl: Wdl,
+        encoding: &Encoding,
+    ) -> Option<TableValue> {
+        // a symmetric table only has white to move, and a table with black stronger only has it
+        // as white, either way the colors are swapped and the board turned around
+        let is_flipped =
+            is_black_stronger || (self.is_symmetric && board.player_to_move == PlayerColor::BLACK);
+        let flip_color = if is_flipped { 8 } else { 0 };
+        let flip_squares = if is_flipped { 56 } else { 0 };
+        let side_to_move = is_flipped as usize ^ board.player_to_move.index();
+        let mut squares = [0usize; MAX_PIECES];
+        let mut pieces = [0u8; MAX_PIECES];
+        let mut size = 0;
+        let mut lead_pawns = 0;
+        let mut lead_pawn_count = 0;
+        let mut file = 0;
+        if self.has_pawns {
+            let lead_pawn_code = self.pairs[0][0].pieces[0] ^ flip_color;
+            let lead_color = if lead_pawn_code & 8 == 0 {
+                PlayerColor::WHITE
+            } else {
+                PlayerColor::BLACK
+            };
+            lead_pawns = board.get_bitboard(PieceType::PAWN, lead_color);
+            for square in crate::bitboard::squares(lead_pawns) {
+                squares[size] = square ^ flip_squares;
+                size += 1;
+            }
+            lead_pawn_count = size;
+            let mut leading = 0;
+            for i in 1..lead_pawn_count {
+                if encoding.map_pawns[squares[i]] > encoding.map_pawns[squares[leading]] {
+                    leading = i;
+                }
+            }
+            squares.swap(0, leading);
+            file = file_of(squares[0]).min(file_of(squares[0] ^ 7));
+        }
+        if self.is_dtz {
+            let flags = self.pairs[0][file].flags;
+            let has_both_sides = self.is_symmetric && !self.has_pawns;
+            if (flags & FLAG_SIDE_TO_MOVE) as usize != side_to_move && !has_both_sides {
+                return Some(TableValue::OtherSideToMove);
+            }
+        }
+        for square in crate::bitboard::squares(board.get_occupied() ^ lead_pawns) {
+            squares[size] = square ^ flip_squares;
+            pieces[size] = get_piece_code_at(board, square) ^ flip_color;
+            size += 1;
+        }
+        if size != self.piece_count {
+            return None;
+        }
+        let pairs = &self.pairs[side_to_move % self.side_count()][file];
+        // into the order the table has its pieces in
+        for i in lead_pawn_count..size - 1 {
+            for j in i + 1..size {
+                if pairs.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+        // the leading piece goes onto the a to d files
+        if file_of(squares[0]) > 3 {
+            for square in squares[..size].iter_mut() {
+                *square ^= 7;
+            }
+        }
+        let mut index;
+        if self.has_pawns {
+            index = encoding.lead_pawn_index[lead_pawn_count][squares[0]];
+            squares[1..lead_pawn_count].sort_by_key(|square| encoding.map_pawns[*square]);
+            for (i, square) in squares.iter().enumerate().take(lead_pawn_count).skip(1) {
+                index += encoding.binomial[i][encoding.map_pawns[*square]];
+            }
+        } else {
+            // and without pawns onto the lower half, and below the a1-h8 diagonal
+            if rank_of(squares[0]) > 3 {
+                for square in squares[..size].iter_mut() {
+                    *square ^= 56;
+                }
+            }
+            for i in 0..pairs.group_length[0] {
+                if off_diagonal(squares[i]) == 0 {
+                    continue;
+                }
+                if off_diagonal(squares[i]) > 0 {
+                    for square in squares[i..size].iter_mut() {
+                        *square = ((*square >> 3) | (*square << 3)) & 63;
+                    }
+                }
+                break;
+            }
+            index = if self.has_unique_pieces {
+                get_unique_pieces_index(&squares, encoding)
+            } else {
+                encoding.map_kk[encoding.map_a1d1d4[squares[0]]][squares[1]] as u64
+            };
+        }
+        index *= pairs.group_index[0];
+        let mut group_start = pairs.group_length[0];
+        // the rest of the pawns can't be on the first or last rank
+        let mut has_remaining_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = 1;
+        while pairs.group_length[next] != 0 {
+            let group_end = group_start + pairs.group_length[next];
+            squares[group_start..group_end].sort_unstable();
+            let mut group_index = 0;
+            for i in group_start..group_end {
+                // squares of earlier groups are taken, the ones below don't count
+                let taken = squares[..group_start]
+                    .iter()
+                    .filter(|square| squares[i] > **square)
+                    .count();
+                let square = squares[i] - taken - if has_remaining_pawns { 8 } else { 0 };
+                group_index += encoding.binomial[i - group_start + 1][square];
+            }
+            has_remaining_pawns = false;
+            index += group_index * pairs.group_index[next];
+            group_start = group_end;
+            next += 1;
+        }
+        let value = pairs.decompress(&self.data, index)?;
+        Some(TableValue::Value(self.map_value(file, value, wdl)?))
+    }
+    fn map_value(&self, file: usize, value: i32, wdl: Wdl) -> Option<i32> {
+        if !self.is_dtz {
+            return Some(value - 2);
+        }
+        let pairs = &self.pairs[0][file];
+        let mut value = value;
+        if pairs.flags & FLAG_MAPPED != 0 {
+            let map = match wdl {
+                Wdl::Win | Wdl::Draw => 0,
+                Wdl::Loss => 1,
+                Wdl::CursedWin => 2,
+                Wdl::BlessedLoss => 3,
+            };
+            let index = pairs.map_index[map] as usize + value as usize;
+            value = if pairs.flags & FLAG_WIDE != 0 {
+                read_u16_le(&self.data, self.map + 2 * index).ok()? as i32
+            } else {
+                read_u8(&self.data, self.map + index).ok()? as i32
+            };
+        }
+        // stored in moves unless the flags say plies
+        let is_in_moves = match wdl {
+            Wdl::Win => pairs.flags & FLAG_WIN_PLIES == 0,
+            Wdl::Loss => pairs.flags & FLAG_LOSS_PLIES == 0,
+            Wdl::CursedWin | Wdl::BlessedLoss => true,
+            Wdl::Draw => false,
+        };
+        if is_in_moves {
+            value *= 2;
+        }
+        Some(value + 1)
+    }
+}
+// three pieces that are the only ones of their kind, the first on or below the a1-h8 diagonal in the
+// a1-d1-d4 triangle: 31332 placements
+fn get_unique_pieces_index(squares: &[usize], encoding: &Encoding) -> u64 {
+    let (first, second, third) = (squares[0], squares[1], squares[2]);
+    let adjust_second = (second > first) as usize;
+    let adjust_third = (third > first) as usize + (third > second) as usize;
+    let index = if off_diagonal(first) != 0 {
+        (encoding.map_a1d1d4[first] * 63 + second - adjust_second) * 62 + third - adjust_third
+    } else if off_diagonal(second) != 0 {
+        (6 * 63 + rank_of(first) * 28 + encoding.map_b1h1h7[second]) * 62 + third - adjust_third
+    } else if off_diagonal(third) != 0 {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + rank_of(first) * 7 * 28
+            + (rank_of(second) - adjust_second) * 28
+            + encoding.map_b1h1h7[third]
+    } else {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + 4 * 7 * 28
+            + rank_of(first) * 7 * 6
+            + (rank_of(second) - adjust_second) * 6
+            + (rank_of(third) - adjust_third)
+    };
+    index as u64
+}
+
+impl PairsData {
+    fn read_sizes(&mut self, data: &[u8], mut offset: usize) -> Result<usize, TablebaseError> {
+        self.flags = read_u8(data, offset)?;
+        offset += 1;
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            // the only value is where the symbol length would be
+            self.min_symbol_length = read_u8(data, offset)? as usize;
+            return Ok(offset + 1);
+        }
+        let group_count = self.group_length.iter().position(|length| *length == 0);
+        let table_size = self.group_index[group_count.unwrap_or(MAX_PIECES)];
+        self.block_size = 1 << read_u8(data, offset)?;
+        self.span = 1 << read_u8(data, offset + 1)?;
+        self.sparse_index_size = table_size.div_ceil(self.span) as usize;
+        let padding = read_u8(data, offset + 2)? as usize;
+        self.block_count = read_u32_le(data, offset + 3)? as usize;
+        self.block_length_size = self.block_count + padding;
+        let max_symbol_length = read_u8(data, offset + 7)? as usize;
+        self.min_symbol_length = read_u8(data, offset + 8)? as usize;
+        offset += 9;
+        if max_symbol_length < self.min_symbol_length || self.min_symbol_length == 0 {
+            return Err(TablebaseError::InvalidFormat);
+        }
+        self.lowest_symbol = offset;
+        // canonical Huffman codes: longer codes have lower values, so walking up from the longest
+        // the lowest code of every length follows from the one below it
+        let lengths = max_symbol_length - self.min_symbol_length + 1;
+        self.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            let lowest = read_u16_le(data, self.lowest_symbol + 2 * i)? as u64;
+            let lowest_next = read_u16_le(data, self.lowest_symbol + 2 * (i + 1))? as u64;
+            self.base64[i] = (self.base64[i + 1] + lowest - lowest_next) / 2;
+        }
+        for (i, base) in self.base64.iter_mut().enumerate() {
+            *base = base
+                .checked_shl((64 - i - self.min_symbol_length) as u32)
+                .unwrap_or(0);
+        }
+        offset += lengths * 2;
+        let symbol_count = read_u16_le(data, offset)? as usize;
+        offset += 2;
+        self.btree = offset;
+        if self.btree + symbol_count * 3 > data.len() {
+            return Err(TablebaseError::Truncated);
+        }
+        self.symbol_length = vec![0; symbol_count];
+        let mut visited = vec![false; symbol_count];
+        for symbol in 0..symbol_count {
+            if !visited[symbol] {
+                self.symbol_length[symbol] = self.get_symbol_length(data, symbol, &mut visited);
+            }
+        }
+        Ok(offset + symbol_count * 3 + (symbol_count & 1))
+    }
+    // a symbol stands for its left and right symbols one after the other, down to single values
+    fn get_symbol_length(&mut self, data: &[u8], symbol: usize, visited: &mut Vec<bool>) -> u32 {
+        visited[symbol] = true;
+        let (left, right) = self.get_pair(data, symbol);
+        if right == 0xfff {
+            return 0;
+        }
+        for child in [left, right].iter() {
+            if *child < visited.len() && !visited[*child] {
+                self.symbol_length[*child] = self.get_symbol_length(data, *child, visited);
+            }
+        }
+        let length = |child: usize| self.symbol_length.get(child).copied().unwrap_or(0);
+        length(left) + length(right) + 1
+    }
+    // 12 bits each, packed in three bytes
+    fn get_pair(&self, data: &[u8], symbol: usize) -> (usize, usize) {
+        let offset = self.btree + symbol * 3;
+        let bytes = [data[offset], data[offset + 1], data[offset + 2]];
+        let left = ((bytes[1] as usize & 0xf) << 8) | bytes[0] as usize;
+        let right = ((bytes[2] as usize) << 4) | (bytes[1] as usize >> 4);
+        (left, right)
+    }
+    fn decompress(&self, data: &[u8], index: u64) -> Option<i32> {
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            return Some(self.min_symbol_length as i32);
+        }
+        // the sparse index knows the block and the offset in it of every span-th value, give or
+        // take half a span, from there it's counting blocks forward or back
+        let k = (index / self.span) as usize;
+        if k >= self.sparse_index_size {
+            return None;
+        }
+        let entry = self.sparse_index + k * 6;
+        let mut block = read_u32_le(data, entry).ok()? as usize;
+        let mut offset = read_u16_le(data, entry + 4).ok()? as i64;
+        offset += (index % self.span) as i64 - (self.span / 2) as i64;
+        let block_length = |block: usize| {
+            read_u16_le(data, self.block_length + block * 2)
+                .ok()
+                .map(i64::from)
+        };
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+        if block >= self.block_count {
+            return None;
+        }
+        // the symbols of a block are read off the front of a 64 bit buffer
+        let mut position = self.blocks + block * self.block_size;
+        let mut buffer = read_u64_be(data, position).ok()?;
+        position += 8;
+        let mut buffer_size = 64;
+        let mut symbol;
+        loop {
+            let mut length = 0;
+            while buffer < self.base64[length] {
+                length += 1;
+                if length >= self.base64.len() {
+                    return None;
+                }
+            }
+            symbol =
+                ((buffer - self.base64[length]) >> (64 - length - self.min_symbol_length)) as usize;
+            symbol += read_u16_le(data, self.lowest_symbol + 2 * length).ok()? as usize;
+            let symbol_length = *self.symbol_length.get(symbol)? as i64;
+            if offset < symbol_length + 1 {
+                break;
+            }
+            offset -= symbol_length + 1;
+            length += self.min_symbol_length;
+            buffer <<= length;
+            buffer_size -= length;
+            if buffer_size <= 32 {
+                buffer_size += 32;
+                buffer |= (read_u32_be(data, position).ok()? as u64) << (64 - buffer_size);
+                position += 4;
+            }
+        }
+        // then down the pairs the symbol stands for, to the single value at the offset
+        while self.symbol_length[symbol] != 0 {
+            let (left, right) = self.get_pair(data, symbol);
+            let left_length = *self.symbol_length.get(left)? as i64;
+            if offset < left_length + 1 {
+                symbol = left;
+            } else {
+                offset -= left_length + 1;
+                symbol = right;
+            }
+            if symbol >= self.symbol_length.len() {
+                return None;
+            }
+        }
+        Some(self.get_pair(data, symbol).0 as i32)
+    }
+}
+
+fn read_u8(data: &[u8], offset: usize) -> Result<u8, TablebaseError> {
+    data.get(offset).copied().ok_or(TablebaseError::Truncated)
+}
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N], TablebaseError> {
+    let mut bytes = [0; N];
+    bytes.copy_from_slice(
+        data.get(offset..offset + N)
+            .ok_or(TablebaseError::Truncated)?,
+    );
+    Ok(bytes)
+}
+fn read_u16_le(data: &[u8], offset: usize) -> Result<u16, TablebaseError> {
+    read_bytes(data, offset).map(u16::from_le_bytes)
+}
+fn read_u32_le(data: &[u8], offset: usize) -> Result<u32, TablebaseError> {
+    read_bytes(data, offset).map(u32::from_le_bytes)
+}
+fn read_u32_be(data: &[u8], offset: usize) -> Result<u32, TablebaseError> {
+    read_bytes(data, offset).map(u32::from_be_bytes)
+}
+fn read_u64_be(data: &[u8], offset: usize) -> Result<u64, TablebaseError> {
+    read_bytes(data, offset).map(u64::from_be_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(mut values: Vec<usize>) -> Vec<usize> {
+        values.sort_unstable();
+        values
+    }
+
+    #[test]
+    fn triangle_and_lower_half() {
+        let encoding = Encoding::new();
+        let triangle = (0..64).filter(|square| {
+            file_of(*square) < 4 && rank_of(*square) < 4 && off_diagonal(*square) <= 0
+        });
+        let codes = triangle.map(|square| encoding.map_a1d1d4[square]);
+        assert_eq!(sorted(codes.collect()), (0..10).collect::<Vec<usize>>());
+        // a1, b2, c3 and d4 last
+        for (i, square) in [0, 9, 18, 27].iter().enumerate() {
+            assert_eq!(encoding.map_a1d1d4[*square], 6 + i);
+        }
+        let below = (0..64).filter(|square| off_diagonal(*square) < 0);
+        let codes = below.map(|square| encoding.map_b1h1h7[square]);
+        assert_eq!(sorted(codes.collect()), (0..28).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn king_pairs() {
+        let encoding = Encoding::new();
+        let mut codes = Vec::new();
+        for (first, attacks) in KING_ATTACKS.iter().enumerate() {
+            if file_of(first) > 3 || rank_of(first) > 3 || off_diagonal(first) > 0 {
+                continue;
+            }
+            for second in 0..64 {
+                let is_mirrored = off_diagonal(first) == 0 && off_diagonal(second) > 0;
+                if first == second || attacks & (1 << second) != 0 || is_mirrored {
+                    continue;
+                }
+                codes.push(encoding.map_kk[encoding.map_a1d1d4[first]][second]);
+            }
+        }
+        // every placement numbered once
+        assert_eq!(sorted(codes), (0..462).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn binomials() {
+        let encoding = Encoding::new();
+        for n in 0..64 {
+            let mut expected: u64 = 1;
+            for k in 0..MAX_PIECES {
+                assert_eq!(encoding.binomial[k][n], expected, "{} choose {}", n, k);
+                expected = expected * (n as u64).saturating_sub(k as u64) / (k as u64 + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn pawns() {
+        let encoding = Encoding::new();
+        let pawn_squares = (8..56).collect::<Vec<usize>>();
+        let codes = pawn_squares
+            .iter()
+            .map(|square| encoding.map_pawns[*square]);
+        assert_eq!(sorted(codes.collect()), (0..48).collect::<Vec<usize>>());
+        // a lone pawn on the a to d files, one of six ranks
+        assert_eq!(encoding.lead_pawns_size[1], [6; 4]);
+        // two pawns whose leader is on the a to d files
+        let leading_pairs = pawn_squares
+            .iter()
+            .flat_map(|first| pawn_squares.iter().map(move |second| (*first, *second)))
+            .filter(|(first, second)| {
+                encoding.map_pawns[*first] > encoding.map_pawns[*second] && file_of(*first) < 4
+            })
+            .count();
+        assert_eq!(
+            encoding.lead_pawns_size[2].iter().sum::<u64>(),
+            leading_pairs as u64
+        );
+    }
+}
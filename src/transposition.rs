@@ -0,0 +1,233 @@
+use crate::game_types::*;
+use crate::search::is_mate_score;
+use std::mem;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+pub const DEFAULT_HASH_SIZE_MB: usize = 16;
+
+// how the stored score relates to the real one, depending on whether the search failed high or low
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+#[derive(Clone, Copy)]
+pub struct TranspositionEntry {
+    key: u64,
+    pub best_move: Option<ChessMove>,
+    pub score: i32,
+    pub depth: u32,
+    pub bound: Bound,
+    // the search the entry was written in, entries of older searches get replaced first
+    generation: u8,
+}
+/** A transposition table the threads of a search share without locking. Every entry is two words,
+the packed entry and the key xored with it, so an entry another thread was halfway through writing
+doesn't match its key and is just missed. */
+pub struct TranspositionTable {
+    entries: Vec<[AtomicU64; 2]>,
+    generation: AtomicU8,
+}
+
+// the bits of a packed entry: the move, the score, the depth, the bound, the generation, and one
+// that's set in all entries so that an empty one is all zeros
+const MOVE_BITS: u32 = 20;
+const SCORE_SHIFT: u32 = MOVE_BITS;
+const DEPTH_SHIFT: u32 = SCORE_SHIFT + 16;
+const BOUND_SHIFT: u32 = DEPTH_SHIFT + 8;
+const GENERATION_SHIFT: u32 = BOUND_SHIFT + 2;
+const FILLED_BIT: u64 = 1 << 63;
+
+impl TranspositionEntry {
+    /** The stored score, usable as is only if the bound allows a cutoff in the alpha-beta window. */
+    pub fn get_cutoff_score(&self, depth: u32, alpha: i32, beta: i32) -> Option<i32> {
+        if self.depth < depth {
+            return None;
+        }
+        match self.bound {
+            Bound::Exact => Some(self.score),
+            Bound::Lower if self.score >= beta => Some(self.score),
+            Bound::Upper if self.score <= alpha => Some(self.score),
+            _ => None,
+        }
+    }
+    fn pack(&self) -> u64 {
+        let bound = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        self.best_move.map_or(0, |mv| pack_move(&mv))
+            | (self.score as i16 as u16 as u64) << SCORE_SHIFT
+            | (self.depth.min(u8::MAX as u32) as u64) << DEPTH_SHIFT
+            | bound << BOUND_SHIFT
+            | (self.generation as u64) << GENERATION_SHIFT
+            | FILLED_BIT
+    }
+    fn unpack(key: u64, data: u64) -> TranspositionEntry {
+        TranspositionEntry {
+            key,
+            best_move: unpack_move(data),
+            score: (data >> SCORE_SHIFT) as u16 as i16 as i32,
+            depth: (data >> DEPTH_SHIFT) as u8 as u32,
+            bound: match (data >> BOUND_SHIFT) & 3 {
+                0 => Bound::Exact,
+                1 => Bound::Lower,
+                _ => Bound::Upper,
+            },
+            generation: (data >> GENERATION_SHIFT) as u8,
+        }
+    }
+}
+// squares, piece types and color, and a bit telling there's a move at all
+fn pack_move(mv: &ChessMove) -> u64 {
+    let from = (mv.from.x + mv.from.y * 8) as u64;
+    let to = (mv.to.x + mv.to.y * 8) as u64;
+    from | to << 6
+        | (mv.from.piece_type.index() as u64) << 12
+        | (mv.to.piece_type.index() as u64) << 15
+        | (mv.from.color.index() as u64) << 18
+        | 1 << 19
+}
+fn unpack_move(data: u64) -> Option<ChessMove> {
+    if data & 1 << 19 == 0 {
+        return None;
+    }
+    let color = if data >> 18 & 1 == 0 {
+        PlayerColor::WHITE
+    } else {
+        PlayerColor::BLACK
+    };
+    let piece = |square: u64, piece_type: u64| {
+        Piece::new(
+            (square % 8) as i8,
+            (square / 8) as i8,
+            PIECE_TYPES[piece_type as usize % PIECE_TYPES.len()],
+            color,
+        )
+    };
+    Some(ChessMove::new(
+        piece(data & 63, data >> 12 & 7),
+        piece(data >> 6 & 63, data >> 15 & 7),
+    ))
+}
+impl TranspositionTable {
+    /** A table taking up about size_mb megabytes, rounded down to a power of two entries. */
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let fitting_entries = (size_mb * 1024 * 1024 / mem::size_of::<[AtomicU64; 2]>()).max(1);
+        // a power of two lets the index be a mask of the key
+        let entry_count = 1 << (63 - (fitting_entries as u64).leading_zeros());
+        TranspositionTable {
+            entries: (0..entry_count)
+                .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+                .collect(),
+            generation: AtomicU8::new(0),
+        }
+    }
+    pub fn clear(&self) {
+        for entry in self.entries.iter() {
+            entry[0].store(0, Ordering::Relaxed);
+            entry[1].store(0, Ordering::Relaxed);
+        }
+        self.generation.store(0, Ordering::Relaxed);
+    }
+    /** Called at the start of every search, so that entries from earlier ones lose their priority. */
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+    fn get_generation(&self) -> u8 {
+        self.generation.load(Ordering::Relaxed)
+    }
+    fn index(&self, key: u64) -> usize {
+        (key & (self.entries.len() as u64 - 1)) as usize
+    }
+    fn load(&self, index: usize) -> Option<TranspositionEntry> {
+        let entry = &self.entries[index];
+        let data = entry[1].load(Ordering::Relaxed);
+        if data == 0 {
+            return None;
+        }
+        let key = entry[0].load(Ordering::Relaxed) ^ data;
+        Some(TranspositionEntry::unpack(key, data))
+    }
+    /** Looks the position up, the score comes back relative to the given ply. */
+    pub fn probe(&self, key: u64, ply: i32) -> Option<TranspositionEntry> {
+        match self.load(self.index(key)) {
+            Some(entry) if entry.key == key => Some(TranspositionEntry {
+                score: score_from_table(entry.score, ply),
+                ..entry
+            }),
+            _ => None,
+        }
+    }
+    /** Stores a search result. An entry of a different position is only replaced when it's from an
+    earlier search or wasn't searched deeper than this one. */
+    pub fn store(
+        &self,
+        key: u64,
+        depth: u32,
+        ply: i32,
+        score: i32,
+        bound: Bound,
+        best_move: Option<ChessMove>,
+    ) {
+        let index = self.index(key);
+        let generation = self.get_generation();
+        let mut best_move = best_move;
+        if let Some(existing) = self.load(index) {
+            if existing.key != key && existing.generation == generation && existing.depth > depth {
+                return;
+            }
+            // a fail low doesn't know of a best move, the one from an earlier search is still good
+            if existing.key == key && best_move.is_none() {
+                best_move = existing.best_move;
+            }
+        }
+        let data = TranspositionEntry {
+            key,
+            best_move,
+            score: score_to_table(score, ply),
+            depth,
+            bound,
+            generation,
+        }
+        .pack();
+        let entry = &self.entries[index];
+        entry[0].store(key ^ data, Ordering::Relaxed);
+        entry[1].store(data, Ordering::Relaxed);
+    }
+    /** How many of a thousand entries are filled in by the current search, UCI's hashfull. */
+    pub fn usage_permill(&self) -> u32 {
+        let sample = self.entries.len().min(1000);
+        let generation = self.get_generation();
+        let used = (0..sample)
+            .filter(|index| match self.load(*index) {
+                Some(entry) => entry.generation == generation,
+                None => false,
+            })
+            .count();
+        (used * 1000 / sample) as u32
+    }
+}
+
+// mate scores are stored as the distance to mate from the stored position instead of from the root,
+// the same position can be reached at different plies
+fn score_to_table(score: i32, ply: i32) -> i32 {
+    if !is_mate_score(score) {
+        score
+    } else if score > 0 {
+        score + ply
+    } else {
+        score - ply
+    }
+}
+fn score_from_table(score: i32, ply: i32) -> i32 {
+    if !is_mate_score(score) {
+        score
+    } else if score > 0 {
+        score - ply
+    } else {
+        score + ply
+    }
+}
@@ -29,6 +29,9 @@ impl MenuButton {
             on_click,
         }
     }
+    pub fn set_text_content(&mut self, content: impl Into<String>) {
+        self.text.set_content(content);
+    }
 }
 impl UIMouseInteractiveRect for MenuButton {
     fn check_mouse_interaction(&mut self, ctx: &mut Context) -> tetra::Result<Transition> {
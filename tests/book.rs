@@ -0,0 +1,125 @@
+use lil_chess::book::{encode_move, BookEntry, BookError, OpeningBook};
+use lil_chess::fen::STARTING_POSITION_FEN;
+use lil_chess::game_types::BoardState;
+use lil_chess::pgn::{PgnGame, PgnReader};
+use lil_chess::random::Random;
+
+const STARTING_POSITION_KEY: u64 = 0x463b96181691fc9c;
+
+fn encode(fen: &str, long_algebraic: &str) -> u16 {
+    let board = BoardState::from_fen(fen).unwrap();
+    encode_move(&board.move_from_long_algebraic(long_algebraic).unwrap())
+}
+fn entry(key: u64, raw_move: u16, weight: u16) -> BookEntry {
+    BookEntry {
+        key,
+        raw_move,
+        weight,
+        learn: 0,
+    }
+}
+
+#[test]
+fn move_encoding() {
+    // to file and rank, then from file and rank, then the promotion piece
+    assert_eq!(encode(STARTING_POSITION_FEN, "e2e4"), 0x031c);
+    assert_eq!(encode(STARTING_POSITION_FEN, "g1f3"), 0x0195);
+    // castling is the king taking its own rook
+    let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+    assert_eq!(encode(fen, "e1g1"), 0x0107);
+    assert_eq!(encode(fen, "e1c1"), 0x0100);
+    let fen = "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1";
+    assert_eq!(encode(fen, "e8g8"), 0x0f3f);
+    assert_eq!(encode(fen, "e8c8"), 0x0f38);
+    let fen = "3k4/P7/8/8/8/8/8/4K3 w - - 0 1";
+    assert_eq!(encode(fen, "a7a8n"), 0x1c38);
+    assert_eq!(encode(fen, "a7a8b"), 0x2c38);
+    assert_eq!(encode(fen, "a7a8r"), 0x3c38);
+    assert_eq!(encode(fen, "a7a8q"), 0x4c38);
+}
+
+#[test]
+fn decoding_entries() {
+    let bytes = [
+        0x46, 0x3b, 0x96, 0x18, 0x16, 0x91, 0xfc, 0x9c, // key
+        0x03, 0x1c, // e2e4
+        0x00, 0x2a, // weight
+        0x00, 0x00, 0x01, 0x02, // learn
+    ];
+    let book = OpeningBook::from_bytes(&bytes).unwrap();
+    assert_eq!(book.len(), 1);
+    assert_eq!(book.to_bytes(), bytes.to_vec());
+    let board = BoardState::from_fen(STARTING_POSITION_FEN).unwrap();
+    assert_eq!(
+        book.get_moves(&board),
+        vec![(board.move_from_long_algebraic("e2e4").unwrap(), 42)]
+    );
+    assert!(matches!(
+        OpeningBook::from_bytes(&bytes[..15]),
+        Err(BookError::InvalidLength(15))
+    ));
+    assert!(OpeningBook::from_bytes(&[]).unwrap().is_empty());
+}
+
+#[test]
+fn castling_and_promotions_from_the_book() {
+    let castling = BoardState::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    let promotion = BoardState::from_fen("3k4/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let book = OpeningBook::new(vec![
+        entry(castling.hash(), 0x0107, 2),
+        entry(castling.hash(), 0x0100, 1),
+        entry(promotion.hash(), 0x1c38, 5),
+    ]);
+    let moves = book.get_moves(&castling);
+    assert_eq!(moves.len(), 2);
+    assert!(moves[0].0.is_king_side_castles());
+    assert!(moves[1].0.is_queen_side_castles());
+    assert_eq!(
+        book.get_moves(&promotion),
+        vec![(promotion.move_from_long_algebraic("a7a8n").unwrap(), 5)]
+    );
+}
+
+#[test]
+fn starting_position_lookup() {
+    let board = BoardState::from_fen(STARTING_POSITION_FEN).unwrap();
+    assert_eq!(board.hash(), STARTING_POSITION_KEY);
+    let mut book = OpeningBook::new(vec![
+        entry(STARTING_POSITION_KEY + 1, 0x031c, 100),
+        entry(STARTING_POSITION_KEY, 0x02db, 1),
+        entry(STARTING_POSITION_KEY, 0x031c, 3),
+        // an illegal move under a colliding key
+        entry(STARTING_POSITION_KEY, 0x0324, 50),
+    ]);
+    let e4 = board.move_from_long_algebraic("e2e4").unwrap();
+    let d4 = board.move_from_long_algebraic("d2d4").unwrap();
+    assert_eq!(book.get_moves(&board), vec![(e4, 3), (d4, 1)]);
+    let mut random = Random::new(7);
+    for _ in 0..20 {
+        let mv = book.choose_move(&board, &mut random).unwrap();
+        assert!(mv == e4 || mv == d4);
+    }
+    book.max_depth = 0;
+    assert_eq!(book.choose_move(&board, &mut random), None);
+}
+
+#[test]
+fn building_from_games() {
+    let text = "1. e4 e5 2. Nf3 1-0\n\n1. e4 c5 1/2-1/2\n\n1. d4 d5 0-1\n";
+    let games = PgnReader::new(text)
+        .collect::<Result<Vec<PgnGame>, _>>()
+        .unwrap();
+    let book = OpeningBook::from_games(games.iter(), 16);
+    let board = BoardState::from_fen(STARTING_POSITION_FEN).unwrap();
+    let e4 = board.move_from_long_algebraic("e2e4").unwrap();
+    // two for the win, one for the draw, and d4 only ever lost
+    assert_eq!(book.get_moves(&board), vec![(e4, 3)]);
+    let book = OpeningBook::from_bytes(&book.to_bytes()).unwrap();
+    let mut after_e4 = board;
+    after_e4.make_move(&e4);
+    let c5 = after_e4.move_from_long_algebraic("c7c5").unwrap();
+    assert_eq!(book.get_moves(&after_e4), vec![(c5, 1)]);
+    let after_d4 = &games[2].history.board_states[1];
+    let d5 = after_d4.move_from_long_algebraic("d7d5").unwrap();
+    assert_eq!(book.get_moves(after_d4), vec![(d5, 2)]);
+}
@@ -0,0 +1,87 @@
+use lil_chess::evaluation::{EvaluationWeights, TaperedWeight, WeightsError};
+use lil_chess::game_types::{BoardState, PieceType};
+
+#[test]
+fn defaults_round_trip() {
+    let config = EvaluationWeights::default().to_config();
+    let weights = EvaluationWeights::from_config(&config).unwrap();
+    assert_eq!(weights.to_config(), config);
+    // nothing mentioned, nothing changed
+    let weights = EvaluationWeights::from_config("# just a comment\n\n").unwrap();
+    assert_eq!(weights.to_config(), config);
+}
+
+#[test]
+fn keys_comments_and_continued_lines() {
+    let mut table = String::from("pawn_table_endgame =\n");
+    for rank in 0..8 {
+        table.push_str(&format!(
+            "    {} {} {} {} {} {} {} {}\n",
+            rank, 1, 2, 3, 4, 5, 6, 7
+        ));
+    }
+    let config = format!(
+        "# weights for a test\nknight_value = 310 290 # a comment\nbishop_pair = 40\n    60\n{}",
+        table
+    );
+    let weights = EvaluationWeights::from_config(&config).unwrap();
+    let defaults = EvaluationWeights::default();
+    assert_eq!(
+        weights.piece_values[PieceType::KNIGHT.index()],
+        TaperedWeight::new(310, 290)
+    );
+    assert_eq!(weights.bishop_pair, TaperedWeight::new(40, 60));
+    let pawn_table = weights.piece_square_tables[PieceType::PAWN.index()];
+    let default_pawn_table = defaults.piece_square_tables[PieceType::PAWN.index()];
+    assert_eq!(pawn_table[0].endgame, 0);
+    assert_eq!(pawn_table[17].endgame, 1);
+    assert_eq!(pawn_table[63].endgame, 7);
+    // the other half of the table keeps its default
+    assert_eq!(pawn_table[17].midgame, default_pawn_table[17].midgame);
+    assert_eq!(weights.doubled_pawn, defaults.doubled_pawn);
+}
+
+#[test]
+fn weights_change_the_evaluation() {
+    let board = BoardState::from_fen("4k3/8/8/8/8/8/8/1N2K3 w - - 0 1").unwrap();
+    let defaults = EvaluationWeights::default();
+    let weights = EvaluationWeights::from_config("knight_value = 1300 1300").unwrap();
+    assert!(weights.evaluate(&board) >= defaults.evaluate(&board) + 900);
+}
+
+#[test]
+fn errors() {
+    let error = |config: &str| EvaluationWeights::from_config(config).unwrap_err();
+    assert!(matches!(
+        error("# the key got lost\n  5 6\nknight_value = 1 2"),
+        WeightsError::MissingEquals { line: 2 }
+    ));
+    assert!(matches!(
+        error("knight_value = 1 2\nknight_speed = 3 4"),
+        WeightsError::UnknownKey { line: 2, ref key } if key == "knight_speed"
+    ));
+    assert!(matches!(
+        error("knight_value = 1 2\nbishop_value =\n 3 4.5"),
+        WeightsError::InvalidValue { line: 3, ref value } if value == "4.5"
+    ));
+    assert!(matches!(
+        error("knight_value = 1 2 3"),
+        WeightsError::WrongValueCount {
+            line: 1,
+            expected: 2,
+            found: 3
+        }
+    ));
+    assert!(matches!(
+        error("pawn_table_midgame = 1 2 3"),
+        WeightsError::WrongValueCount {
+            expected: 64,
+            found: 3,
+            ..
+        }
+    ));
+    assert!(matches!(
+        EvaluationWeights::load("no/such/weights.cfg"),
+        Err(WeightsError::Io(_))
+    ));
+}
@@ -0,0 +1,137 @@
+use lil_chess::fen::{FenError, STARTING_POSITION_FEN};
+use lil_chess::game::{TEST_POSITION_1_FEN, TEST_POSITION_2_FEN};
+use lil_chess::game_types::{BoardState, PieceType, PlayerColor};
+use tetra::math::Vec2;
+
+#[test]
+fn starting_position() {
+    let board = BoardState::from_fen(STARTING_POSITION_FEN).unwrap();
+    assert_eq!(board.player_to_move, PlayerColor::WHITE);
+    assert!(board.castling_rules.white_can_still_castle_k);
+    assert!(board.castling_rules.black_can_still_castle_q);
+    assert_eq!(board.can_take_en_passant, None);
+    assert_eq!(board.halfmove_clock, 0);
+    assert_eq!(board.fullmove_number, 1);
+    let king = board.get_piece_at_square(Vec2::new(4, 0)).unwrap();
+    assert_eq!(king.piece_type, PieceType::KING);
+    assert_eq!(king.color, PlayerColor::WHITE);
+    let queen = board.get_piece_at_square(Vec2::new(3, 7)).unwrap();
+    assert_eq!(queen.piece_type, PieceType::QUEEN);
+    assert_eq!(queen.color, PlayerColor::BLACK);
+    assert!(board.get_piece_at_square(Vec2::new(4, 3)).is_none());
+}
+
+#[test]
+fn round_trip() {
+    let fens = [
+        STARTING_POSITION_FEN,
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        "rnbqkbnr/pppp1ppp/8/8/3Pp3/8/PPP1PPPP/RNBQKBNR b Kq d3 0 3",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 37 60",
+        "4k3/8/8/8/8/8/8/R3K3 b Q - 5 40",
+        TEST_POSITION_1_FEN,
+        TEST_POSITION_2_FEN,
+    ];
+    for fen in fens.iter() {
+        assert_eq!(BoardState::from_fen(fen).unwrap().to_fen(), *fen);
+    }
+}
+
+#[test]
+fn move_counters_may_be_left_out() {
+    let board = BoardState::from_fen("4k3/8/8/8/8/8/8/4K3 b - -").unwrap();
+    assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/4K3 b - - 0 1");
+}
+
+#[test]
+fn moves_update_the_fields() {
+    let mut board = BoardState::from_fen(STARTING_POSITION_FEN).unwrap();
+    for text in ["e2e4", "g8f6", "e1e2"].iter() {
+        let mv = board.move_from_long_algebraic(text).unwrap();
+        board.make_move(&mv);
+    }
+    assert_eq!(
+        board.to_fen(),
+        "rnbqkb1r/pppppppp/5n2/8/4P3/8/PPPPKPPP/RNBQ1BNR b kq - 2 2"
+    );
+}
+
+#[test]
+fn errors() {
+    let cases = [
+        ("", FenError::WrongFieldCount(0)),
+        ("8/8/8/8/8/8/8/8 w - - 0", FenError::WrongFieldCount(5)),
+        ("4k3/8/8/8/8/8/4K3 w - - 0 1", FenError::WrongRankCount(7)),
+        (
+            "4k3/8/8/8/8/8/8/4K4 w - - 0 1",
+            FenError::WrongRankLength { rank: 1, length: 9 },
+        ),
+        (
+            "4k3/8/8/8/8/7/8/4K3 w - - 0 1",
+            FenError::WrongRankLength { rank: 3, length: 7 },
+        ),
+        (
+            "4k3/8/8/8/8/8/8/4K2X w - - 0 1",
+            FenError::InvalidPiece('X'),
+        ),
+        (
+            "4k3/8/8/8/8/8/8/4K3 x - - 0 1",
+            FenError::InvalidSideToMove("x".to_owned()),
+        ),
+        (
+            "4k3/8/8/8/8/8/8/4K3 w KX - 0 1",
+            FenError::InvalidCastlingRights("KX".to_owned()),
+        ),
+        (
+            "4k3/8/8/8/8/8/8/4K3 w KK - 0 1",
+            FenError::InvalidCastlingRights("KK".to_owned()),
+        ),
+        (
+            "4k3/8/8/8/8/8/8/4K3 w - e3 0 1",
+            FenError::InvalidEnPassantSquare("e3".to_owned()),
+        ),
+        (
+            "4k3/8/8/8/8/8/8/4K3 b - e6 0 1",
+            FenError::InvalidEnPassantSquare("e6".to_owned()),
+        ),
+        (
+            "4k3/8/8/8/8/8/8/4K3 w - i6 0 1",
+            FenError::InvalidEnPassantSquare("i6".to_owned()),
+        ),
+        (
+            "4k3/8/8/8/8/8/8/4K3 w - - -1 1",
+            FenError::InvalidHalfmoveClock("-1".to_owned()),
+        ),
+        (
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 0",
+            FenError::InvalidFullmoveNumber("0".to_owned()),
+        ),
+        (
+            "4k2P/8/8/8/8/8/8/4K3 w - - 0 1",
+            FenError::PawnOnBackRank { x: 7, y: 7 },
+        ),
+        (
+            "8/8/8/8/8/8/8/4K3 w - - 0 1",
+            FenError::WrongKingCount {
+                color: PlayerColor::BLACK,
+                count: 0,
+            },
+        ),
+        (
+            "4k3/8/8/8/8/8/8/3KK3 w - - 0 1",
+            FenError::WrongKingCount {
+                color: PlayerColor::WHITE,
+                count: 2,
+            },
+        ),
+    ];
+    for (fen, error) in cases.iter() {
+        assert_eq!(
+            BoardState::from_fen(fen).err().as_ref(),
+            Some(error),
+            "'{}'",
+            fen
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use lil_chess::fen::STARTING_POSITION_FEN;
+use lil_chess::game_types::{BoardState, GameHistory, GameStatus, PlayerColor};
+
+fn play(fen: &str, moves: &[&str]) -> GameHistory {
+    let mut history = GameHistory::new(vec![BoardState::from_fen(fen).unwrap()], None);
+    for text in moves.iter() {
+        let mv = history.get_board().move_from_san(text).unwrap();
+        history.execute_move(&mv);
+    }
+    history
+}
+fn status(fen: &str) -> GameStatus {
+    play(fen, &[]).get_status()
+}
+
+#[test]
+fn checkmate_and_stalemate() {
+    let history = play(STARTING_POSITION_FEN, &["f3", "e5", "g4", "Qh4#"]);
+    assert_eq!(
+        history.get_status(),
+        GameStatus::Checkmate {
+            winner: PlayerColor::BLACK
+        }
+    );
+    assert_eq!(
+        status("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"),
+        GameStatus::Stalemate
+    );
+    assert_eq!(status(STARTING_POSITION_FEN), GameStatus::Ongoing);
+}
+
+#[test]
+fn threefold_repetition() {
+    let knights_out_and_back = ["Nf3", "Nf6", "Ng1", "Ng8"];
+    let moves = knights_out_and_back.repeat(2);
+    // the starting position has now occurred three times
+    assert_eq!(
+        play(STARTING_POSITION_FEN, &moves).get_status(),
+        GameStatus::ThreefoldRepetition
+    );
+    assert_eq!(
+        play(STARTING_POSITION_FEN, &moves[..7]).get_status(),
+        GameStatus::Ongoing
+    );
+    // a pawn move in between starts the count over
+    let moves = [&["e4", "e5"][..], &moves[..7]].concat();
+    assert_eq!(
+        play(STARTING_POSITION_FEN, &moves).get_status(),
+        GameStatus::Ongoing
+    );
+}
+
+#[test]
+fn repetition_needs_the_same_castling_rights() {
+    // the kings go back where they were, but can't castle any more
+    let fen = "r3k2r/pppp1ppp/8/8/8/8/PPPP1PPP/R3K2R w KQkq - 0 1";
+    let kings_out_and_back = ["Ke2", "Ke7", "Ke1", "Ke8"];
+    assert_eq!(
+        play(fen, &kings_out_and_back.repeat(2)).get_status(),
+        GameStatus::Ongoing
+    );
+    assert_eq!(
+        play(fen, &kings_out_and_back.repeat(3)).get_status(),
+        GameStatus::ThreefoldRepetition
+    );
+}
+
+#[test]
+fn fifty_move_rule() {
+    let fen = "4k3/8/8/8/8/8/4P3/R3K3 w - - 99 80";
+    assert_eq!(status(fen), GameStatus::Ongoing);
+    assert_eq!(play(fen, &["Ra2"]).get_status(), GameStatus::FiftyMoveRule);
+    // a pawn move resets the count
+    assert_eq!(play(fen, &["e4"]).get_status(), GameStatus::Ongoing);
+    // checkmate on the hundredth half move still counts
+    assert_eq!(
+        play("k7/8/1K6/8/8/8/8/7R w - - 99 80", &["Rh8#"]).get_status(),
+        GameStatus::Checkmate {
+            winner: PlayerColor::WHITE
+        }
+    );
+}
+
+#[test]
+fn insufficient_material() {
+    let drawn = [
+        "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/4KN2 w - - 0 1",
+        "4k3/8/8/8/8/8/8/4KB2 b - - 0 1",
+        // bishops that all stand on the same color of squares
+        "4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/B1B1K3 w - - 0 1",
+    ];
+    for fen in drawn.iter() {
+        assert_eq!(status(fen), GameStatus::InsufficientMaterial, "'{}'", fen);
+    }
+    let playable = [
+        "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/3RK3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/1NN1K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/2B1KN2 w - - 0 1",
+        // bishops on both colors
+        "4k1b1/8/8/8/8/8/8/2B1K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1",
+    ];
+    for fen in playable.iter() {
+        assert_eq!(status(fen), GameStatus::Ongoing, "'{}'", fen);
+    }
+}
+
+#[test]
+fn timeout() {
+    let out_of_time = |fen: &str| {
+        let mut history = play(fen, &[]);
+        history.player_out_of_time = Some(history.get_board().player_to_move);
+        history.get_status()
+    };
+    assert_eq!(
+        out_of_time("r3k3/8/8/8/8/8/8/R3K3 b - - 0 1"),
+        GameStatus::Timeout {
+            winner: PlayerColor::WHITE
+        }
+    );
+    assert_eq!(
+        out_of_time("r3k3/8/8/8/8/8/8/1NN1K3 b - - 0 1"),
+        GameStatus::Timeout {
+            winner: PlayerColor::WHITE
+        }
+    );
+    // the opponent can't mate with what it has left, however much the flagged side has
+    for fen in [
+        "4k3/8/8/8/8/8/8/R3K3 w - - 0 1",
+        "r3k3/8/8/8/8/8/8/2B1K3 b - - 0 1",
+        "4kn2/8/8/8/8/8/8/R3K3 w - - 0 1",
+    ]
+    .iter()
+    {
+        assert_eq!(
+            out_of_time(fen),
+            GameStatus::InsufficientMaterial,
+            "'{}'",
+            fen
+        );
+    }
+}
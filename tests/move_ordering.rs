@@ -0,0 +1,112 @@
+use lil_chess::game_types::{BoardState, ChessMove};
+use lil_chess::move_ordering::{MoveOrdering, MovePicker};
+
+const POSITIONS: [&str; 5] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    // en passant, and promotions with and without a capture
+    "r3k3/1P6/8/3pP3/8/8/8/4K3 w q d6 0 2",
+    // in check, only a few moves get out of it
+    "4k3/8/8/8/1b6/8/8/R3K1N1 w Q - 0 1",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+];
+
+fn moves(board: &BoardState, long_algebraic: &[&str]) -> Vec<ChessMove> {
+    long_algebraic
+        .iter()
+        .map(|text| board.move_from_long_algebraic(text).unwrap())
+        .collect()
+}
+fn pick_all(
+    board: &BoardState,
+    hash_move: Option<ChessMove>,
+    refutations: &[ChessMove],
+    ordering: &MoveOrdering,
+) -> Vec<ChessMove> {
+    let mut picker = MovePicker::new(
+        board,
+        board.get_all_legal_moves(),
+        hash_move,
+        refutations,
+        ordering,
+    );
+    let mut picked = Vec::new();
+    while let Some(mv) = picker.next(board) {
+        picked.push(mv);
+    }
+    picked
+}
+fn sorted(moves: &[ChessMove]) -> Vec<String> {
+    let mut moves = moves
+        .iter()
+        .map(|mv| mv.to_long_algebraic())
+        .collect::<Vec<String>>();
+    moves.sort();
+    moves
+}
+
+#[test]
+fn every_legal_move_once() {
+    let starting_position = BoardState::from_fen(POSITIONS[0]).unwrap();
+    // killer and counter moves from another position, that can't be played in most of these
+    let mut ordering = MoveOrdering::new();
+    let previous_move = Some(moves(&starting_position, &["e2e4"])[0]);
+    for (ply, mv) in moves(&starting_position, &["g1f3", "b1c3", "d2d4"])
+        .into_iter()
+        .enumerate()
+    {
+        ordering.record_cutoff(mv, ply as i32 % 2, 6, previous_move, &[]);
+    }
+    for fen in POSITIONS.iter() {
+        let board = BoardState::from_fen(fen).unwrap();
+        let legal_moves = board.get_all_legal_moves();
+        let mut refutations = ordering.get_refutations(0, previous_move);
+        refutations.extend(ordering.get_refutations(1, None));
+        // a legal one as well, which may be a capture or the hash move
+        refutations.push(legal_moves[legal_moves.len() / 2]);
+        let hash_moves = [
+            None,
+            legal_moves.first().copied(),
+            legal_moves.last().copied(),
+        ];
+        for hash_move in hash_moves.iter() {
+            let picked = pick_all(&board, *hash_move, &refutations, &ordering);
+            assert_eq!(sorted(&picked), sorted(&legal_moves), "'{}'", fen);
+            if hash_move.is_some() {
+                assert_eq!(picked.first(), hash_move.as_ref());
+            }
+        }
+    }
+}
+
+#[test]
+fn illegal_refutations_are_skipped() {
+    let starting_position = BoardState::from_fen(POSITIONS[0]).unwrap();
+    let killer_move = moves(&starting_position, &["g1f3"])[0];
+    let counter_move = moves(&starting_position, &["b1c3"])[0];
+    let previous_move = moves(&starting_position, &["e2e4"])[0];
+    let mut ordering = MoveOrdering::new();
+    ordering.record_cutoff(killer_move, 3, 4, None, &[]);
+    ordering.record_cutoff(counter_move, 5, 4, Some(previous_move), &[]);
+    let refutations = ordering.get_refutations(3, Some(previous_move));
+    assert_eq!(refutations, vec![killer_move, counter_move]);
+
+    // played from where they were found they come right after the captures, here there are none
+    let board = BoardState::from_fen("4k3/8/8/8/8/8/8/1N2K1N1 w - - 0 1").unwrap();
+    let picked = pick_all(&board, None, &refutations, &ordering);
+    assert_eq!(picked[..2], [killer_move, counter_move]);
+    // the knights are gone, pinned, or can't stop a check
+    for fen in [
+        "4k3/8/8/8/8/8/8/R3K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/rN2K1Nr w - - 0 1",
+        "4k3/4q3/8/8/8/8/8/1N2K1N1 w - - 0 1",
+    ]
+    .iter()
+    {
+        let board = BoardState::from_fen(fen).unwrap();
+        let picked = pick_all(&board, None, &refutations, &ordering);
+        assert_eq!(sorted(&picked), sorted(&board.get_all_legal_moves()));
+        assert!(!picked.contains(&killer_move), "'{}'", fen);
+        assert!(!picked.contains(&counter_move), "'{}'", fen);
+    }
+}
@@ -0,0 +1,165 @@
+use lil_chess::fen::STARTING_POSITION_FEN;
+use lil_chess::game_types::BoardState;
+
+// node counts by depth, starting at depth 1, from https://www.chessprogramming.org/Perft_Results
+const KIWIPETE_FEN: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+const POSITION_3_FEN: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+const POSITION_4_FEN: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+const POSITION_4_MIRRORED_FEN: &str =
+    "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+const POSITION_5_FEN: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+const POSITION_6_FEN: &str =
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+fn assert_perft(fen: &str, node_counts: &[u64]) {
+    let board = BoardState::from_fen(fen).unwrap();
+    for (i, expected) in node_counts.iter().enumerate() {
+        let depth = i as u32 + 1;
+        assert_eq!(
+            board.perft(depth),
+            *expected,
+            "perft({}) of '{}'",
+            depth,
+            fen
+        );
+    }
+}
+
+#[test]
+fn starting_position() {
+    assert_perft(STARTING_POSITION_FEN, &[20, 400, 8902]);
+}
+#[test]
+fn kiwipete() {
+    assert_perft(KIWIPETE_FEN, &[48, 2039]);
+}
+#[test]
+fn position_3() {
+    assert_perft(POSITION_3_FEN, &[14, 191, 2812, 43238]);
+}
+#[test]
+fn position_4() {
+    assert_perft(POSITION_4_FEN, &[6, 264, 9467]);
+    assert_perft(POSITION_4_MIRRORED_FEN, &[6, 264, 9467]);
+}
+#[test]
+fn position_5() {
+    assert_perft(POSITION_5_FEN, &[44, 1486]);
+}
+#[test]
+fn position_6() {
+    assert_perft(POSITION_6_FEN, &[46, 2079]);
+}
+// positions built around one rule each: castling rights, en passant pins, promotions and so on
+#[test]
+fn edge_cases() {
+    assert_perft("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", &[26, 568, 13744]);
+    assert_perft("1k6/1b6/8/8/7R/8/8/4K2R b K - 0 1", &[13, 284, 3529]);
+    assert_perft("3k4/3p4/8/K1P4r/8/8/8/8 b - - 0 1", &[18, 92, 1670, 10138]);
+    assert_perft(
+        "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1",
+        &[15, 126, 1928, 13931],
+    );
+    assert_perft("8/5bk1/8/2Pp4/8/1K6/8/8 w - d6 0 1", &[8, 104, 736, 9287]);
+    assert_perft(
+        "r3k2r/1b4bq/8/8/8/8/7B/R3K2R w KQkq - 0 1",
+        &[26, 1141, 27826],
+    );
+    assert_perft(
+        "r3k2r/8/3Q4/8/8/5q2/8/R3K2R b KQkq - 0 1",
+        &[44, 1494, 50509],
+    );
+    assert_perft("2K2r2/4P3/8/8/8/8/8/3k4 w - - 0 1", &[11, 133, 1442, 19174]);
+    assert_perft("4k3/1P6/8/8/8/8/K7/8 w - - 0 1", &[9, 40, 472, 2661]);
+    assert_perft("8/8/2k5/5q2/5n2/8/5K2/8 b - - 0 1", &[37, 183, 6559]);
+}
+
+#[test]
+fn unmake_move_restores_position() {
+    for fen in [KIWIPETE_FEN, POSITION_4_FEN, POSITION_5_FEN].iter() {
+        let mut board = BoardState::from_fen(fen).unwrap();
+        for mv in board.get_all_legal_moves().iter() {
+            let undo_info = board.make_move(mv);
+            board.unmake_move(mv, undo_info);
+            assert_eq!(board.to_fen(), *fen);
+        }
+    }
+}
+
+// every node's key has to match the one computed from scratch for the same position
+fn assert_hashes(board: &mut BoardState, depth: u32) {
+    let from_scratch = BoardState::from_fen(&board.to_fen()).unwrap();
+    assert_eq!(
+        board.hash(),
+        from_scratch.hash(),
+        "hash of '{}'",
+        board.to_fen()
+    );
+    if depth == 0 {
+        return;
+    }
+    for mv in board.get_all_legal_moves().iter() {
+        let undo_info = board.make_move(mv);
+        assert_hashes(board, depth - 1);
+        board.unmake_move(mv, undo_info);
+    }
+}
+#[test]
+fn hash_is_updated_incrementally() {
+    for fen in [KIWIPETE_FEN, POSITION_3_FEN, POSITION_4_FEN].iter() {
+        assert_hashes(&mut BoardState::from_fen(fen).unwrap(), 2);
+    }
+    // en passant only counts when the pawn can really be taken
+    assert_hashes(
+        &mut BoardState::from_fen("4k3/8/8/8/1p6/8/P1P5/4K3 w - - 0 1").unwrap(),
+        2,
+    );
+}
+
+// the test positions published along with the Polyglot book format, reached from the starting position
+#[test]
+fn hash_matches_polyglot_keys() {
+    let lines: [(&[&str], u64); 9] = [
+        (&[], 0x463b96181691fc9c),
+        (&["e2e4"], 0x823c9b50fd114196),
+        (&["e2e4", "d7d5"], 0x0756b94461c50fb0),
+        (&["e2e4", "d7d5", "e4e5"], 0x662fafb965db29d4),
+        (&["e2e4", "d7d5", "e4e5", "f7f5"], 0x22a48b5a8e47ff78),
+        (
+            &["e2e4", "d7d5", "e4e5", "f7f5", "e1e2"],
+            0x652a607ca3f242c1,
+        ),
+        (
+            &["e2e4", "d7d5", "e4e5", "f7f5", "e1e2", "e8f7"],
+            0x00fdd303c946bdd9,
+        ),
+        (
+            &["a2a4", "b7b5", "h2h4", "b5b4", "c2c4"],
+            0x3c8123ea7b067637,
+        ),
+        (
+            &["a2a4", "b7b5", "h2h4", "b5b4", "c2c4", "b4c3", "a1a3"],
+            0x5c3f9b829b279560,
+        ),
+    ];
+    for (moves, expected) in lines.iter() {
+        let mut board = BoardState::from_fen(STARTING_POSITION_FEN).unwrap();
+        for text in moves.iter() {
+            let mv = board.move_from_long_algebraic(text).unwrap();
+            board.make_move(&mv);
+        }
+        assert_eq!(board.hash(), *expected, "hash after {:?}", moves);
+    }
+}
+
+// too slow for every test run, try `cargo test --release -- --ignored`
+#[test]
+#[ignore]
+fn deep_perft() {
+    assert_perft(STARTING_POSITION_FEN, &[20, 400, 8902, 197281, 4865609]);
+    assert_perft(KIWIPETE_FEN, &[48, 2039, 97862, 4085603]);
+    assert_perft(POSITION_3_FEN, &[14, 191, 2812, 43238, 674624]);
+    assert_perft(POSITION_4_FEN, &[6, 264, 9467, 422333]);
+    assert_perft(POSITION_5_FEN, &[44, 1486, 62379, 2103487]);
+    assert_perft(POSITION_6_FEN, &[46, 2079, 89890, 3894594]);
+}
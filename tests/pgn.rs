@@ -0,0 +1,154 @@
+use lil_chess::fen::STARTING_POSITION_FEN;
+use lil_chess::game_types::{BoardState, GameHistory};
+use lil_chess::pgn::{PgnError, PgnGame, PgnReader};
+
+fn play(fen: &str, moves: &[&str]) -> GameHistory {
+    let mut history = GameHistory::new(vec![BoardState::from_fen(fen).unwrap()], None);
+    for text in moves.iter() {
+        let mv = history.get_board().move_from_san(text).unwrap();
+        history.execute_move(&mv);
+    }
+    history
+}
+fn read_one(text: &str) -> PgnGame {
+    let mut games = PgnReader::new(text).collect::<Vec<Result<PgnGame, PgnError>>>();
+    assert_eq!(games.len(), 1, "games in '{}'", text);
+    games.remove(0).unwrap()
+}
+
+#[test]
+fn round_trip() {
+    let history = play(
+        STARTING_POSITION_FEN,
+        &["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "O-O"],
+    );
+    let mut game = PgnGame::new(history);
+    game.set_tag("White", "Anna \"The Rook\" Müller");
+    game.set_tag("Black", "Bob");
+    game.set_tag("Annotator", "LilChess");
+    game.initial_comment = Some("The Ruy Lopez".to_owned());
+    game.annotations[2].nags.push(1);
+    game.annotations[4].comment = Some("the main line".to_owned());
+    let pgn = game.to_pgn();
+    let read = read_one(&pgn);
+    assert_eq!(read.history.get_san_moves(), game.history.get_san_moves());
+    assert_eq!(read.get_tag("White"), Some("Anna \"The Rook\" Müller"));
+    assert_eq!(read.get_tag("Annotator"), Some("LilChess"));
+    assert_eq!(read.get_result(), "*");
+    assert_eq!(read.initial_comment.as_deref(), Some("The Ruy Lopez"));
+    assert_eq!(read.annotations[2].nags, vec![1]);
+    assert_eq!(
+        read.annotations[4].comment.as_deref(),
+        Some("the main line")
+    );
+    assert_eq!(read.to_pgn(), pgn);
+}
+
+#[test]
+fn round_trip_from_a_position() {
+    let fen = "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1";
+    let pgn = PgnGame::new(play(fen, &["e4", "Kd7"])).to_pgn();
+    assert!(pgn.contains("[SetUp \"1\"]"));
+    assert!(pgn.contains(&format!("[FEN \"{}\"]", fen)));
+    let read = read_one(&pgn);
+    assert_eq!(read.history.board_states[0].to_fen(), fen);
+    assert_eq!(read.history.get_san_moves(), vec!["e4", "Kd7"]);
+}
+
+#[test]
+fn result_of_the_final_position() {
+    let history = play(STARTING_POSITION_FEN, &["f3", "e5", "g4", "Qh4#"]);
+    let game = PgnGame::new(history);
+    assert_eq!(game.get_result(), "0-1");
+    assert!(game.to_pgn().ends_with("2. g4 Qh4# 0-1\n\n"));
+}
+
+#[test]
+fn tags() {
+    let game = read_one(
+        "[Event \"Club \\\"Open\\\" \\\\ 2021\"]\n[Site \"?\"]\n[WhiteElo \"2100\"]\n\n1. d4 d5 1/2-1/2\n",
+    );
+    assert_eq!(game.get_tag("Event"), Some("Club \"Open\" \\ 2021"));
+    assert_eq!(game.get_tag("WhiteElo"), Some("2100"));
+    assert_eq!(game.get_tag("Round"), None);
+    assert_eq!(game.get_result(), "1/2-1/2");
+}
+
+#[test]
+fn comments_and_variations() {
+    let game = read_one(
+        "{Before the first move} 1. e4 {best by test} e5 (1... c5 {the Sicilian} 2. Nf3 (2. c3)) \
+         2. Nf3 ; a rest of line comment\nNc6 {first} {second} *",
+    );
+    assert_eq!(game.history.get_san_moves(), vec!["e4", "e5", "Nf3", "Nc6"]);
+    assert_eq!(
+        game.initial_comment.as_deref(),
+        Some("Before the first move")
+    );
+    assert_eq!(game.annotations[0].comment.as_deref(), Some("best by test"));
+    assert_eq!(game.annotations[1].comment, None);
+    assert_eq!(game.annotations[3].comment.as_deref(), Some("first second"));
+}
+
+#[test]
+fn nags() {
+    let game = read_one("1. e4! e5?! 2. Nf3 $14 Nc6 $2 $32 3. Bc4!! Nf6?? *");
+    let nags = game
+        .annotations
+        .iter()
+        .map(|annotation| annotation.nags.clone())
+        .collect::<Vec<Vec<u8>>>();
+    assert_eq!(
+        nags,
+        vec![vec![1], vec![6], vec![14], vec![2, 32], vec![3], vec![4]]
+    );
+    assert_eq!(game.history.get_san_moves()[4], "Bc4");
+}
+
+#[test]
+fn move_numbers_and_castling() {
+    let game =
+        read_one("1.e4 e5 2.Nf3 Nc6 3.Bc4 Bc5 4.0-0 Nf6 5.d3 d6 6.Bg5 Bg4 7.Nc3 Qd7 8.Qd2 0-0-0 *");
+    let moves = game.history.get_san_moves();
+    assert_eq!(moves.len(), 16);
+    assert_eq!(moves[6], "O-O");
+    assert_eq!(moves[15], "O-O-O");
+    let game = read_one("1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. 0-0 12... *");
+    assert_eq!(game.history.get_san_moves()[6], "O-O");
+}
+
+#[test]
+fn recovers_from_malformed_games() {
+    let text = "[Event \"Broken tag\"]\n[White \"Ann\"\n[Black \"Bob\"]\n\n1. e4 e5 1-0\n\n\
+                [Event \"Illegal move\"]\n\n1. e4 e4 2. Nf3 *\n\n\
+                [Event \"Bad FEN\"]\n[FEN \"8/8/8 w - - 0 1\"]\n\n1. e4 *\n\n\
+                [Event \"Unbalanced\"]\n\n1. e4 (1. d4 e5 *\n\n\
+                [Event \"Good\"]\n[White \"Jürgen\"]\n\n1. d4 {naïve} d5 0-1\n";
+    let games = PgnReader::new(text).collect::<Vec<Result<PgnGame, PgnError>>>();
+    assert_eq!(games.len(), 5);
+    assert!(matches!(games[0], Err(PgnError::InvalidTag(_))));
+    assert!(matches!(
+        games[1],
+        Err(PgnError::IllegalMove { ply: 2, .. })
+    ));
+    assert!(matches!(games[2], Err(PgnError::InvalidFen(_))));
+    assert!(matches!(games[3], Err(PgnError::UnbalancedVariation)));
+    let good = games[4].as_ref().unwrap();
+    assert_eq!(good.get_tag("Event"), Some("Good"));
+    assert_eq!(good.get_tag("White"), Some("Jürgen"));
+    assert_eq!(good.history.get_san_moves(), vec!["d4", "d5"]);
+    assert_eq!(good.get_result(), "0-1");
+}
+
+#[test]
+fn skips_non_ascii_text_after_an_error() {
+    let text = "[Event \"Ça commence\"]\n\n1. e4 e4 {Schöne Grüße} 2. Nf3 Ærøskøbing ½-½\n\n\
+                [Event \"Next\"]\n\n1. c4 *\n";
+    let games = PgnReader::new(text).collect::<Vec<Result<PgnGame, PgnError>>>();
+    assert_eq!(games.len(), 2);
+    assert!(games[0].is_err());
+    assert_eq!(
+        games[1].as_ref().unwrap().history.get_san_moves(),
+        vec!["c4"]
+    );
+}
@@ -0,0 +1,74 @@
+use lil_chess::fen::STARTING_POSITION_FEN;
+use lil_chess::game_types::{BoardState, GameHistory};
+use lil_chess::review::{review_game, GameReview, Judgement};
+use lil_chess::search::{CancellationToken, SearchLimits, Searcher};
+
+fn play(moves: &[&str]) -> GameHistory {
+    let board = BoardState::from_fen(STARTING_POSITION_FEN).unwrap();
+    let mut history = GameHistory::new(vec![board], None);
+    for san in moves.iter() {
+        let mv = history
+            .board_states
+            .last()
+            .unwrap()
+            .move_from_san(san)
+            .unwrap();
+        history.execute_move(&mv);
+    }
+    history
+}
+fn review(history: &GameHistory) -> GameReview {
+    let mut searcher = Searcher::new(16);
+    let limits = SearchLimits::depth(4);
+    review_game(&mut searcher, history, &limits, &CancellationToken::new()).unwrap()
+}
+
+#[test]
+fn hanging_the_queen_is_a_blunder() {
+    // the queen steps onto the diagonal the bishop was just let out on
+    let history = play(&["e4", "d6", "Qg4", "Bxg4"]);
+    let review = review(&history);
+    assert_eq!(review.moves.len(), 4);
+    assert_eq!(review.scores.len(), 5);
+    let blunder = &review.moves[2];
+    assert_eq!(blunder.judgement, Some(Judgement::Blunder));
+    assert!(blunder.score_loss >= 500, "{}", blunder.score_loss);
+    // anything else keeps the queen out of the bishop's reach
+    let mut board = history.board_states[2].clone();
+    let best_move = blunder.best_move.unwrap();
+    board.make_move(&best_move);
+    let replies = board.get_all_legal_moves();
+    assert!(replies.iter().all(|mv| !board.is_capture(mv)));
+    // taking the queen is what the engine would have played too
+    assert!(review.moves[3].best_move.is_none());
+    assert_eq!(review.moves[3].judgement, None);
+    // the first move is a quiet one that gives nothing away
+    assert_eq!(review.moves[0].judgement, None);
+    // from White's side, and no further than a decided game
+    assert!(review.scores[4] < -500, "{}", review.scores[4]);
+    assert!(review.scores.iter().all(|score| score.abs() <= 1000));
+}
+
+#[test]
+fn a_drawn_game_ends_level() {
+    // the knights go out and back twice, the starting position comes up a third time
+    let history = play(&["Nf3", "Nf6", "Ng1", "Ng8", "Nf3", "Nf6", "Ng1", "Ng8"]);
+    assert!(history.get_status().is_over());
+    let review = review(&history);
+    assert_eq!(review.scores.last(), Some(&0));
+    assert!(review
+        .moves
+        .iter()
+        .all(|move_review| move_review.judgement != Some(Judgement::Blunder)));
+}
+
+#[test]
+fn judgement_thresholds() {
+    assert_eq!(Judgement::from_score_loss(0), None);
+    assert_eq!(Judgement::from_score_loss(49), None);
+    assert_eq!(Judgement::from_score_loss(50), Some(Judgement::Inaccuracy));
+    assert_eq!(Judgement::from_score_loss(100), Some(Judgement::Mistake));
+    assert_eq!(Judgement::from_score_loss(249), Some(Judgement::Mistake));
+    assert_eq!(Judgement::from_score_loss(250), Some(Judgement::Blunder));
+    assert_eq!(Judgement::Blunder.symbol(), "??");
+}
@@ -0,0 +1,120 @@
+use lil_chess::fen::STARTING_POSITION_FEN;
+use lil_chess::game_types::BoardState;
+use lil_chess::san::SanError;
+
+// the move given in long algebraic notation, written in SAN, and read back
+fn assert_san(fen: &str, long_algebraic: &str, san: &str) {
+    let board = BoardState::from_fen(fen).unwrap();
+    let mv = board.move_from_long_algebraic(long_algebraic).unwrap();
+    assert_eq!(
+        board.move_to_san(&mv),
+        san,
+        "'{}' in '{}'",
+        long_algebraic,
+        fen
+    );
+    assert_eq!(board.move_from_san(san), Ok(mv), "'{}' in '{}'", san, fen);
+}
+
+#[test]
+fn pieces_and_pawns() {
+    assert_san(STARTING_POSITION_FEN, "e2e4", "e4");
+    assert_san(STARTING_POSITION_FEN, "g1f3", "Nf3");
+    let fen = "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2";
+    assert_san(fen, "e4d5", "exd5");
+    assert_san(fen, "f1b5", "Bb5+");
+}
+
+#[test]
+fn disambiguation() {
+    // by file
+    let fen = "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1";
+    assert_san(fen, "b1d2", "Nbd2");
+    assert_san(fen, "f1d2", "Nfd2");
+    // by rank
+    let fen = "4k3/8/8/R7/8/8/8/R3K3 w - - 0 1";
+    assert_san(fen, "a1a3", "R1a3");
+    assert_san(fen, "a5a3", "R5a3");
+    // by both, when neither tells all three apart
+    let fen = "4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1";
+    assert_san(fen, "a1b2", "Qa1b2");
+    assert_san(fen, "c1b2", "Qcb2");
+    assert_san(fen, "a3b2", "Q3b2");
+    // a pinned rival doesn't count
+    assert_san("4k3/8/8/8/8/8/8/1N2KN1r w - - 0 1", "b1d2", "Nd2");
+}
+
+#[test]
+fn en_passant() {
+    let fen = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2";
+    assert_san(fen, "e5d6", "exd6");
+    let board = BoardState::from_fen(fen).unwrap();
+    assert_eq!(
+        board.move_from_san("exd6 e.p."),
+        board.move_from_long_algebraic("e5d6")
+    );
+}
+
+#[test]
+fn promotion() {
+    let fen = "3k4/P7/8/8/8/8/8/4K3 w - - 0 1";
+    assert_san(fen, "a7a8q", "a8=Q+");
+    assert_san(fen, "a7a8n", "a8=N");
+    let board = BoardState::from_fen(fen).unwrap();
+    assert_eq!(
+        board.move_from_san("a8Q"),
+        board.move_from_long_algebraic("a7a8q")
+    );
+    assert_eq!(
+        board.move_from_san("a8R"),
+        board.move_from_long_algebraic("a7a8r")
+    );
+    // which piece it becomes has to be said
+    assert_eq!(
+        board.move_from_san("a8"),
+        Err(SanError::IllegalMove("a8".to_owned()))
+    );
+}
+
+#[test]
+fn castling() {
+    let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+    assert_san(fen, "e1g1", "O-O");
+    assert_san(fen, "e1c1", "O-O-O");
+    let board = BoardState::from_fen(fen).unwrap();
+    assert_eq!(board.move_from_san("0-0"), board.move_from_san("O-O"));
+    assert_eq!(board.move_from_san("0-0-0"), board.move_from_san("O-O-O"));
+}
+
+#[test]
+fn check_and_mate() {
+    // the fool's mate
+    let fen = "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2";
+    assert_san(fen, "d8h4", "Qh4#");
+    // annotations after the move are ignored
+    let board = BoardState::from_fen(fen).unwrap();
+    assert_eq!(board.move_from_san("Qh4#!!"), board.move_from_san("Qh4"));
+    assert_san("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1", "a1a8", "Ra8+");
+}
+
+#[test]
+fn errors() {
+    let board = BoardState::from_fen("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1").unwrap();
+    assert_eq!(board.move_from_san(""), Err(SanError::Empty));
+    assert_eq!(
+        board.move_from_san("Nd2"),
+        Err(SanError::AmbiguousMove("Nd2".to_owned()))
+    );
+    assert_eq!(
+        board.move_from_san("Ne4"),
+        Err(SanError::IllegalMove("Ne4".to_owned()))
+    );
+    assert_eq!(
+        board.move_from_san("Nz9"),
+        Err(SanError::InvalidSyntax("Nz9".to_owned()))
+    );
+    assert_eq!(
+        board.move_from_san("O-O"),
+        Err(SanError::IllegalMove("O-O".to_owned()))
+    );
+}
@@ -0,0 +1,113 @@
+use lil_chess::game_types::BoardState;
+use lil_chess::search::{
+    get_mate_in, CancellationToken, SearchLimits, SearchResult, Searcher, Selectivity, MATE_SCORE,
+};
+
+// a plain alpha-beta search and the selective one, on one thread and on several
+fn setups() -> Vec<(Selectivity, usize)> {
+    vec![
+        (Selectivity::none(), 1),
+        (Selectivity::default(), 1),
+        (Selectivity::none(), 3),
+        (Selectivity::default(), 3),
+    ]
+}
+fn search(
+    board: &BoardState,
+    depth: u32,
+    selectivity: Selectivity,
+    threads: usize,
+) -> SearchResult {
+    let mut searcher = Searcher::new(1);
+    searcher.selectivity = selectivity;
+    searcher.threads = threads;
+    searcher.search(
+        board,
+        &SearchLimits::depth(depth),
+        &CancellationToken::new(),
+    )
+}
+// the best move in every setup, with its score
+fn search_everywhere(fen: &str, depth: u32) -> Vec<(String, i32)> {
+    let board = BoardState::from_fen(fen).unwrap();
+    setups()
+        .into_iter()
+        .map(|(selectivity, threads)| {
+            let result = search(&board, depth, selectivity, threads);
+            (result.best_move.unwrap().to_long_algebraic(), result.score)
+        })
+        .collect()
+}
+
+#[test]
+fn mate_in_one() {
+    for (best_move, score) in search_everywhere("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 3) {
+        assert_eq!(best_move, "a1a8");
+        assert_eq!(score, MATE_SCORE - 1);
+        assert_eq!(get_mate_in(score), Some(1));
+    }
+}
+
+#[test]
+fn mate_in_two() {
+    let fen = "7k/8/8/8/8/8/R7/1R5K w - - 0 1";
+    // the rooks have a few ways of cutting the king off, whichever one is played black gets mated next
+    for (best_move, score) in search_everywhere(fen, 5) {
+        assert_eq!(score, MATE_SCORE - 3);
+        assert_eq!(get_mate_in(score), Some(2));
+        let mut board = BoardState::from_fen(fen).unwrap();
+        let mv = board.move_from_long_algebraic(&best_move).unwrap();
+        board.make_move(&mv);
+        let reply = search(&board, 4, Selectivity::none(), 1);
+        assert_eq!(reply.score, -MATE_SCORE + 2, "{}", best_move);
+    }
+}
+
+#[test]
+fn getting_mated() {
+    // the king's only move walks into the other rook
+    for (best_move, score) in search_everywhere("7k/R7/8/8/8/8/8/1R5K b - - 0 1", 4) {
+        assert_eq!(best_move, "h8g8");
+        assert_eq!(score, -MATE_SCORE + 2);
+        assert_eq!(get_mate_in(score), Some(-1));
+    }
+}
+
+#[test]
+fn wins_a_hanging_queen() {
+    for (best_move, score) in search_everywhere("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1", 4) {
+        assert_eq!(best_move, "d1d5");
+        assert!(score > 400, "{}", score);
+    }
+}
+
+#[test]
+fn forks_king_and_rook() {
+    for (best_move, score) in search_everywhere("r3k3/8/8/1N6/8/8/8/4K3 w - - 0 1", 5) {
+        assert_eq!(best_move, "b5c7");
+        assert!(score > 0, "{}", score);
+    }
+}
+
+#[test]
+fn repeating_a_position_is_a_draw() {
+    // the kings step away and back, now the black king returning to d8 repeats the first position
+    let mut board = BoardState::from_fen("3k4/8/8/8/8/8/8/Q3K3 w - - 0 1").unwrap();
+    let mut previous_positions = Vec::new();
+    for long_algebraic in ["e1e2", "d8e8", "e2e1"].iter() {
+        previous_positions.push(board.hash());
+        let mv = board.move_from_long_algebraic(long_algebraic).unwrap();
+        board.make_move(&mv);
+    }
+    for (selectivity, threads) in setups() {
+        let lost = search(&board, 4, selectivity, threads);
+        assert!(lost.score < -500, "{}", lost.score);
+        let mut searcher = Searcher::new(1);
+        searcher.selectivity = selectivity;
+        searcher.threads = threads;
+        searcher.previous_positions = previous_positions.clone();
+        let result = searcher.search(&board, &SearchLimits::depth(4), &CancellationToken::new());
+        assert_eq!(result.best_move.unwrap().to_long_algebraic(), "e8d8");
+        assert_eq!(result.score, 0);
+    }
+}
@@ -0,0 +1,50 @@
+use lil_chess::game_types::BoardState;
+
+fn see(fen: &str, long_algebraic: &str) -> i32 {
+    let board = BoardState::from_fen(fen).unwrap();
+    let mv = board.move_from_long_algebraic(long_algebraic).unwrap();
+    board.static_exchange_evaluation(&mv)
+}
+
+#[test]
+fn single_captures() {
+    // an undefended pawn
+    assert_eq!(
+        see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"),
+        100
+    );
+    // a defended knight taken by a pawn, and a defended pawn taken by the queen
+    assert_eq!(see("4k3/8/3p4/4n3/3P4/8/8/4K3 w - - 0 1", "d4e5"), 200);
+    assert_eq!(see("4k3/8/5p2/4p3/8/8/8/Q3K3 w - - 0 1", "a1e5"), -800);
+    // en passant takes the pawn that isn't on the target square
+    assert_eq!(see("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2", "e5d6"), 100);
+}
+
+#[test]
+fn quiet_moves() {
+    assert_eq!(see("4k3/8/8/3p4/8/8/8/4RK2 w - - 0 1", "e1e4"), -500);
+    assert_eq!(see("4k3/8/8/8/8/8/8/4RK2 w - - 0 1", "e1e4"), 0);
+}
+
+#[test]
+fn exchanges_with_x_rays() {
+    // both sides bring in pieces standing behind the first attackers
+    assert_eq!(
+        see(
+            "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+            "d3e5"
+        ),
+        -200
+    );
+    // doubled rooks win the pawn against a single defender
+    assert_eq!(see("4k3/4r3/8/4p3/8/8/4R3/4R2K w - - 0 1", "e2e5"), 100);
+    // but not against two
+    assert_eq!(see("4k3/4r3/4r3/4p3/8/8/4R3/4R2K w - - 0 1", "e2e5"), -400);
+}
+
+#[test]
+fn king_recaptures() {
+    // the king takes back when nothing defends the knight
+    assert_eq!(see("8/8/4k3/3p4/8/4N3/8/4K3 w - - 0 1", "e3d5"), -200);
+    assert_eq!(see("8/8/4k3/3p4/8/4N3/8/3RK3 w - - 0 1", "e3d5"), 100);
+}
@@ -0,0 +1,237 @@
+#!/usr/bin/env python3
+"""Writes KRvK.rtbw and KRvK.rtbz, small tables in the Syzygy layout for the tablebase tests.
+
+They're worked out here by retrograde analysis rather than taken from the official set, and store
+every value as a fixed length symbol without any pair compression, which the probing code reads the
+same way. Run it from this directory: python3 generate.py
+"""
+import struct
+
+# the position numbering for three unique pieces, as in the Syzygy probing code
+TRIANGLE = [
+    6, 0, 1, 2, 2, 1, 0, 6,
+    0, 7, 3, 4, 4, 3, 7, 0,
+    1, 3, 8, 5, 5, 8, 3, 1,
+    2, 4, 5, 9, 9, 5, 4, 2,
+    2, 4, 5, 9, 9, 5, 4, 2,
+    1, 3, 8, 5, 5, 8, 3, 1,
+    0, 7, 3, 4, 4, 3, 7, 0,
+    6, 0, 1, 2, 2, 1, 0, 6]
+LOWER = [
+    28, 0, 1, 2, 3, 4, 5, 6,
+    0, 29, 7, 8, 9, 10, 11, 12,
+    1, 7, 30, 13, 14, 15, 16, 17,
+    2, 8, 13, 31, 18, 19, 20, 21,
+    3, 9, 14, 18, 32, 22, 23, 24,
+    4, 10, 15, 19, 22, 33, 25, 26,
+    5, 11, 16, 20, 23, 25, 34, 27,
+    6, 12, 17, 21, 24, 26, 27, 35]
+DIAG = [
+    0, 0, 0, 0, 0, 0, 0, 8,
+    0, 1, 0, 0, 0, 0, 9, 0,
+    0, 0, 2, 0, 0, 10, 0, 0,
+    0, 0, 0, 3, 11, 0, 0, 0,
+    0, 0, 0, 12, 4, 0, 0, 0,
+    0, 0, 13, 0, 0, 5, 0, 0,
+    0, 14, 0, 0, 0, 0, 6, 0,
+    15, 0, 0, 0, 0, 0, 0, 7]
+SIZE = 31332
+
+WDL_MAGIC = b'\x71\xe8\x23\x5d'
+DTZ_MAGIC = b'\xd7\x66\x0c\xa5'
+LOSS, DRAW = 0, 2
+
+
+def off_diagonal(s):
+    return (s >> 3) - (s & 7)
+
+
+def flip_diagonal(s):
+    return ((s >> 3) | (s << 3)) & 63
+
+
+def encode(p):
+    p = list(p)
+    if p[0] & 4:
+        p = [s ^ 7 for s in p]
+    if p[0] & 0x20:
+        p = [s ^ 0x38 for s in p]
+    i = 0
+    while i < 3 and off_diagonal(p[i]) == 0:
+        i += 1
+    if i < 3 and off_diagonal(p[i]) > 0:
+        for j in range(i, 3):
+            p[j] = flip_diagonal(p[j])
+    t = int(p[1] > p[0])
+    l = int(p[2] > p[0]) + int(p[2] > p[1])
+    if off_diagonal(p[0]):
+        return TRIANGLE[p[0]] * 63 * 62 + (p[1] - t) * 62 + (p[2] - l)
+    if off_diagonal(p[1]):
+        return 6 * 63 * 62 + DIAG[p[0]] * 28 * 62 + LOWER[p[1]] * 62 + p[2] - l
+    if off_diagonal(p[2]):
+        return 6 * 63 * 62 + 4 * 28 * 62 + DIAG[p[0]] * 7 * 28 + (DIAG[p[1]] - t) * 28 + LOWER[p[2]]
+    return (6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + DIAG[p[0]] * 7 * 6 + (DIAG[p[1]] - t) * 6
+            + (DIAG[p[2]] - l))
+
+
+def adjacent(a, b):
+    return a != b and abs((a & 7) - (b & 7)) <= 1 and abs((a >> 3) - (b >> 3)) <= 1
+
+
+def king_steps(s):
+    return [t for t in range(64) if adjacent(s, t)]
+
+
+def rook_steps(rook, blockers):
+    steps = []
+    for df, dr in ((1, 0), (-1, 0), (0, 1), (0, -1)):
+        f, r = (rook & 7) + df, (rook >> 3) + dr
+        while 0 <= f < 8 and 0 <= r < 8 and f + r * 8 not in blockers:
+            steps.append(f + r * 8)
+            f += df
+            r += dr
+    return steps
+
+
+# only the white king blocks, the black king is either the target or moving out of the way
+def is_attacked_by_rook(rook, target, white_king):
+    return target in rook_steps(rook, {white_king})
+
+
+def positions():
+    for wk in range(64):
+        for wr in range(64):
+            for bk in range(64):
+                if len({wk, wr, bk}) == 3 and not adjacent(wk, bk):
+                    yield wk, wr, bk
+
+
+# black's king moves, the rook counts as taken when the black king lands on it
+def black_moves(wk, wr, bk):
+    return [s for s in king_steps(bk)
+            if not adjacent(s, wk) and s != wk and (s == wr or not is_attacked_by_rook(wr, s, wk))]
+
+
+def black_to_move_wdl(wk, wr, bk):
+    moves = black_moves(wk, wr, bk)
+    if wr in moves:
+        return DRAW
+    if not moves and not is_attacked_by_rook(wr, bk, wk):
+        return DRAW
+    return LOSS
+
+
+# Plies to mate with White winning: the fewest for White to move, the most for Black. A capture of the
+# rook or a stalemate is a draw, and a mated king has 0 plies left.
+def plies_to_mate():
+    white, black = {}, {}
+    for position in positions():
+        if black_to_move_wdl(*position) == LOSS and not black_moves(*position):
+            black[position] = 0
+    level = dict(black)
+    plies = 0
+    while level:
+        plies += 1
+        next_level = {}
+        if plies % 2 == 1:
+            # white moves into a position already known to lose for black
+            for (wk, wr, bk) in level:
+                before = [(s, wr, bk) for s in king_steps(wk) if s != wr and not adjacent(s, bk)]
+                before += [(wk, s, bk) for s in rook_steps(wr, {wk, bk})]
+                for position in before:
+                    if position not in white and not is_attacked_by_rook(position[1], bk, position[0]):
+                        white[position] = plies
+                        next_level[position] = plies
+        else:
+            # black loses once every one of its moves leads to a win for white
+            for (wk, wr, bk) in level:
+                for s in king_steps(bk):
+                    position = (wk, wr, s)
+                    if s in (wk, wr) or adjacent(s, wk) or position in black:
+                        continue
+                    if black_to_move_wdl(*position) != LOSS:
+                        continue
+                    if all((wk, wr, t) in white for t in black_moves(*position)):
+                        black[position] = plies
+                        next_level[position] = plies
+        level = next_level
+    return white, black
+
+
+def sparse_index(block_count, per_block, span_log):
+    span = 1 << span_log
+    entries = []
+    for k in range((SIZE + span - 1) // span):
+        index = k * span + span // 2
+        # past the end it's still counted from the start of the last block, which is shorter
+        block = min(index // per_block, block_count - 1)
+        entries.append(struct.pack('<IH', block, index - block * per_block))
+    return b''.join(entries)
+
+
+# the fixed length symbols of values, packed into 64 byte blocks
+def compress(flags, values, symbol_values, symbol_length):
+    per_block = 512 // symbol_length
+    block_count = (SIZE + per_block - 1) // per_block
+    sizes = bytes([flags, 6, 9, 0]) + struct.pack('<I', block_count)
+    sizes += bytes([symbol_length, symbol_length])
+    sizes += struct.pack('<HH', 0, len(symbol_values))
+    for value in symbol_values:
+        sizes += bytes([value & 0xff, ((value >> 8) & 0xf) | 0xf0, 0xff])
+    sizes += b'\x00' * (len(symbol_values) & 1)
+    lengths = b''.join(struct.pack('<H', min(per_block, SIZE - b * per_block) - 1)
+                       for b in range(block_count))
+    blocks = b''
+    for b in range(block_count):
+        chunk = values[b * per_block:(b + 1) * per_block]
+        bits = ''.join(format(v, '0%db' % symbol_length) for v in chunk).ljust(512, '0')
+        blocks += int(bits, 2).to_bytes(64, 'big')
+    return sizes, sparse_index(block_count, per_block, 9), lengths, blocks
+
+
+def pad(data, alignment):
+    return data + b'\x00' * (-len(data) % alignment)
+
+
+def table_values(value_of):
+    values = [None] * SIZE
+    for position in positions():
+        value = value_of(position)
+        if value is None:
+            continue
+        index = encode(position)
+        assert values[index] in (None, value), (position, values[index], value)
+        values[index] = value
+    return [0 if value is None else value for value in values]
+
+
+def write_wdl(path):
+    # white to move always wins, black to move either loses or draws, one bit a value
+    values = table_values(lambda position: 1 if black_to_move_wdl(*position) == DRAW else 0)
+    sizes, sparse, lengths, blocks = compress(0, values, [LOSS, DRAW], 1)
+    data = pad(WDL_MAGIC + bytes([1, 0x00, 0x66, 0x44, 0xee]), 2)
+    data += bytes([0x80, 4]) + sizes + sparse + lengths
+    open(path, 'wb').write(pad(data, 64) + blocks)
+
+
+def write_dtz(path, white):
+    # white to move, in moves: a win in n plies, always odd, is stored as (n - 1) / 2 through the map
+    moves = table_values(lambda position: (white[position] - 1) // 2 if position in white else None)
+    by_count = sorted(set(moves), key=lambda value: (-moves.count(value), value))
+    symbols = [by_count.index(value) for value in moves]
+    sizes, sparse, lengths, blocks = compress(2, symbols, range(len(by_count)), 4)
+    data = pad(DTZ_MAGIC + bytes([0, 0x00, 0x66, 0x44, 0xee]), 2)
+    data += sizes
+    # the maps of wins, losses, cursed wins and blessed losses
+    data += pad(bytes([len(by_count)] + by_count + [0, 0, 0]), 2)
+    data += sparse + lengths
+    open(path, 'wb').write(pad(data, 64) + blocks)
+
+
+if __name__ == '__main__':
+    white, black = plies_to_mate()
+    legal_white = [p for p in positions() if not is_attacked_by_rook(p[1], p[2], p[0])]
+    assert all(p in white for p in legal_white)
+    print('longest win', max(white.values()), 'plies')
+    write_wdl('KRvK.rtbw')
+    write_dtz('KRvK.rtbz', white)
@@ -0,0 +1,157 @@
+use lil_chess::game_types::{BoardState, PlayerColor};
+use lil_chess::search::{CancellationToken, SearchLimits, Searcher, TABLEBASE_WIN_SCORE};
+use lil_chess::tablebase::{RootMove, Tablebases, Wdl, MAX_RANK};
+use std::sync::Arc;
+
+// KRvK.rtbw and KRvK.rtbz aren't the official Syzygy files, tests/syzygy/generate.py works them out
+// and writes them in the same layout, with every value a fixed length symbol and no pair compression:
+//   KRvK.rtbw  sha256 71c117d08207faefbd04574715e65b9760a983d5fd3617acd40539170499dd46
+//   KRvK.rtbz  sha256 47b3b91a1c8587757317da06520b3953d2ef41586264d108f3949d428125521f
+// The WDL table has White to move winning, and Black to move losing, or drawing for stalemates and
+// positions where the rook can be taken. The DTZ table has White to move, in moves through a map.
+const TABLEBASE_DIRECTORY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/syzygy");
+
+fn adjacent(a: usize, b: usize) -> bool {
+    (a % 8).max(b % 8) - (a % 8).min(b % 8) <= 1 && (a / 8).max(b / 8) - (a / 8).min(b / 8) <= 1
+}
+fn board(pieces: [(usize, char); 3], side_to_move: &str) -> BoardState {
+    let mut rows = Vec::new();
+    for rank in (0..8).rev() {
+        let mut row = String::new();
+        let mut empty = 0;
+        for file in 0..8 {
+            match pieces.iter().find(|(square, _)| *square == file + rank * 8) {
+                Some((_, letter)) => {
+                    if empty > 0 {
+                        row.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    row.push(*letter);
+                }
+                None => empty += 1,
+            }
+        }
+        if empty > 0 {
+            row.push_str(&empty.to_string());
+        }
+        rows.push(row);
+    }
+    BoardState::from_fen(&format!("{} {} - - 0 1", rows.join("/"), side_to_move)).unwrap()
+}
+// what the side without the rook gets out of the position, found by looking at its moves
+fn defending_result(board: &BoardState) -> Wdl {
+    let moves = board.get_all_legal_moves();
+    let can_take = moves.iter().any(|mv| board.is_capture(mv));
+    if can_take || (moves.is_empty() && !board.evaluate_is_checkmate()) {
+        Wdl::Draw
+    } else {
+        Wdl::Loss
+    }
+}
+
+#[test]
+fn finds_the_tables() {
+    assert_eq!(Tablebases::new(TABLEBASE_DIRECTORY).max_pieces(), 3);
+    let missing = Tablebases::new("no/such/directory");
+    assert_eq!(missing.max_pieces(), 0);
+    let board = board([(4, 'K'), (0, 'R'), (60, 'k')], "w");
+    assert_eq!(missing.probe_wdl(&board), None);
+}
+
+#[test]
+fn probes_every_position() {
+    let tablebases = Tablebases::new(TABLEBASE_DIRECTORY);
+    for king in 0..64 {
+        for rook in (0..64).filter(|rook| *rook != king) {
+            for other_king in 0..64 {
+                if other_king == rook || other_king == king || adjacent(king, other_king) {
+                    continue;
+                }
+                let black_to_move = board([(king, 'K'), (rook, 'R'), (other_king, 'k')], "b");
+                assert_eq!(
+                    tablebases.probe_wdl(&black_to_move),
+                    Some(defending_result(&black_to_move)),
+                    "'{}'",
+                    black_to_move.to_fen()
+                );
+                // and the same with the colors swapped, the table is looked up the other way round
+                let white_to_move = board([(king, 'k'), (rook, 'r'), (other_king, 'K')], "w");
+                assert_eq!(
+                    tablebases.probe_wdl(&white_to_move),
+                    Some(defending_result(&white_to_move)),
+                    "'{}'",
+                    white_to_move.to_fen()
+                );
+                if !black_to_move.evaluate_is_check(None) {
+                    let winning = board([(king, 'K'), (rook, 'R'), (other_king, 'k')], "w");
+                    assert_eq!(tablebases.probe_wdl(&winning), Some(Wdl::Win));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn dtz_counts_down_along_the_winning_line() {
+    let tablebases = Tablebases::new(TABLEBASE_DIRECTORY);
+    // one of the longest wins, mate in 16
+    let mut board = BoardState::from_fen("8/8/8/8/4k3/8/5R2/1K6 w - - 0 1").unwrap();
+    let mut dtz = tablebases.probe_dtz(&board).unwrap();
+    assert_eq!(dtz, 31);
+    while !board.evaluate_is_checkmate() {
+        let root_moves = tablebases.probe_root(&board).unwrap();
+        assert_eq!(root_moves[0].dtz, dtz, "'{}'", board.to_fen());
+        board.make_move(&root_moves[0].mv);
+        if board.evaluate_is_checkmate() {
+            assert_eq!(dtz, 1);
+            break;
+        }
+        // the quickest win and the slowest loss, a ply less either way
+        let next_dtz = tablebases.probe_dtz(&board).unwrap();
+        assert_eq!(next_dtz, -dtz + dtz.signum(), "'{}'", board.to_fen());
+        dtz = next_dtz;
+    }
+    assert_eq!(board.player_to_move, PlayerColor::BLACK);
+}
+
+#[test]
+fn root_moves_keep_the_win() {
+    let tablebases = Tablebases::new(TABLEBASE_DIRECTORY);
+    // the rook can go next to the black king, where it's lost
+    let board = BoardState::from_fen("8/8/8/3k4/R7/8/8/4K3 w - - 0 1").unwrap();
+    let root_moves = tablebases.probe_root(&board).unwrap();
+    assert_eq!(root_moves.len(), board.get_all_legal_moves().len());
+    let (wins, draws): (Vec<&RootMove>, Vec<&RootMove>) = root_moves
+        .iter()
+        .partition(|root_move| root_move.wdl == Wdl::Win);
+    let mut drawn_moves = draws
+        .iter()
+        .map(|root_move| root_move.mv.to_long_algebraic())
+        .collect::<Vec<String>>();
+    drawn_moves.sort();
+    assert_eq!(drawn_moves, ["a4c4", "a4d4", "a4e4"]);
+    assert!(draws.iter().all(|root_move| root_move.rank == 0));
+    // the wins first, the quickest ahead
+    assert!(wins.iter().all(|root_move| root_move.rank == MAX_RANK));
+    assert!(wins.windows(2).all(|pair| pair[0].dtz <= pair[1].dtz));
+    assert!(root_moves[..wins.len()]
+        .iter()
+        .all(|root_move| root_move.wdl == Wdl::Win));
+
+    // and the search plays the quickest one without searching
+    let mut searcher = Searcher::new(1);
+    searcher.tablebases = Some(Arc::new(Tablebases::new(TABLEBASE_DIRECTORY)));
+    let result = searcher.search(&board, &SearchLimits::depth(10), &CancellationToken::new());
+    assert_eq!(result.best_move, Some(root_moves[0].mv));
+    assert_eq!(result.score, TABLEBASE_WIN_SCORE - root_moves[0].dtz);
+    assert_eq!(result.nodes, 0);
+
+    // taking the rook is the only move that doesn't lose
+    let board = BoardState::from_fen("8/8/8/3k4/2R5/8/8/4K3 b - - 0 1").unwrap();
+    let root_moves = tablebases.probe_root(&board).unwrap();
+    assert_eq!(root_moves[0].mv.to_long_algebraic(), "d5c4");
+    assert_eq!((root_moves[0].wdl, root_moves[0].rank), (Wdl::Draw, 0));
+    assert!(root_moves[1..]
+        .iter()
+        .all(|root_move| root_move.wdl == Wdl::Loss && root_move.rank == -MAX_RANK));
+}
@@ -0,0 +1,74 @@
+use lil_chess::fen::STARTING_POSITION_FEN;
+use lil_chess::game_types::BoardState;
+use lil_chess::search::{CancellationToken, Clock, SearchLimits, Searcher};
+use std::time::Duration;
+
+fn clock(time_left: u64, increment: u64, moves_to_go: Option<u32>) -> Clock {
+    Clock {
+        time_left: Duration::from_millis(time_left),
+        increment: Duration::from_millis(increment),
+        moves_to_go,
+    }
+}
+fn millis(duration: Duration) -> u128 {
+    duration.as_millis()
+}
+
+#[test]
+fn allocation() {
+    // a share of the time left, less what it takes to send the move
+    assert_eq!(millis(clock(60_000, 0, None).allocate_time()), 1998);
+    assert_eq!(millis(clock(60_000, 0, Some(10)).allocate_time()), 5995);
+    // plus three quarters of the increment
+    assert_eq!(millis(clock(60_000, 2000, Some(10)).allocate_time()), 7495);
+    assert_eq!(millis(clock(60_000, 2000, None).allocate_time()), 3498);
+    // the last move before the time control gets all of it
+    assert_eq!(millis(clock(60_000, 0, Some(1)).allocate_time()), 59_950);
+    assert_eq!(millis(clock(60_000, 0, Some(0)).allocate_time()), 59_950);
+}
+
+#[test]
+fn allocation_never_exceeds_the_clock() {
+    assert_eq!(millis(clock(1000, 10_000, None).allocate_time()), 950);
+    assert_eq!(millis(clock(1000, 10_000, Some(1)).allocate_time()), 950);
+    assert_eq!(millis(clock(30, 0, None).allocate_time()), 0);
+    assert_eq!(millis(clock(0, 5000, Some(5)).allocate_time()), 0);
+}
+
+#[test]
+fn time_limit() {
+    assert_eq!(SearchLimits::depth(5).get_time_limit(), None);
+    let move_time = SearchLimits::move_time(Duration::from_millis(300));
+    assert_eq!(move_time.get_time_limit(), Some(Duration::from_millis(300)));
+    let on_the_clock = SearchLimits::clock(clock(60_000, 0, Some(10)));
+    assert_eq!(
+        on_the_clock.get_time_limit(),
+        Some(Duration::from_millis(5995))
+    );
+    // whichever is shorter
+    let both = SearchLimits {
+        move_time: Some(Duration::from_millis(300)),
+        ..on_the_clock.clone()
+    };
+    assert_eq!(both.get_time_limit(), Some(Duration::from_millis(300)));
+    let both = SearchLimits {
+        move_time: Some(Duration::from_secs(10)),
+        ..on_the_clock
+    };
+    assert_eq!(both.get_time_limit(), Some(Duration::from_millis(5995)));
+}
+
+#[test]
+fn search_keeps_to_the_clock() {
+    let board = BoardState::from_fen(STARTING_POSITION_FEN).unwrap();
+    let mut searcher = Searcher::new(1);
+    // 195 milliseconds, with plenty of leeway for a busy machine
+    let limits = SearchLimits::clock(clock(2000, 0, Some(10)));
+    let result = searcher.search(&board, &limits, &CancellationToken::new());
+    assert!(result.best_move.is_some());
+    assert!(
+        result.elapsed < Duration::from_secs(1),
+        "{:?}",
+        result.elapsed
+    );
+}
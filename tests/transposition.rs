@@ -0,0 +1,101 @@
+use lil_chess::fen::STARTING_POSITION_FEN;
+use lil_chess::game_types::BoardState;
+use lil_chess::search::MATE_SCORE;
+use lil_chess::transposition::{Bound, TranspositionTable};
+use std::thread;
+
+#[test]
+fn store_and_probe() {
+    let board = BoardState::from_fen(STARTING_POSITION_FEN).unwrap();
+    let mv = board.move_from_long_algebraic("e2e4").unwrap();
+    let table = TranspositionTable::new(1);
+    let key = board.hash();
+    assert!(table.probe(key, 0).is_none());
+    table.store(key, 7, 0, -35, Bound::Lower, Some(mv));
+    let entry = table.probe(key, 0).unwrap();
+    assert_eq!(entry.best_move, Some(mv));
+    assert_eq!(entry.score, -35);
+    assert_eq!(entry.depth, 7);
+    assert_eq!(entry.bound, Bound::Lower);
+    assert!(table.probe(key ^ 1, 0).is_none());
+    // a fail low keeps the best move of the earlier search
+    table.store(key, 8, 0, -60, Bound::Upper, None);
+    let entry = table.probe(key, 0).unwrap();
+    assert_eq!(entry.best_move, Some(mv));
+    assert_eq!(entry.bound, Bound::Upper);
+    table.clear();
+    assert!(table.probe(key, 0).is_none());
+}
+
+#[test]
+fn cutoff_scores() {
+    let table = TranspositionTable::new(1);
+    let cutoff = |bound, depth, alpha, beta| {
+        table.store(1, 5, 0, 100, bound, None);
+        table
+            .probe(1, 0)
+            .unwrap()
+            .get_cutoff_score(depth, alpha, beta)
+    };
+    assert_eq!(cutoff(Bound::Exact, 5, 0, 50), Some(100));
+    assert_eq!(cutoff(Bound::Exact, 6, 0, 50), None);
+    assert_eq!(cutoff(Bound::Lower, 4, 0, 100), Some(100));
+    assert_eq!(cutoff(Bound::Lower, 4, 0, 101), None);
+    assert_eq!(cutoff(Bound::Upper, 4, 100, 200), Some(100));
+    assert_eq!(cutoff(Bound::Upper, 4, 99, 200), None);
+}
+
+#[test]
+fn mate_scores_are_relative_to_the_ply() {
+    let table = TranspositionTable::new(1);
+    // mate in five plies from the root, found three plies in
+    table.store(1, 2, 3, MATE_SCORE - 5, Bound::Exact, None);
+    assert_eq!(table.probe(1, 3).unwrap().score, MATE_SCORE - 5);
+    assert_eq!(table.probe(1, 1).unwrap().score, MATE_SCORE - 3);
+    table.store(2, 2, 4, -MATE_SCORE + 6, Bound::Exact, None);
+    assert_eq!(table.probe(2, 0).unwrap().score, -MATE_SCORE + 2);
+    // other scores don't change
+    table.store(3, 2, 4, 250, Bound::Exact, None);
+    assert_eq!(table.probe(3, 9).unwrap().score, 250);
+}
+
+#[test]
+fn replacement() {
+    let table = TranspositionTable::new(1);
+    // both keys land on the same entry, only the high bits differ
+    let (first, second) = (0x1234, 0x1234 | 1 << 40);
+    table.store(first, 10, 0, 1, Bound::Exact, None);
+    table.store(second, 2, 0, 2, Bound::Exact, None);
+    assert_eq!(table.probe(first, 0).unwrap().score, 1);
+    assert!(table.probe(second, 0).is_none());
+    // a shallower entry from an earlier search does get replaced
+    table.new_search();
+    table.store(second, 2, 0, 2, Bound::Exact, None);
+    assert!(table.probe(first, 0).is_none());
+    assert_eq!(table.probe(second, 0).unwrap().score, 2);
+}
+
+// threads sharing a single entry never see one write's key with another write's data
+#[test]
+fn torn_entries_are_missed() {
+    let table = TranspositionTable::new(0);
+    let key_of = |i: u64| i.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+    let score_of = |key: u64| (key >> 48) as i32 % 20_000;
+    let depth_of = |key: u64| (key >> 40) as u32 % 64;
+    thread::scope(|scope| {
+        for thread_index in 0..4 {
+            let table = &table;
+            scope.spawn(move || {
+                for i in 0..200_000 {
+                    let key = key_of(i * 4 + thread_index);
+                    table.store(key, depth_of(key), 0, score_of(key), Bound::Exact, None);
+                    // the entry is often another thread's by now, or being written by one
+                    if let Some(entry) = table.probe(key, 0) {
+                        assert_eq!(entry.score, score_of(key));
+                        assert_eq!(entry.depth, depth_of(key));
+                    }
+                }
+            });
+        }
+    });
+}